@@ -0,0 +1,82 @@
+//! In-memory audit trail of performed actions
+
+use crate::protocol::{Action, ActionLogEntry, NodeId};
+use crate::redact::SECURE_VALUE_MASK;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Ring buffer of the most recent successful actions.
+///
+/// Once full, recording an action drops the oldest entry. A capacity of 0
+/// records nothing.
+pub(crate) struct ActionLog {
+    capacity: usize,
+    entries: Mutex<VecDeque<ActionLogEntry>>,
+}
+
+impl ActionLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Append an action, stamped with the current time.
+    ///
+    /// With `secure`, for an action on a secure text field, the text the
+    /// action carries and the previous value are masked.
+    pub fn record(
+        &self,
+        node_id: &NodeId,
+        action: &Action,
+        previous_value: Option<String>,
+        secure: bool,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mask = || SECURE_VALUE_MASK.to_string();
+        let (action, previous_value) = match action {
+            _ if !secure => (action.clone(), previous_value),
+            Action::SetValue { .. } => (
+                Action::SetValue { value: mask() },
+                previous_value.map(|_| mask()),
+            ),
+            Action::TypeText { .. } => (
+                Action::TypeText { text: mask() },
+                previous_value.map(|_| mask()),
+            ),
+            _ => (action.clone(), previous_value.map(|_| mask())),
+        };
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(ActionLogEntry {
+            timestamp_ms,
+            node_id: node_id.clone(),
+            action,
+            previous_value,
+        });
+    }
+
+    /// The last `limit` entries (all of them if `None`), oldest first
+    pub fn recent(&self, limit: Option<usize>) -> Vec<ActionLogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
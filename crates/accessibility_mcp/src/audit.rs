@@ -0,0 +1,187 @@
+//! Accessibility audits: where a node's name comes from, and problems across a tree
+
+use crate::platform::AccessibilityProvider;
+use crate::protocol::{Action, AuditIssue, NameAudit, NameSources, Node, NodeId};
+use std::collections::HashMap;
+
+/// Work out whether a node with `role` and these attributes has an accessible
+/// name, and which attribute supplies it.
+///
+/// Follows VoiceOver's order: the title, then a linked title element, then
+/// the description. Static text is named by its value. Help text is only a
+/// hint, so it never counts as a name.
+pub(crate) fn audit_name(role: &str, sources: NameSources) -> NameAudit {
+    let present = |s: &Option<String>| s.as_deref().is_some_and(|s| !s.trim().is_empty());
+
+    let name_source = if present(&sources.title) {
+        Some("AXTitle")
+    } else if sources.title_ui_element.is_some() {
+        Some("AXTitleUIElement")
+    } else if present(&sources.description) {
+        Some("AXDescription")
+    } else if role == "AXStaticText" && present(&sources.value) {
+        Some("AXValue")
+    } else {
+        None
+    };
+
+    let explanation = match name_source {
+        Some("AXTitle") => "Named by its title (AXTitle)".to_string(),
+        Some("AXTitleUIElement") => {
+            "Named by a linked label element (AXTitleUIElement)".to_string()
+        }
+        Some("AXDescription") => {
+            "Named by its description (AXDescription), as it has no title or linked label"
+                .to_string()
+        }
+        Some(_) => "Named by its text (AXValue), as static text is".to_string(),
+        None if present(&sources.help) => "No accessible name: it only has help text (AXHelp), \
+                                           which is read as a hint, not a name"
+            .to_string(),
+        None => "No accessible name: it has no title, linked label, or description".to_string(),
+    };
+
+    NameAudit {
+        has_accessible_name: name_source.is_some(),
+        name_source: name_source.map(str::to_string),
+        explanation,
+        sources,
+    }
+}
+
+/// Rule ids reported in [`AuditIssue::rule`]
+pub(crate) mod rules {
+    /// A control that can be acted on has no accessible name
+    pub const MISSING_NAME: &str = "missing-name";
+    /// An image or button whose label is absent or blank
+    pub const EMPTY_LABEL: &str = "empty-label";
+    /// A control with zero width or height, which can't be found by pointing
+    pub const ZERO_SIZE: &str = "zero-size";
+    /// One element listed at several places in the tree
+    pub const DUPLICATE_ID: &str = "duplicate-id";
+}
+
+/// Roles whose label is what identifies them, even when they can't be acted on
+const LABELLED_ROLES: &[&str] = &["AXImage", "AXButton"];
+
+/// Collects audit issues from the nodes of a traversal.
+///
+/// Feed it every node the walk visits, then take the issues with [`finish`](Self::finish).
+#[derive(Default)]
+pub(crate) struct TreeAudit {
+    issues: Vec<AuditIssue>,
+    /// Each child id and the parents that list it, in the order they were seen
+    parents: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl TreeAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check `node`, asking `provider` for the rest of its name sources when
+    /// the attributes it was read with don't name it
+    pub fn visit(&mut self, provider: &dyn AccessibilityProvider, node: &Node) {
+        let mut issue = |rule: &str, message: String| {
+            self.issues.push(AuditIssue {
+                node_id: node.id.clone(),
+                rule: rule.to_string(),
+                message,
+            })
+        };
+        let role = node.role.strip_prefix("AX").unwrap_or(&node.role);
+        let interactive = node.actions.iter().any(|a| !matches!(a, Action::Focus));
+
+        let sources = NameSources {
+            title: node.name.clone(),
+            description: node.description.clone(),
+            value: node.value.clone(),
+            ..NameSources::default()
+        };
+        let labelled = LABELLED_ROLES.contains(&node.role.as_str());
+        let named = audit_name(&node.role, sources).has_accessible_name
+            // Only nodes that would be flagged pay for the extra reads, which
+            // include the linked label a node's own attributes don't show
+            || ((labelled || interactive)
+                && provider
+                    .name_sources(&node.id)
+                    .is_ok_and(|sources| audit_name(&node.role, sources).has_accessible_name));
+        if !named {
+            if labelled {
+                issue(rules::EMPTY_LABEL, format!("{} has an empty label", role));
+            } else if interactive {
+                issue(
+                    rules::MISSING_NAME,
+                    format!("{} can be acted on but has no accessible name", role),
+                );
+            }
+        }
+
+        if let Some(bounds) = node.bounds {
+            if interactive && (bounds.width <= 0.0 || bounds.height <= 0.0) {
+                issue(
+                    rules::ZERO_SIZE,
+                    format!("{} is {}x{}", role, bounds.width, bounds.height),
+                );
+            }
+        }
+
+        for child in &node.children {
+            self.parents
+                .entry(child.clone())
+                .or_default()
+                .push(node.id.clone());
+        }
+    }
+
+    /// The issues found, in visiting order, followed by any duplicated ids
+    pub fn finish(mut self) -> Vec<AuditIssue> {
+        let mut duplicates: Vec<_> = self
+            .parents
+            .into_iter()
+            .filter(|(_, parents)| parents.len() > 1)
+            .collect();
+        duplicates.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        for (node_id, parents) in duplicates {
+            let parents: Vec<_> = parents.iter().map(NodeId::as_str).collect();
+            self.issues.push(AuditIssue {
+                node_id,
+                rule: rules::DUPLICATE_ID.to_string(),
+                message: format!(
+                    "Listed as a child {} times, under {}",
+                    parents.len(),
+                    parents.join(", ")
+                ),
+            });
+        }
+        self.issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_text_is_not_a_name() {
+        let help_only = NameSources {
+            help: Some("Saves the document".to_string()),
+            ..NameSources::default()
+        };
+        let audit = audit_name("AXButton", help_only);
+        assert!(!audit.has_accessible_name);
+        assert!(audit.explanation.contains("AXHelp"));
+
+        let text = NameSources {
+            value: Some("Ready".to_string()),
+            ..NameSources::default()
+        };
+        assert_eq!(
+            audit_name("AXStaticText", text.clone())
+                .name_source
+                .as_deref(),
+            Some("AXValue")
+        );
+        assert!(!audit_name("AXTextField", text).has_accessible_name);
+    }
+}
@@ -0,0 +1,122 @@
+//! One-shot command-line client for a running accessibility MCP server
+//!
+//! ```text
+//! a11y-mcp --port 3000 tree
+//! a11y-mcp --pid 4242 find "Save"
+//! a11y-mcp --addr 127.0.0.1:3000 action 0x6000012 press
+//! ```
+
+use accessibility_mcp::client::Client;
+use accessibility_mcp::{Action, NodeId};
+use anyhow::{Context, Result};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: a11y-mcp <CONNECTION> <COMMAND>
+
+Connection (one of):
+  --addr <HOST:PORT>    HTTP server address or base URL
+  --port <PORT>         HTTP server on 127.0.0.1
+  --socket <PATH>       Unix socket path
+  --pid <PID>           Unix socket of the process with this PID
+
+Commands:
+  tree                  Print the accessibility tree
+  get <ID>              Print a single node
+  find <NAME>           Find nodes whose name contains NAME
+  action <ID> <ACTION>  Perform an action. ACTION is a name (press, focus,
+                        increment, decrement, context_menu), a compact code
+                        (e.g. v:hello), or a JSON action object";
+
+enum Command {
+    Tree,
+    Get(NodeId),
+    Find(String),
+    Action(NodeId, Action),
+}
+
+fn parse_args(args: &[String]) -> Result<(Client, Command)> {
+    let mut client = None;
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--addr" => client = Some(Client::new(value()?.as_str())?),
+            "--port" => client = Some(Client::with_port(value()?.parse().context("Invalid port")?)),
+            #[cfg(unix)]
+            "--socket" => client = Some(Client::unix(value()?)),
+            #[cfg(unix)]
+            "--pid" => {
+                let pid = value()?.parse().context("Invalid PID")?;
+                client = Some(Client::unix(
+                    accessibility_mcp::Config::socket_path_for_pid(pid),
+                ));
+            }
+            _ => rest.push(arg.as_str()),
+        }
+    }
+
+    let client = client.context("No server given; pass --addr, --port, --socket, or --pid")?;
+    let command = match rest.as_slice() {
+        ["tree"] => Command::Tree,
+        ["get", id] => Command::Get(NodeId::from(*id)),
+        ["find", name] => Command::Find(name.to_string()),
+        ["action", id, action] => Command::Action(NodeId::from(*id), parse_action(action)?),
+        _ => anyhow::bail!("Unrecognized command: {}", rest.join(" ")),
+    };
+
+    Ok((client, command))
+}
+
+/// Accept a JSON action, a bare action name, or a compact code
+fn parse_action(text: &str) -> Result<Action> {
+    if text.starts_with('{') {
+        return serde_json::from_str(text).context("Invalid JSON action");
+    }
+
+    if let Ok(action) = serde_json::from_value(serde_json::json!({ "type": text })) {
+        return Ok(action);
+    }
+
+    Action::from_compact(text)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let (client, command) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("error: {:#}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match command {
+        Command::Tree => client.query_tree().await,
+        Command::Get(id) => client.get_node(&id).await,
+        Command::Find(name) => client.find_by_name(&name).await,
+        Command::Action(id, action) => client.perform_action(&id, action).await,
+    };
+
+    match result.and_then(|data| Ok(serde_json::to_string_pretty(&data)?)) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
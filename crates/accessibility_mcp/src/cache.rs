@@ -0,0 +1,102 @@
+//! Short-lived cache of fully-read nodes
+
+use crate::protocol::{Node, NodeId};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Caches nodes returned by the provider for a fixed time-to-live.
+///
+/// A zero TTL disables caching entirely. The cache also tracks the server
+/// epoch, which advances each time the cache is rebuilt.
+pub(crate) struct NodeCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<NodeId, (Instant, Node)>>,
+    epoch: AtomicU64,
+}
+
+impl NodeCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether nodes are cached at all (a non-zero TTL)
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Return the cached node if still fresh, otherwise fetch and cache it
+    pub fn get_or_fetch(
+        &self,
+        node_id: &NodeId,
+        fetch: impl FnOnce() -> Result<Node>,
+    ) -> Result<Node> {
+        if !self.is_enabled() {
+            return fetch();
+        }
+
+        if let Some((fetched_at, node)) = self.entries.lock().unwrap().get(node_id) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(node.clone());
+            }
+        }
+
+        let node = fetch()?;
+        let mut entries = self.entries.lock().unwrap();
+        // A miss already costs a provider read, so sweeping here is cheap by comparison
+        entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < self.ttl);
+        entries.insert(node_id.clone(), (Instant::now(), node.clone()));
+        Ok(node)
+    }
+
+    /// Drop a node from the cache, e.g. after an action may have changed it
+    pub fn invalidate(&self, node_id: &NodeId) {
+        self.entries.lock().unwrap().remove(node_id);
+    }
+
+    /// Drop every entry without starting a new epoch, e.g. after an action
+    /// that may have changed nodes other than its target
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Drop every entry and start a new epoch; returns the new epoch
+    pub fn rebuild(&self) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        self.epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// The current epoch
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::node;
+
+    #[test]
+    fn expired_entries_are_evicted_on_the_next_miss() {
+        let cache = NodeCache::new(Duration::from_millis(20));
+        cache
+            .get_or_fetch(&NodeId::from("a"), || Ok(node("a", "AXButton")))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+
+        cache
+            .get_or_fetch(&NodeId::from("b"), || Ok(node("b", "AXButton")))
+            .unwrap();
+        let entries = cache.entries.lock().unwrap();
+        assert!(!entries.contains_key(&NodeId::from("a")));
+        assert!(entries.contains_key(&NodeId::from("b")));
+    }
+}
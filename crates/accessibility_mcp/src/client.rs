@@ -0,0 +1,211 @@
+//! Typed client for talking to a running MCP server
+//!
+//! # Example
+//!
+//! ```no_run
+//! use accessibility_mcp::client::Client;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = Client::new("127.0.0.1:3000")?;
+//! let tree = client.query_tree().await?;
+//! println!("{:?}", tree);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::protocol::{
+    Action, MatchMode, Message, MessageContent, NodeId, Request, Response, ResponseData, SortOrder,
+    TraversalOrder,
+};
+use anyhow::{Context, Result};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Number of times a request is retried when the server can't be reached
+const CONNECT_RETRIES: usize = 3;
+
+/// Delay before the first reconnection attempt; doubles on each retry
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Client for a running server, over HTTP or a Unix socket.
+///
+/// Each request travels on its own HTTP exchange (or socket connection), so
+/// responses are always correlated with the request that produced them.
+/// HTTP connections are pooled; if the server restarts, the next request
+/// reconnects transparently.
+#[derive(Debug, Clone)]
+pub struct Client {
+    transport: Transport,
+}
+
+#[derive(Debug, Clone)]
+enum Transport {
+    Http {
+        url: String,
+        http: reqwest::Client,
+    },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Client {
+    /// Create a client for the server at `addr`.
+    ///
+    /// Accepts either `host:port` or a full `http://host:port` base URL.
+    /// `https://` URLs are refused: the server only speaks plain HTTP, and
+    /// the client is built without TLS support.
+    pub fn new(addr: impl Into<String>) -> Result<Self> {
+        let addr = addr.into();
+        if addr.starts_with("https://") {
+            anyhow::bail!(
+                "{} uses https, but the server only serves plain HTTP; use http:// \
+                 (or a Unix socket) instead",
+                addr
+            );
+        }
+        let base = if addr.starts_with("http://") {
+            addr
+        } else {
+            format!("http://{}", addr)
+        };
+        Ok(Self::http(&base))
+    }
+
+    /// Create a client for a server listening on the given local port
+    pub fn with_port(port: u16) -> Self {
+        Self::http(&format!("http://127.0.0.1:{}", port))
+    }
+
+    fn http(base: &str) -> Self {
+        Self {
+            transport: Transport::Http {
+                url: format!("{}/mcp", base.trim_end_matches('/')),
+                http: reqwest::Client::new(),
+            },
+        }
+    }
+
+    /// Create a client for a server's Unix socket (see [`Config::unix_socket`](crate::Config::unix_socket))
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Self {
+            transport: Transport::Unix(path.into()),
+        }
+    }
+
+    /// Query the accessibility tree from the root
+    pub async fn query_tree(&self) -> Result<ResponseData> {
+        self.send(Request::QueryTree {
+            max_depth: None,
+            max_nodes: None,
+            fields: None,
+            order: TraversalOrder::default(),
+            window_id: None,
+            deadline_ms: None,
+        })
+        .await
+    }
+
+    /// Get a specific node by ID
+    pub async fn get_node(&self, node_id: &NodeId) -> Result<ResponseData> {
+        self.send(Request::GetNode {
+            node_id: node_id.clone(),
+            fields: None,
+            include_children: true,
+            describe_actions: false,
+        })
+        .await
+    }
+
+    /// Find nodes by name (substring match)
+    pub async fn find_by_name(&self, name: &str) -> Result<ResponseData> {
+        self.send(Request::FindByName {
+            name: name.to_string(),
+            match_mode: MatchMode::default(),
+            order: TraversalOrder::default(),
+            window_id: None,
+            sort: SortOrder::default(),
+            deadline_ms: None,
+            include_children: false,
+        })
+        .await
+    }
+
+    /// Perform an action on a node
+    pub async fn perform_action(&self, node_id: &NodeId, action: Action) -> Result<ResponseData> {
+        self.send(Request::PerformAction {
+            node_id: node_id.clone(),
+            action,
+            idempotency_key: None,
+        })
+        .await
+    }
+
+    /// Send any request, returning the result data or the server's error
+    pub async fn send(&self, request: Request) -> Result<ResponseData> {
+        let message = Message::request(request);
+
+        let reply = match &self.transport {
+            Transport::Http { url, http } => send_http(http, url, &message).await?,
+            #[cfg(unix)]
+            Transport::Unix(path) => send_unix(path, &message).await?,
+        };
+
+        match reply.content {
+            MessageContent::Response(Response::Success { result }) => Ok(result),
+            MessageContent::Response(Response::Error { error }) => {
+                anyhow::bail!("{:?}: {}", error.code, error.message)
+            }
+            MessageContent::Request(_) => anyhow::bail!("Expected response, got request"),
+        }
+    }
+}
+
+async fn send_http(http: &reqwest::Client, url: &str, message: &Message) -> Result<Message> {
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+
+    let response = loop {
+        match http.post(url).json(message).send().await {
+            Ok(response) => break response,
+            // Only retry when the request never reached the server, so
+            // actions are never performed twice
+            Err(e) if e.is_connect() && attempt < CONNECT_RETRIES => {
+                tracing::debug!("Failed to connect to {}: {}; retrying", url, e);
+                attempt += 1;
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to reach {}", url)),
+        }
+    };
+
+    response
+        .json()
+        .await
+        .context("Failed to parse server response")
+}
+
+/// Send one request on a fresh connection and read back the single reply line
+#[cfg(unix)]
+async fn send_unix(path: &std::path::Path, message: &Message) -> Result<Message> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    let (read, mut write) = stream.into_split();
+
+    let frame = crate::transport::encode_frame(message)?;
+    write.write_all(&frame).await?;
+    write.flush().await?;
+
+    let mut line = String::new();
+    BufReader::new(read).read_line(&mut line).await?;
+    if line.is_empty() {
+        anyhow::bail!("Server closed the connection without replying");
+    }
+
+    serde_json::from_str(&line).context("Failed to parse server response")
+}
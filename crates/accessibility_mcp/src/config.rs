@@ -0,0 +1,198 @@
+//! Server configuration
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Options for [`start_mcp_server_with_config`](crate::start_mcp_server_with_config)
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The HTTP port to bind to. If 0, the OS will assign an arbitrary available port.
+    /// If the specified port is unavailable, successive ports up to port+100 are tried.
+    pub port: u16,
+    /// The address the HTTP server binds to; `127.0.0.1` if `None`.
+    ///
+    /// Binding anything but loopback lets other hosts on the network inspect
+    /// and drive the application, and is logged as a warning.
+    pub bind_addr: Option<IpAddr>,
+    /// Also serve newline-delimited JSON messages on this Unix domain socket.
+    ///
+    /// Any stale file at the path is replaced, and the file is removed on shutdown.
+    pub unix_socket: Option<PathBuf>,
+    /// Bind `unix_socket` in Linux's abstract namespace rather than on the filesystem.
+    ///
+    /// The path is used as the socket's name and no file is created, so
+    /// nothing is left behind if the process is killed. Other platforms
+    /// ignore this and bind the path as a regular file.
+    pub abstract_socket: bool,
+    /// Also serve newline-delimited JSON messages over a pair of named pipes,
+    /// for sandboxes that can share files but not sockets. Unix only.
+    pub fifo: Option<FifoPaths>,
+    /// How long `get_node` results are reused before asking the provider again.
+    ///
+    /// 0 (the default) disables the cache. Performing an action drops every
+    /// cached entry, since it may have changed more than its target.
+    pub node_cache_ttl_ms: u64,
+    /// Whether tree traversals descend into embedded web content.
+    ///
+    /// Web areas are still reported (with `is_web_content` set), but when
+    /// this is false their subtrees are skipped to keep trees manageable.
+    pub descend_into_web: bool,
+    /// Maximum number of simultaneous socket connections; unlimited if `None`.
+    ///
+    /// Connections beyond the limit receive a `transient` "server busy" error
+    /// and are closed. Does not apply to HTTP, where each request is independent.
+    pub max_connections: Option<usize>,
+    /// Splice anonymous single-child `AXGroup` wrappers out of traversals.
+    ///
+    /// The promoted child lists the groups it replaced in `collapsed_from`.
+    pub collapse_single_child_groups: bool,
+    /// How messages are delimited by [`run_stdio_server`](crate::run_stdio_server).
+    pub stdio_framing: StdioFraming,
+    /// How many performed actions `get_action_log` remembers. 0 (the
+    /// default) disables the log; enabling it costs a read of the node's
+    /// value before each action that may change it.
+    pub action_log_size: usize,
+    /// How the platform provider derives node ids.
+    pub id_scheme: IdScheme,
+    /// Replace the value of secure text fields (subrole `AXSecureTextField`)
+    /// with `"••••"`, so passwords never leave the machine.
+    pub redact_secure_fields: bool,
+    /// Roles whose values are always left out, e.g. `AXStaticText` for an app
+    /// that displays personal data. Nodes are still reported.
+    pub redact_roles: Vec<String>,
+    /// Strip control characters other than tab, newline, and carriage return
+    /// from names, values, descriptions, and other text before returning them,
+    /// for clients whose JSON handling chokes on them. Off by default.
+    pub sanitize_strings: bool,
+    /// Fill in `value` for nodes whose raw value says little: the selected
+    /// child's name for tab groups, lists, and radio groups, and `checked`,
+    /// `unchecked`, or `mixed` for checkboxes. Off by default, reporting
+    /// values as the platform does.
+    pub synthesize_container_values: bool,
+    /// Shut the server down, stdio included, once every inspected application
+    /// is found to have quit.
+    ///
+    /// Either way, requests made after an app quits fail with `app_terminated`.
+    pub shutdown_on_app_exit: bool,
+    /// Report each node's AccessKit role in `akit_role`.
+    ///
+    /// Requires the `accesskit` feature; ignored otherwise.
+    pub normalize_roles: bool,
+    /// Report each node's smallest dimension, and whether it falls below the
+    /// 44x44pt minimum target size, in `visual`.
+    ///
+    /// Requires the `visual` feature; ignored otherwise.
+    pub visual: bool,
+    /// Pretty-print JSON responses, for reading them by hand.
+    ///
+    /// Applies to HTTP and `Content-Length`-framed stdio only. Newline-delimited
+    /// streams (the Unix socket, the FIFO pair, and default stdio) always stay
+    /// compact, since a pretty response would span several lines and end its
+    /// frame early.
+    pub pretty_json: bool,
+    /// Refuse synthesized input (`press_at`, `type_text`) with a `transient`
+    /// error unless the inspected app is the frontmost one, since the OS
+    /// delivers it to whatever is in front. Actions performed through the
+    /// accessibility API itself, like `press`, are unaffected.
+    pub require_frontmost: bool,
+    /// On macOS, show the system prompt asking the user to grant accessibility
+    /// access if this process doesn't have it yet. Otherwise the server starts
+    /// anyway and requests fail until access is granted.
+    pub prompt_for_permission: bool,
+    /// Don't print the `[MCP] listening on ...` banners to stderr, for hosts
+    /// that parse stderr or expect silence. `tracing` logs are unaffected and
+    /// go wherever the host's subscriber sends them.
+    pub quiet: bool,
+    /// Hold the first requests for up to this long after startup until the
+    /// application's root has a window, since AccessKit may not have built
+    /// the tree yet when the server starts. 0 (the default) doesn't wait.
+    pub init_timeout_ms: u64,
+    /// Most nodes a single `perform_on_matches` request may act on. A query
+    /// matching more is refused outright rather than acting on some of them.
+    pub max_batch_actions: usize,
+    /// Longest any single request may wait, in milliseconds: larger
+    /// `timeout_ms` and `deadline_ms` values, and `capture_snapshots`'
+    /// `interval_ms`, are clamped to it.
+    pub max_wait_ms: u64,
+    /// Directory `export_tree` writes into; the request names a file relative
+    /// to it. `None` (the default) disables `export_tree`, since any web page
+    /// can reach the HTTP transport.
+    pub export_dir: Option<PathBuf>,
+}
+
+/// The named pipes of the FIFO transport, which must already exist (see `mkfifo(1)`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FifoPaths {
+    /// Clients write requests here, one per line
+    pub requests: PathBuf,
+    /// The server writes a response line here for each request, in order
+    pub responses: PathBuf,
+}
+
+/// How node ids are assigned to platform elements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdScheme {
+    /// The element's address, e.g. `0x6000012a4b40`
+    #[default]
+    Pointer,
+    /// Increasing integers in the order elements are first seen
+    Sequential,
+    /// The element's role path, e.g. `AXApplication/AXWindow[0]/AXButton[2]`
+    Structural,
+}
+
+/// Message framing for the stdio transport
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdioFraming {
+    /// One JSON message per line
+    #[default]
+    LineDelimited,
+    /// LSP-style `Content-Length: N\r\n\r\n` headers before each JSON body
+    ContentLength,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 0,
+            bind_addr: None,
+            unix_socket: None,
+            abstract_socket: false,
+            fifo: None,
+            node_cache_ttl_ms: 0,
+            descend_into_web: true,
+            max_connections: None,
+            collapse_single_child_groups: false,
+            stdio_framing: StdioFraming::LineDelimited,
+            action_log_size: 0,
+            id_scheme: IdScheme::Pointer,
+            redact_secure_fields: true,
+            redact_roles: Vec::new(),
+            sanitize_strings: false,
+            synthesize_container_values: false,
+            shutdown_on_app_exit: false,
+            normalize_roles: false,
+            visual: false,
+            pretty_json: false,
+            require_frontmost: false,
+            prompt_for_permission: false,
+            quiet: false,
+            init_timeout_ms: 0,
+            max_batch_actions: 50,
+            max_wait_ms: 60_000,
+            export_dir: None,
+        }
+    }
+}
+
+impl Config {
+    /// The conventional per-process socket path, `/tmp/accessibility_mcp_{PID}.sock`
+    pub fn default_socket_path() -> PathBuf {
+        Self::socket_path_for_pid(std::process::id())
+    }
+
+    /// The conventional socket path for another process, e.g. to connect to it
+    pub fn socket_path_for_pid(pid: u32) -> PathBuf {
+        PathBuf::from(format!("/tmp/accessibility_mcp_{}.sock", pid))
+    }
+}
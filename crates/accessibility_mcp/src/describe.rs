@@ -0,0 +1,225 @@
+//! One-sentence, human-readable summaries of nodes
+
+use crate::platform::parse_checked;
+use crate::protocol::{Action, Node, Rect};
+
+/// Summarize a node, e.g. "Enabled button labeled 'Save', located top-right."
+///
+/// `window` is the bounds of the window containing the node. The position
+/// is described relative to it, and left out when it isn't known.
+pub(crate) fn describe(node: &Node, window: Option<&Rect>) -> String {
+    let is_toggle = matches!(node.role.as_str(), "AXCheckBox" | "AXRadioButton");
+    let checked = node.checked.or_else(|| {
+        is_toggle
+            .then(|| parse_checked(node.value.as_deref()))
+            .flatten()
+    });
+
+    let mut words = Vec::new();
+    match node.enabled {
+        Some(true) => words.push("enabled".to_string()),
+        Some(false) => words.push("disabled".to_string()),
+        None => {}
+    }
+    match checked {
+        Some(true) => words.push("checked".to_string()),
+        Some(false) => words.push("unchecked".to_string()),
+        None => {}
+    }
+    words.push(role_words(&node.role));
+
+    let mut text = capitalize(&words.join(" "));
+
+    let non_empty = |s: &Option<String>| s.clone().filter(|s| !s.is_empty());
+    if let Some(name) = non_empty(&node.name) {
+        text.push_str(&format!(" labeled '{}'", name));
+    } else if let Some(description) = non_empty(&node.description) {
+        text.push_str(&format!(" described as '{}'", description));
+    }
+
+    // A toggle's value is its checked state, already covered above
+    if !is_toggle {
+        if let Some(value) = non_empty(&node.value_description).or_else(|| non_empty(&node.value)) {
+            text.push_str(&format!(" with value '{}'", value));
+        }
+    }
+
+    if let Some(region) = node.bounds.zip(window).and_then(|(b, w)| region(&b, w)) {
+        text.push_str(&format!(", located {}", region));
+    }
+
+    text.push('.');
+    text
+}
+
+/// The path to a node as its nodes' names joined with " > ", root first,
+/// e.g. "Main > Login > Submit". Nodes without a name show their role.
+pub(crate) fn breadcrumb(path: &[Node]) -> String {
+    path.iter()
+        .map(|node| match node.name.as_deref() {
+            Some(name) if !name.is_empty() => name,
+            _ => node.role.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+/// What performing `action` on a node with `role` does, e.g. "Move the
+/// slider up, increasing its value"
+pub(crate) fn describe_action(role: &str, action: &Action) -> String {
+    let noun = role_words(role);
+    match action {
+        Action::Focus => "Move keyboard focus to this element".to_string(),
+        Action::Press => match role {
+            "AXButton" => "Activate this button".to_string(),
+            "AXCheckBox" => "Toggle this check box".to_string(),
+            "AXRadioButton" => "Select this option".to_string(),
+            "AXMenuItem" | "AXMenuBarItem" => "Choose this menu item".to_string(),
+            "AXLink" => "Follow this link".to_string(),
+            "AXPopUpButton" | "AXMenuButton" => "Open this menu".to_string(),
+            _ => format!("Activate this {}", noun),
+        },
+        Action::Increment => match role {
+            "AXSlider" => "Move the slider up, increasing its value".to_string(),
+            _ => format!("Increase the value of this {} by one step", noun),
+        },
+        Action::Decrement => match role {
+            "AXSlider" => "Move the slider down, decreasing its value".to_string(),
+            _ => format!("Decrease the value of this {} by one step", noun),
+        },
+        Action::SetValue { .. } => {
+            if crate::platform::TEXT_ROLES.contains(&role) {
+                "Replace the text in this field".to_string()
+            } else {
+                format!("Set the value of this {}", noun)
+            }
+        }
+        Action::Scroll { .. } => format!("Scroll the content of this {}", noun),
+        Action::ContextMenu => "Open the context menu for this element".to_string(),
+        Action::Custom { name } => format!("Perform the platform action '{}'", name),
+        Action::SetChecked { .. } => format!("Check or uncheck this {}", noun),
+        Action::PressAt { .. } => format!("Click at a point inside this {}", noun),
+        Action::TypeText { .. } => "Focus this element and type text into it".to_string(),
+    }
+}
+
+/// "AXCheckBox" -> "check box"; roles without the AX prefix are split the same way
+fn role_words(role: &str) -> String {
+    let role = role.strip_prefix("AX").unwrap_or(role);
+    let mut words = String::new();
+    for (i, c) in role.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            words.push(' ');
+        }
+        words.extend(c.to_lowercase());
+    }
+    words
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Which ninth of the window the center of `bounds` falls in, e.g. "top-right" or "center"
+fn region(bounds: &Rect, window: &Rect) -> Option<&'static str> {
+    if window.width <= 0.0 || window.height <= 0.0 {
+        return None;
+    }
+
+    let fx = (bounds.x + bounds.width / 2.0 - window.x) / window.width;
+    let fy = (bounds.y + bounds.height / 2.0 - window.y) / window.height;
+    let third = |f: f64| match f {
+        f if f < 1.0 / 3.0 => 0,
+        f if f < 2.0 / 3.0 => 1,
+        _ => 2,
+    };
+
+    Some(match (third(fy), third(fx)) {
+        (0, 0) => "top-left",
+        (0, 1) => "top",
+        (0, _) => "top-right",
+        (1, 0) => "left",
+        (1, 1) => "center",
+        (1, _) => "right",
+        (_, 0) => "bottom-left",
+        (_, 1) => "bottom",
+        (_, _) => "bottom-right",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::node;
+
+    const WINDOW: Rect = Rect {
+        x: 100.0,
+        y: 100.0,
+        width: 900.0,
+        height: 600.0,
+    };
+
+    fn at(x: f64, y: f64) -> Option<Rect> {
+        Some(Rect {
+            x,
+            y,
+            width: 60.0,
+            height: 20.0,
+        })
+    }
+
+    #[test]
+    fn describes_button() {
+        let button = Node {
+            name: Some("Save".to_string()),
+            enabled: Some(true),
+            bounds: at(900.0, 120.0),
+            ..node("save", "AXButton")
+        };
+        assert_eq!(
+            describe(&button, Some(&WINDOW)),
+            "Enabled button labeled 'Save', located top-right."
+        );
+    }
+
+    #[test]
+    fn describes_disabled_checkbox() {
+        let checkbox = Node {
+            name: Some("Remember me".to_string()),
+            value: Some("0".to_string()),
+            enabled: Some(false),
+            bounds: at(120.0, 650.0),
+            ..node("remember", "AXCheckBox")
+        };
+        assert_eq!(
+            describe(&checkbox, Some(&WINDOW)),
+            "Disabled unchecked check box labeled 'Remember me', located bottom-left."
+        );
+    }
+
+    #[test]
+    fn describes_slider_with_value() {
+        let slider = Node {
+            name: Some("Volume".to_string()),
+            value: Some("0.5".to_string()),
+            value_description: Some("50%".to_string()),
+            enabled: Some(true),
+            bounds: at(520.0, 390.0),
+            ..node("volume", "AXSlider")
+        };
+        assert_eq!(
+            describe(&slider, Some(&WINDOW)),
+            "Enabled slider labeled 'Volume' with value '50%', located center."
+        );
+
+        // Without a window there is nothing to place it relative to
+        assert_eq!(
+            describe(&slider, None),
+            "Enabled slider labeled 'Volume' with value '50%'."
+        );
+    }
+}
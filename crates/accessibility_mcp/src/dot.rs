@@ -0,0 +1,114 @@
+//! Rendering of walked trees as GraphViz DOT, for documentation and debugging
+
+use crate::protocol::Node;
+use std::collections::HashSet;
+
+/// Render `nodes` as a DOT digraph, labeling each node with its role and name.
+///
+/// Edges are drawn from each node to those of its children that are also in
+/// `nodes`, so a depth-limited walk renders without dangling edges. A walk
+/// cut short by its node limit is `truncated`, which the graph says in a
+/// comment and in its label.
+pub(crate) fn render_dot(nodes: &[Node], truncated: bool) -> String {
+    let present: HashSet<_> = nodes.iter().map(|node| &node.id).collect();
+
+    let mut dot = String::from("digraph accessibility {\n    node [shape=box];\n");
+    if truncated {
+        let note = format!("Truncated after {} nodes", nodes.len());
+        dot.push_str(&format!("    // {}\n", note));
+        dot.push_str(&format!("    label={};\n    labelloc=b;\n", quote(&note)));
+    }
+    for node in nodes {
+        let label = match node.name.as_deref().filter(|name| !name.is_empty()) {
+            Some(name) => format!("{} '{}'", node.role, name),
+            None => node.role.clone(),
+        };
+        dot.push_str(&format!(
+            "    {} [label={}];\n",
+            quote(node.id.as_str()),
+            quote(&label)
+        ));
+    }
+    for node in nodes {
+        for child in node.children.iter().filter(|child| present.contains(child)) {
+            dot.push_str(&format!(
+                "    {} -> {};\n",
+                quote(node.id.as_str()),
+                quote(child.as_str())
+            ));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// A DOT quoted string
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, sample_tree};
+    use crate::platform::AccessibilityProvider;
+    use crate::protocol::TraversalOrder;
+    use crate::traversal::Walk;
+
+    fn walked(max_depth: Option<usize>) -> Vec<Node> {
+        let provider = sample_tree();
+        let mut walk = Walk::new(TraversalOrder::DocumentOrder);
+        walk.max_depth = max_depth;
+        let mut nodes = Vec::new();
+        walk.run(&provider, provider.get_root().unwrap(), |n, _| {
+            nodes.push(n.clone())
+        });
+        nodes
+    }
+
+    #[test]
+    fn renders_labels_and_parent_child_edges() {
+        let dot = render_dot(&walked(None), false);
+        assert!(!dot.contains("Truncated"));
+
+        assert!(dot.starts_with("digraph accessibility {"));
+        assert!(dot.contains(r#""a1" [label="AXGroup 'a1'"];"#));
+        assert!(dot.contains(r#""root" -> "a";"#));
+        assert!(dot.contains(r#""b" -> "b1";"#));
+        assert_eq!(dot.matches(" -> ").count(), 5);
+
+        // Children beyond the depth limit get no edge
+        let shallow = render_dot(&walked(Some(1)), false);
+        assert_eq!(shallow.matches(" -> ").count(), 2);
+    }
+
+    #[test]
+    fn escapes_quotes_in_labels() {
+        let quoted = Node {
+            name: Some("Say \"hi\"".to_string()),
+            ..node("q", "AXButton")
+        };
+        let dot = render_dot(&[quoted], false);
+        assert!(dot.contains(r#""q" [label="AXButton 'Say \"hi\"'"];"#));
+    }
+
+    #[test]
+    fn truncated_graphs_say_so() {
+        let dot = render_dot(&walked(None), true);
+        assert!(dot.contains("    // Truncated after 6 nodes\n"));
+        assert!(dot.contains(r#"    label="Truncated after 6 nodes";"#));
+    }
+}
@@ -0,0 +1,149 @@
+//! Replay of recent action results for retried requests
+
+use crate::protocol::{Action, NodeId, Response};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long an idempotency key is remembered after its action succeeds
+pub(crate) const IDEMPOTENCY_TTL: Duration = Duration::from_secs(60);
+
+/// What a remembered response answers: the client's key, scoped to the
+/// target, node and action it was sent with, so a key reused for a different
+/// action performs it rather than replaying an unrelated result
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct IdempotencyKey {
+    pub target: String,
+    pub node_id: NodeId,
+    /// The action's JSON form, since actions can hold floats
+    pub action: String,
+    pub key: String,
+}
+
+impl IdempotencyKey {
+    pub fn new(target: &str, node_id: &NodeId, action: &Action, key: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            node_id: node_id.clone(),
+            action: serde_json::to_string(action).unwrap_or_default(),
+            key: key.to_string(),
+        }
+    }
+}
+
+/// The successful response for one key, once there is one
+type Slot = tokio::sync::Mutex<Option<(Instant, Response)>>;
+
+/// Successful responses by the idempotency key of the request that produced them.
+///
+/// Only successes are remembered, so a failed action can be retried with the
+/// same key.
+pub(crate) struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<IdempotencyKey, Arc<Slot>>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `perform` unless `key` succeeded within the TTL, in which case
+    /// return that earlier response instead.
+    ///
+    /// Requests with the same key run one at a time, so a retry that arrives
+    /// while the original is still being performed waits for it rather than
+    /// racing it. Requests with different keys don't wait for each other.
+    pub async fn run_once(
+        &self,
+        key: IdempotencyKey,
+        perform: impl Future<Output = Response>,
+    ) -> Response {
+        let slot = {
+            let mut entries = self.entries.lock().unwrap();
+            // A slot someone else holds is in use; otherwise it's kept only
+            // while its response is fresh
+            entries.retain(|_, slot| {
+                Arc::strong_count(slot) > 1
+                    || slot.try_lock().is_ok_and(|done| self.is_fresh(&done))
+            });
+            entries.entry(key).or_default().clone()
+        };
+
+        let mut done = slot.lock().await;
+        if self.is_fresh(&done) {
+            if let Some((_, response)) = done.as_ref() {
+                return response.clone();
+            }
+        }
+
+        let response = perform.await;
+        *done = matches!(response, Response::Success { .. })
+            .then(|| (Instant::now(), response.clone()));
+        response
+    }
+
+    fn is_fresh(&self, done: &Option<(Instant, Response)>) -> bool {
+        done.as_ref()
+            .is_some_and(|(seen_at, _)| seen_at.elapsed() < self.ttl)
+    }
+
+    /// Number of keys currently remembered or in flight
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ResponseData;
+
+    fn key(name: &str) -> IdempotencyKey {
+        IdempotencyKey::new("app", &NodeId::from("ok"), &Action::Press, name)
+    }
+
+    async fn pressed() -> Response {
+        Response::Success {
+            result: ResponseData::ActionResult {
+                success: true,
+                new_value: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_keys_are_evicted() {
+        let cache = IdempotencyCache::new(Duration::ZERO);
+        cache.run_once(key("first"), pressed()).await;
+        cache.run_once(key("second"), pressed()).await;
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn different_keys_run_concurrently() {
+        let cache = Arc::new(IdempotencyCache::new(IDEMPOTENCY_TTL));
+        let (release, held) = tokio::sync::oneshot::channel::<()>();
+        let slow = tokio::spawn({
+            let cache = cache.clone();
+            async move {
+                let perform = async {
+                    let _ = held.await;
+                    pressed().await
+                };
+                cache.run_once(key("slow"), perform).await
+            }
+        });
+        tokio::task::yield_now().await;
+
+        // Would wait forever if the slow action held the whole cache
+        cache.run_once(key("quick"), pressed()).await;
+        release.send(()).unwrap();
+        slow.await.unwrap();
+    }
+}
@@ -17,12 +17,32 @@
 //! }
 //! ```
 
+mod action_log;
+mod audit;
+mod cache;
+pub mod client;
+mod config;
+mod describe;
+mod dot;
+mod idempotency;
 pub mod platform;
 pub mod protocol;
+mod redact;
+#[cfg(feature = "accesskit")]
+mod roles;
 mod server;
+mod synthesize;
+mod transport;
+mod traversal;
+mod visibility;
 
+pub use config::{Config, FifoPaths, IdScheme, StdioFraming};
 pub use protocol::{Action, Node, NodeId, Rect};
-pub use server::{start_all, start_mcp_server, McpHandle};
+pub use server::{
+    run_stdio_server, start_all, start_mcp_server, start_mcp_server_with_async_provider,
+    start_mcp_server_with_config, start_mcp_server_with_provider, start_mcp_server_with_providers,
+    McpHandle,
+};
 
 #[cfg(test)]
 mod tests {
@@ -39,6 +59,7 @@ mod tests {
             bounds: None,
             actions: vec![Action::Press],
             children: vec![],
+            ..Default::default()
         };
 
         assert_eq!(node.id.as_str(), "test-id");
@@ -60,6 +81,9 @@ mod tests {
 
         let request = Request::GetNode {
             node_id: NodeId::from("test-123"),
+            fields: None,
+            include_children: true,
+            describe_actions: false,
         };
 
         let message = Message::request(request);
@@ -83,6 +107,7 @@ mod tests {
             bounds: None,
             actions: vec![Action::Press],
             children: vec![],
+            ..Default::default()
         };
 
         let response = Response::Success {
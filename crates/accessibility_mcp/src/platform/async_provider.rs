@@ -0,0 +1,587 @@
+//! Async counterpart of [`AccessibilityProvider`], and adapters between the two
+//!
+//! Backends built on async APIs (such as AT-SPI over D-Bus) implement
+//! [`AsyncAccessibilityProvider`] and are served through [`SyncAdapter`].
+//! Async code that needs to call a blocking backend wraps it in
+//! [`BlockingAdapter`], which moves each call onto Tokio's blocking pool.
+
+use super::{defaults, AccessibilityProvider, NodeFields};
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// An accessibility backend whose calls are asynchronous.
+///
+/// Mirrors [`AccessibilityProvider`] method for method, with the same
+/// defaults; those that do more than forward share their logic with it.
+#[async_trait]
+pub trait AsyncAccessibilityProvider: Send + Sync {
+    /// Get the root accessibility node for this process
+    async fn get_root(&self) -> Result<Node>;
+
+    /// Get all children of a given node
+    async fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>>;
+
+    /// Get a specific node by ID
+    async fn get_node(&self, node_id: &NodeId) -> Result<Node>;
+
+    /// Get a node, reading only the given attributes where the platform allows.
+    ///
+    /// Defaults to [`get_node`](Self::get_node), which reads everything.
+    async fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        let _ = fields;
+        self.get_node(node_id).await
+    }
+
+    /// Perform an accessibility action on a node
+    async fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()>;
+
+    /// Re-read the attributes of a node that has already been resolved.
+    ///
+    /// Defaults to [`get_node`](Self::get_node).
+    async fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        self.get_node(node_id).await
+    }
+
+    /// Get a node's parent; `None` for the root.
+    ///
+    /// Defaults to searching down from the root, giving up with an error
+    /// after [`DEFAULT_MAX_NODES`](crate::traversal::DEFAULT_MAX_NODES) nodes.
+    async fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let mut search = defaults::ParentSearch::new(node_id, self.get_root().await?);
+        loop {
+            match search.next()? {
+                ControlFlow::Continue(id) => search.push_children(self.get_children(&id).await?),
+                ControlFlow::Break(parent) => return Ok(parent),
+            }
+        }
+    }
+
+    /// Get the node that currently has keyboard focus, if the platform reports one
+    async fn get_focused(&self) -> Result<Option<Node>> {
+        Ok(None)
+    }
+
+    /// Get the window that holds `node_id`, or the node itself if it is a window.
+    ///
+    /// Defaults to walking up through [`get_parent`](Self::get_parent).
+    async fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let mut walk = defaults::WindowWalk::default();
+        let mut node = self.get_node(node_id).await?;
+        loop {
+            match walk.step(node) {
+                ControlFlow::Continue(id) => match self.get_parent(&id).await? {
+                    Some(parent) => node = parent,
+                    None => return Ok(None),
+                },
+                ControlFlow::Break(window) => return Ok(window),
+            }
+        }
+    }
+
+    /// Get the window that holds keyboard focus, if any.
+    ///
+    /// Defaults to the window holding [`get_focused`](Self::get_focused)'s node.
+    async fn get_focused_window(&self) -> Result<Option<Node>> {
+        match self.get_focused().await? {
+            Some(focused) => self.get_window(&focused.id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Get the application's menu bar, if it has one.
+    ///
+    /// Defaults to the first child of the root with role `AXMenuBar`.
+    async fn get_menu_bar(&self) -> Result<Option<Node>> {
+        let root = self.get_root().await?;
+        let children = self.get_children(&root.id).await?;
+        Ok(defaults::menu_bar(children))
+    }
+
+    /// The mouse cursor's position in screen coordinates; `None` if the
+    /// platform can't tell
+    async fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        Ok(None)
+    }
+
+    /// The deepest node at a screen point; `None` if there is none or the
+    /// platform can't hit-test
+    async fn node_at_point(&self, _x: f64, _y: f64) -> Result<Option<Node>> {
+        Ok(None)
+    }
+
+    /// Whether the application is the frontmost one.
+    ///
+    /// Defaults to `true`, for platforms that can't tell.
+    async fn is_frontmost_app(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Whether `node_id` still refers to a live element.
+    ///
+    /// Defaults to whether the node can be read at all.
+    async fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        Ok(self
+            .get_node_fields(node_id, NodeFields::NONE)
+            .await
+            .is_ok())
+    }
+
+    /// Up to `length` UTF-16 code units of a text element's value from
+    /// `offset`, with the whole value's length; `None` if the platform can't
+    /// read ranges of this element
+    async fn text_range(
+        &self,
+        _node_id: &NodeId,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        Ok(None)
+    }
+
+    /// The language and region codes of the application's locale
+    async fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        Ok((None, None))
+    }
+
+    /// Give a node keyboard focus.
+    ///
+    /// Defaults to performing [`Action::Focus`] on it.
+    async fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        self.perform_action(node_id, &Action::Focus).await
+    }
+
+    /// The text selected in a text element, if any
+    async fn selected_text(&self, _node_id: &NodeId) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Bring a window to the front.
+    ///
+    /// Defaults to performing [`Action::Focus`] on it.
+    async fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        self.perform_action(window_id, &Action::Focus).await
+    }
+
+    /// The attributes that could give a node its accessible name.
+    ///
+    /// Defaults to the node's name (as the title), description, and value.
+    async fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        Ok(defaults::name_sources(self.get_node(node_id).await?))
+    }
+
+    /// The roles this backend knows. Defaults to none.
+    async fn roles(&self) -> Vec<RoleEntry> {
+        Vec::new()
+    }
+
+    /// The platform's own names for the actions a node supports; `None` if
+    /// the platform can't list them
+    async fn native_action_names(&self, _node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+
+    /// Wait until a node's value changes or `timeout` passes, returning
+    /// whether it changed; `None` if the platform can't be notified of changes
+    async fn wait_for_value_change(
+        &self,
+        _node_id: &NodeId,
+        _timeout: Duration,
+    ) -> Result<Option<bool>> {
+        Ok(None)
+    }
+}
+
+/// Serves a blocking [`AccessibilityProvider`] as an async one, running each
+/// call on Tokio's blocking thread pool so it never stalls the runtime.
+pub struct BlockingAdapter<P: ?Sized> {
+    inner: Arc<P>,
+}
+
+impl<P: AccessibilityProvider + ?Sized + 'static> BlockingAdapter<P> {
+    pub fn new(inner: Arc<P>) -> Self {
+        Self { inner }
+    }
+
+    /// Run `call` against the provider on the blocking pool
+    async fn run<T, F>(&self, call: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&P) -> Result<T> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || call(&inner)).await?
+    }
+}
+
+#[async_trait]
+impl<P: AccessibilityProvider + ?Sized + 'static> AsyncAccessibilityProvider
+    for BlockingAdapter<P>
+{
+    async fn get_root(&self) -> Result<Node> {
+        self.run(|p| p.get_root()).await
+    }
+
+    async fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.get_children(&node_id)).await
+    }
+
+    async fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.get_node(&node_id)).await
+    }
+
+    async fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.get_node_fields(&node_id, fields)).await
+    }
+
+    async fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        let (node_id, action) = (node_id.clone(), action.clone());
+        self.run(move |p| p.perform_action(&node_id, &action)).await
+    }
+
+    async fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.refresh(&node_id)).await
+    }
+
+    async fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.get_parent(&node_id)).await
+    }
+
+    async fn get_focused(&self) -> Result<Option<Node>> {
+        self.run(|p| p.get_focused()).await
+    }
+
+    async fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.get_window(&node_id)).await
+    }
+
+    async fn get_focused_window(&self) -> Result<Option<Node>> {
+        self.run(|p| p.get_focused_window()).await
+    }
+
+    async fn get_menu_bar(&self) -> Result<Option<Node>> {
+        self.run(|p| p.get_menu_bar()).await
+    }
+
+    async fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        self.run(|p| p.cursor_position()).await
+    }
+
+    async fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        self.run(move |p| p.node_at_point(x, y)).await
+    }
+
+    async fn is_frontmost_app(&self) -> Result<bool> {
+        self.run(|p| p.is_frontmost_app()).await
+    }
+
+    async fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.is_alive(&node_id)).await
+    }
+
+    async fn text_range(
+        &self,
+        node_id: &NodeId,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.text_range(&node_id, offset, length))
+            .await
+    }
+
+    async fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        self.run(|p| p.locale()).await
+    }
+
+    async fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.set_keyboard_focus(&node_id)).await
+    }
+
+    async fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.selected_text(&node_id)).await
+    }
+
+    async fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        let window_id = window_id.clone();
+        self.run(move |p| p.focus_window(&window_id)).await
+    }
+
+    async fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.name_sources(&node_id)).await
+    }
+
+    async fn roles(&self) -> Vec<RoleEntry> {
+        self.run(|p| Ok(p.roles())).await.unwrap_or_default()
+    }
+
+    async fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.native_action_names(&node_id)).await
+    }
+
+    async fn wait_for_value_change(
+        &self,
+        node_id: &NodeId,
+        timeout: Duration,
+    ) -> Result<Option<bool>> {
+        let node_id = node_id.clone();
+        self.run(move |p| p.wait_for_value_change(&node_id, timeout))
+            .await
+    }
+}
+
+/// Serves an [`AsyncAccessibilityProvider`] where a blocking provider is
+/// expected, such as to the server's tree walks.
+///
+/// The async provider runs on a runtime owned by the adapter, and each call
+/// blocks the calling thread until it finishes. That works from any thread:
+/// outside a runtime, on the blocking pool where the server handles
+/// requests, and even on a current-thread runtime's only worker.
+pub struct SyncAdapter {
+    inner: Arc<dyn AsyncAccessibilityProvider>,
+    /// Always `Some` until dropped
+    runtime: Option<Runtime>,
+}
+
+impl SyncAdapter {
+    /// Wrap `inner`, starting the adapter's runtime
+    pub fn new(inner: Box<dyn AsyncAccessibilityProvider>) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("a11y-async-provider")
+            .enable_all()
+            .build()
+            .expect("Failed to create Tokio runtime");
+        Self {
+            inner: inner.into(),
+            runtime: Some(runtime),
+        }
+    }
+
+    /// Run the future `call` makes on the adapter's runtime and wait for it
+    fn block_on<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<dyn AsyncAccessibilityProvider>) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let runtime = self
+            .runtime
+            .as_ref()
+            .expect("runtime is only taken on drop");
+        let future = call(self.inner.clone());
+        let (tx, rx) = mpsc::sync_channel(1);
+        runtime.spawn(async move {
+            let _ = tx.send(future.await);
+        });
+        rx.recv().context("async provider call panicked")?
+    }
+}
+
+impl Drop for SyncAdapter {
+    fn drop(&mut self) {
+        // A plain drop waits for the runtime's workers, which panics when
+        // dropped from async code
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl AccessibilityProvider for SyncAdapter {
+    fn get_root(&self) -> Result<Node> {
+        self.block_on(|p| async move { p.get_root().await })
+    }
+
+    fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.get_children(&node_id).await })
+    }
+
+    fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.get_node(&node_id).await })
+    }
+
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.get_node_fields(&node_id, fields).await })
+    }
+
+    fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        let (node_id, action) = (node_id.clone(), action.clone());
+        self.block_on(|p| async move { p.perform_action(&node_id, &action).await })
+    }
+
+    fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.refresh(&node_id).await })
+    }
+
+    fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.get_parent(&node_id).await })
+    }
+
+    fn get_focused(&self) -> Result<Option<Node>> {
+        self.block_on(|p| async move { p.get_focused().await })
+    }
+
+    fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.get_window(&node_id).await })
+    }
+
+    fn get_focused_window(&self) -> Result<Option<Node>> {
+        self.block_on(|p| async move { p.get_focused_window().await })
+    }
+
+    fn get_menu_bar(&self) -> Result<Option<Node>> {
+        self.block_on(|p| async move { p.get_menu_bar().await })
+    }
+
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        self.block_on(|p| async move { p.cursor_position().await })
+    }
+
+    fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        self.block_on(|p| async move { p.node_at_point(x, y).await })
+    }
+
+    fn is_frontmost_app(&self) -> Result<bool> {
+        self.block_on(|p| async move { p.is_frontmost_app().await })
+    }
+
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.is_alive(&node_id).await })
+    }
+
+    fn text_range(
+        &self,
+        node_id: &NodeId,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.text_range(&node_id, offset, length).await })
+    }
+
+    fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        self.block_on(|p| async move { p.locale().await })
+    }
+
+    fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.set_keyboard_focus(&node_id).await })
+    }
+
+    fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.selected_text(&node_id).await })
+    }
+
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        let window_id = window_id.clone();
+        self.block_on(|p| async move { p.focus_window(&window_id).await })
+    }
+
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.name_sources(&node_id).await })
+    }
+
+    fn roles(&self) -> Vec<RoleEntry> {
+        self.block_on(|p| async move { Ok(p.roles().await) })
+            .unwrap_or_default()
+    }
+
+    fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.native_action_names(&node_id).await })
+    }
+
+    fn wait_for_value_change(&self, node_id: &NodeId, timeout: Duration) -> Result<Option<bool>> {
+        let node_id = node_id.clone();
+        self.block_on(|p| async move { p.wait_for_value_change(&node_id, timeout).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{sample_tree, MockProvider};
+
+    #[tokio::test]
+    async fn blocking_adapter_runs_sync_providers() {
+        let mock = sample_tree();
+        let provider = BlockingAdapter::new(Arc::new(mock.clone()));
+
+        let root = provider.get_root().await.unwrap();
+        assert_eq!(root.id, NodeId::from("root"));
+        let children = provider.get_children(&root.id).await.unwrap();
+        assert_eq!(children.len(), 2);
+
+        let a = NodeId::from("a");
+        provider.perform_action(&a, &Action::Press).await.unwrap();
+        assert_eq!(mock.performed(), vec![(a, Action::Press)]);
+        assert!(provider.get_node(&NodeId::from("gone")).await.is_err());
+    }
+
+    /// A mock served back and forth through both adapters
+    fn round_trip(mock: &MockProvider) -> SyncAdapter {
+        SyncAdapter::new(Box::new(BlockingAdapter::new(Arc::new(mock.clone()))))
+    }
+
+    #[test]
+    fn sync_adapter_works_outside_a_runtime() {
+        let mock = sample_tree();
+        let provider = round_trip(&mock);
+
+        assert_eq!(provider.get_root().unwrap().id, NodeId::from("root"));
+        let a = NodeId::from("a");
+        provider.perform_action(&a, &Action::Press).unwrap();
+        assert_eq!(mock.performed(), vec![(a, Action::Press)]);
+    }
+
+    #[tokio::test]
+    async fn sync_adapter_works_on_a_current_thread_runtime() {
+        let mock = sample_tree();
+        let provider = round_trip(&mock);
+
+        assert_eq!(
+            provider.get_children(&NodeId::from("root")).unwrap().len(),
+            2
+        );
+        assert!(provider.get_node(&NodeId::from("gone")).is_err());
+    }
+
+    #[test]
+    fn sync_adapter_forwards_provider_overrides() {
+        let mock = sample_tree();
+        let provider = round_trip(&mock);
+        let a = NodeId::from("a");
+
+        assert!(provider.is_alive(&a).unwrap());
+        mock.remove_node("a");
+        // The mock still answers reads of a removed node, so only its own
+        // `is_alive` can tell
+        assert!(!provider.is_alive(&a).unwrap());
+    }
+}
@@ -0,0 +1,98 @@
+//! The logic behind the provider traits' default methods.
+//!
+//! [`AccessibilityProvider`](super::AccessibilityProvider) and
+//! [`AsyncAccessibilityProvider`](super::AsyncAccessibilityProvider) share
+//! these, so the two defaults can't drift apart; each trait only makes the
+//! reads they ask for, blocking or awaiting as it must.
+
+use crate::protocol::{NameSources, Node, NodeId};
+use crate::traversal::DEFAULT_MAX_NODES;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+/// A search down from the root for the node listing `child` among its children.
+///
+/// Gives up with an error after [`DEFAULT_MAX_NODES`] nodes.
+pub(crate) struct ParentSearch<'a> {
+    child: &'a NodeId,
+    pending: Vec<Node>,
+    visited: HashSet<NodeId>,
+}
+
+impl<'a> ParentSearch<'a> {
+    pub fn new(child: &'a NodeId, root: Node) -> Self {
+        Self {
+            child,
+            pending: vec![root],
+            visited: HashSet::new(),
+        }
+    }
+
+    /// The node whose children to read next, or the parent (`None` once the
+    /// tree is exhausted)
+    pub fn next(&mut self) -> Result<ControlFlow<Option<Node>, NodeId>> {
+        while let Some(node) = self.pending.pop() {
+            // Guards against cycles in misbehaving trees
+            if !self.visited.insert(node.id.clone()) {
+                continue;
+            }
+            if node.children.contains(self.child) {
+                return Ok(ControlFlow::Break(Some(node)));
+            }
+            if self.visited.len() >= DEFAULT_MAX_NODES {
+                anyhow::bail!(
+                    "Parent of {} not found within {} nodes",
+                    self.child.as_str(),
+                    DEFAULT_MAX_NODES
+                );
+            }
+            return Ok(ControlFlow::Continue(node.id));
+        }
+        Ok(ControlFlow::Break(None))
+    }
+
+    /// Queue the children read for the node [`next`](Self::next) returned
+    pub fn push_children(&mut self, children: Vec<Node>) {
+        self.pending.extend(children);
+    }
+}
+
+/// A walk up through a node's ancestors to the window holding it.
+///
+/// Bounded in case a misbehaving tree loops back on itself.
+#[derive(Default)]
+pub(crate) struct WindowWalk {
+    steps: usize,
+}
+
+impl WindowWalk {
+    /// The window, if `node` is one or the walk has gone as far as it may;
+    /// otherwise the node whose parent to step to
+    pub fn step(&mut self, node: Node) -> ControlFlow<Option<Node>, NodeId> {
+        if node.role == "AXWindow" {
+            return ControlFlow::Break(Some(node));
+        }
+        self.steps += 1;
+        if self.steps >= DEFAULT_MAX_NODES {
+            return ControlFlow::Break(None);
+        }
+        ControlFlow::Continue(node.id)
+    }
+}
+
+/// The menu bar among the root's children: the first with role `AXMenuBar`
+pub(crate) fn menu_bar(children: Vec<Node>) -> Option<Node> {
+    children.into_iter().find(|node| node.role == "AXMenuBar")
+}
+
+/// What can be said of a node's name from the node alone: its name (as the
+/// title), description, and value
+pub(crate) fn name_sources(node: Node) -> NameSources {
+    NameSources {
+        title: node.name,
+        description: node.description,
+        value: node.value,
+        ..NameSources::default()
+    }
+}
@@ -1,12 +1,22 @@
 //! macOS accessibility backend using AXAPI
 
-use crate::protocol::{Action, Node, NodeId};
+use super::{AppTerminated, NodeFields};
+use crate::config::IdScheme;
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
 use anyhow::{Context, Result};
-use core_foundation::base::{CFType, TCFType};
+use core_foundation::base::{CFHash, CFType, CFTypeID, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::CFNumber;
+use core_foundation::runloop::{
+    kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopSource, CFRunLoopSourceRef,
+};
 use core_foundation::string::{CFString, CFStringRef};
 
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
 #[link(name = "ApplicationServices", kind = "framework")]
 extern "C" {
@@ -16,34 +26,444 @@ extern "C" {
         attribute: CFStringRef,
         value: *mut CFTypeRef,
     ) -> AXError;
+    fn AXUIElementSetAttributeValue(
+        element: AXUIElementRef,
+        attribute: CFStringRef,
+        value: CFTypeRef,
+    ) -> AXError;
+    fn AXUIElementIsAttributeSettable(
+        element: AXUIElementRef,
+        attribute: CFStringRef,
+        settable: *mut u8,
+    ) -> AXError;
+    fn AXUIElementCopyElementAtPosition(
+        application: AXUIElementRef,
+        x: f32,
+        y: f32,
+        element: *mut AXUIElementRef,
+    ) -> AXError;
     fn AXUIElementPerformAction(element: AXUIElementRef, action: CFStringRef) -> AXError;
+    fn AXUIElementCopyActionNames(element: AXUIElementRef, names: *mut CFTypeRef) -> AXError;
+    fn AXUIElementGetTypeID() -> CFTypeID;
+    fn AXUIElementCopyParameterizedAttributeValue(
+        element: AXUIElementRef,
+        attribute: CFStringRef,
+        parameter: CFTypeRef,
+        value: *mut CFTypeRef,
+    ) -> AXError;
+    fn AXValueCreate(type_: i32, value_ptr: *const std::ffi::c_void) -> CFTypeRef;
+    fn AXValueGetTypeID() -> CFTypeID;
+    fn AXValueGetType(value: CFTypeRef) -> i32;
+    fn AXValueGetValue(value: CFTypeRef, type_: i32, value_ptr: *mut std::ffi::c_void) -> bool;
+    fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
+    fn AXObserverCreate(
+        application: i32,
+        callback: AXObserverCallback,
+        observer: *mut AXObserverRef,
+    ) -> AXError;
+    fn AXObserverAddNotification(
+        observer: AXObserverRef,
+        element: AXUIElementRef,
+        notification: CFStringRef,
+        refcon: *mut std::ffi::c_void,
+    ) -> AXError;
+    fn AXObserverRemoveNotification(
+        observer: AXObserverRef,
+        element: AXUIElementRef,
+        notification: CFStringRef,
+    ) -> AXError;
+    fn AXObserverGetRunLoopSource(observer: AXObserverRef) -> CFRunLoopSourceRef;
+    #[allow(non_upper_case_globals)]
+    static kAXTrustedCheckOptionPrompt: CFStringRef;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFStringGetLength(string: CFStringRef) -> isize;
+    fn CFStringGetCharacters(string: CFStringRef, range: CFRange, buffer: *mut u16);
+    fn CFLocaleCopyCurrent() -> CFTypeRef;
+    fn CFLocaleGetValue(locale: CFTypeRef, key: CFStringRef) -> CFTypeRef;
+    #[allow(non_upper_case_globals)]
+    static kCFLocaleLanguageCode: CFStringRef;
+    #[allow(non_upper_case_globals)]
+    static kCFLocaleCountryCode: CFStringRef;
 }
 
 type AXUIElementRef = *const std::ffi::c_void;
+type AXObserverRef = *const std::ffi::c_void;
+type AXObserverCallback = unsafe extern "C" fn(
+    observer: AXObserverRef,
+    element: AXUIElementRef,
+    notification: CFStringRef,
+    refcon: *mut std::ffi::c_void,
+);
 type AXError = i32;
 type CFTypeRef = *const std::ffi::c_void;
 
 const K_AX_ERROR_SUCCESS: AXError = 0;
-const K_AX_ERROR_API_DISABLED: AXError = -25208;
-const K_AX_ERROR_NO_VALUE: AXError = -25209;
+const K_AX_ERROR_INVALID_UI_ELEMENT: AXError = -25202;
+const K_AX_ERROR_API_DISABLED: AXError = -25211;
+const K_AX_ERROR_NO_VALUE: AXError = -25212;
+const K_AX_ERROR_NOTIFICATION_UNSUPPORTED: AXError = -25207;
+
+/// The name AXError.h gives an error code, without the `k` prefix
+fn ax_error_name(code: AXError) -> Option<&'static str> {
+    Some(match code {
+        0 => "AXErrorSuccess",
+        -25200 => "AXErrorFailure",
+        -25201 => "AXErrorIllegalArgument",
+        -25202 => "AXErrorInvalidUIElement",
+        -25203 => "AXErrorInvalidUIElementObserver",
+        -25204 => "AXErrorCannotComplete",
+        -25205 => "AXErrorAttributeUnsupported",
+        -25206 => "AXErrorActionUnsupported",
+        -25207 => "AXErrorNotificationUnsupported",
+        -25208 => "AXErrorNotImplemented",
+        -25209 => "AXErrorNotificationAlreadyRegistered",
+        -25210 => "AXErrorNotificationNotRegistered",
+        -25211 => "AXErrorAPIDisabled",
+        -25212 => "AXErrorNoValue",
+        -25213 => "AXErrorParameterizedAttributeUnsupported",
+        -25214 => "AXErrorNotEnoughPrecision",
+        _ => return None,
+    })
+}
+
+/// An AXError as shown in messages, e.g. `error code -25206 (AXErrorActionUnsupported)`
+struct AxErrorCode(AXError);
+
+impl std::fmt::Display for AxErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match ax_error_name(self.0) {
+            Some(name) => write!(f, "error code {} ({})", self.0, name),
+            None => write!(f, "error code {}", self.0),
+        }
+    }
+}
 
 // Common AX attribute constants
 const K_AX_ROLE_ATTRIBUTE: &str = "AXRole";
+const K_AX_SUBROLE_ATTRIBUTE: &str = "AXSubrole";
 const K_AX_TITLE_ATTRIBUTE: &str = "AXTitle";
 const K_AX_VALUE_ATTRIBUTE: &str = "AXValue";
+const K_AX_VALUE_DESCRIPTION_ATTRIBUTE: &str = "AXValueDescription";
 const K_AX_DESCRIPTION_ATTRIBUTE: &str = "AXDescription";
+const K_AX_TITLE_UI_ELEMENT_ATTRIBUTE: &str = "AXTitleUIElement";
+const K_AX_HELP_ATTRIBUTE: &str = "AXHelp";
 const K_AX_CHILDREN_ATTRIBUTE: &str = "AXChildren";
+const K_AX_PARENT_ATTRIBUTE: &str = "AXParent";
+const K_AX_SELECTED_CHILDREN_ATTRIBUTE: &str = "AXSelectedChildren";
+const K_AX_ENABLED_ATTRIBUTE: &str = "AXEnabled";
+const K_AX_MENU_ITEM_MARK_CHAR_ATTRIBUTE: &str = "AXMenuItemMarkChar";
+const K_AX_FOCUSED_UI_ELEMENT_ATTRIBUTE: &str = "AXFocusedUIElement";
+const K_AX_FOCUSED_WINDOW_ATTRIBUTE: &str = "AXFocusedWindow";
+const K_AX_WINDOW_ATTRIBUTE: &str = "AXWindow";
+const K_AX_MAIN_ATTRIBUTE: &str = "AXMain";
+const K_AX_WINDOWS_ATTRIBUTE: &str = "AXWindows";
+const K_AX_SELECTED_TEXT_ATTRIBUTE: &str = "AXSelectedText";
+const K_AX_FOCUSED_ATTRIBUTE: &str = "AXFocused";
+const K_AX_FRONTMOST_ATTRIBUTE: &str = "AXFrontmost";
+const K_AX_MENU_BAR_ATTRIBUTE: &str = "AXMenuBar";
 const K_AX_POSITION_ATTRIBUTE: &str = "AXPosition";
 const K_AX_SIZE_ATTRIBUTE: &str = "AXSize";
+const K_AX_NUMBER_OF_CHARACTERS_ATTRIBUTE: &str = "AXNumberOfCharacters";
+const K_AX_STRING_FOR_RANGE_PARAMETERIZED_ATTRIBUTE: &str = "AXStringForRange";
+const K_AX_VALUE_CHANGED_NOTIFICATION: &str = "AXValueChanged";
+
+/// Roles listed by `list_roles`: the common AX roles, which other roles'
+/// elements behave like
+const KNOWN_ROLES: &[&str] = &[
+    "AXApplication",
+    "AXWindow",
+    "AXSheet",
+    "AXButton",
+    "AXMenuButton",
+    "AXCheckBox",
+    "AXRadioButton",
+    "AXRadioGroup",
+    "AXTextField",
+    "AXTextArea",
+    "AXStaticText",
+    "AXHeading",
+    "AXImage",
+    "AXLink",
+    "AXGroup",
+    "AXSlider",
+    "AXIncrementor",
+    "AXPopUpButton",
+    "AXComboBox",
+    "AXMenuBar",
+    "AXMenuBarItem",
+    "AXMenu",
+    "AXMenuItem",
+    "AXList",
+    "AXTable",
+    "AXOutline",
+    "AXRow",
+    "AXCell",
+    "AXScrollArea",
+    "AXScrollBar",
+    "AXSplitter",
+    "AXTabGroup",
+    "AXToolbar",
+    "AXProgressIndicator",
+    "AXWebArea",
+];
+
+/// Whether this process may use the accessibility API; with `prompt`, ask
+/// the user to grant it (in a system dialog) if it may not
+pub(super) fn request_accessibility_permission(prompt: bool) -> bool {
+    let options = CFDictionary::from_CFType_pairs(&[(
+        unsafe { CFString::wrap_under_get_rule(kAXTrustedCheckOptionPrompt) },
+        CFBoolean::from(prompt),
+    )]);
+    unsafe { AXIsProcessTrustedWithOptions(options.as_concrete_TypeRef()) }
+}
+
+/// Post a left mouse click at a global screen point (top-left origin, as AX reports bounds)
+fn click_at(x: f64, y: f64) -> Result<()> {
+    use core_graphics::event::{CGEvent, CGEventTapLocation, CGEventType, CGMouseButton};
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+    use core_graphics::geometry::CGPoint;
+
+    let point = CGPoint::new(x, y);
+    for event_type in [CGEventType::LeftMouseDown, CGEventType::LeftMouseUp] {
+        let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+            .map_err(|_| anyhow::anyhow!("Failed to create event source"))?;
+        let event = CGEvent::new_mouse_event(source, event_type, point, CGMouseButton::Left)
+            .map_err(|_| anyhow::anyhow!("Failed to create mouse event at ({}, {})", x, y))?;
+        event.post(CGEventTapLocation::HID);
+    }
+    Ok(())
+}
+
+/// The mouse cursor's global position.
+///
+/// CGEvent locations share AX's top-left origin on the main display, so
+/// unlike `NSEvent.mouseLocation` (bottom-left origin) they need no flipping.
+fn cursor_location() -> Result<(f64, f64)> {
+    use core_graphics::event::CGEvent;
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+        .map_err(|_| anyhow::anyhow!("Failed to create event source"))?;
+    let event = CGEvent::new(source).map_err(|_| anyhow::anyhow!("Failed to create event"))?;
+    let location = event.location();
+    Ok((location.x, location.y))
+}
+
+/// Type `text` into whatever has keyboard focus, posting a key down and up per character
+#[cfg(feature = "keystrokes")]
+fn type_text(text: &str) -> Result<()> {
+    use core_graphics::event::{CGEvent, CGEventTapLocation};
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    for c in text.chars() {
+        for keydown in [true, false] {
+            let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+                .map_err(|_| anyhow::anyhow!("Failed to create event source"))?;
+            // The virtual key code is ignored once the event carries a string
+            let event = CGEvent::new_keyboard_event(source, 0, keydown)
+                .map_err(|_| anyhow::anyhow!("Failed to create key event for {:?}", c))?;
+            event.set_string(c.encode_utf8(&mut [0; 4]));
+            event.post(CGEventTapLocation::HID);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "keystrokes"))]
+fn type_text(_text: &str) -> Result<()> {
+    anyhow::bail!("Typing requires the `keystrokes` feature")
+}
+
+/// An attribute value read from AXAPI, decoded from its CoreFoundation type
+#[derive(Debug, Clone, PartialEq)]
+enum AttrValue {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Point { x: f64, y: f64 },
+    Size { width: f64, height: f64 },
+    Array(Vec<AttrValue>),
+    Element(NodeId),
+}
+
+const K_AX_VALUE_CG_POINT_TYPE: i32 = 1;
+const K_AX_VALUE_CG_SIZE_TYPE: i32 = 2;
+const K_AX_VALUE_CF_RANGE_TYPE: i32 = 4;
+
+/// CoreFoundation's CFRange, the payload of a `kAXValueCFRangeType` AXValue
+#[repr(C)]
+struct CFRange {
+    location: isize,
+    length: isize,
+}
+
+/// CGPoint/CGSize layout: two CGFloats, which are f64 on 64-bit systems
+#[repr(C)]
+#[derive(Default)]
+struct CGPair(f64, f64);
+
+/// Decode an AXValue wrapping a CGPoint or CGSize; other AXValue types are unsupported
+unsafe fn decode_ax_value(value: CFTypeRef) -> Option<AttrValue> {
+    let value_type = AXValueGetType(value);
+    if value_type != K_AX_VALUE_CG_POINT_TYPE && value_type != K_AX_VALUE_CG_SIZE_TYPE {
+        return None;
+    }
+
+    let mut pair = CGPair::default();
+    if !AXValueGetValue(
+        value,
+        value_type,
+        &mut pair as *mut _ as *mut std::ffi::c_void,
+    ) {
+        return None;
+    }
+
+    Some(if value_type == K_AX_VALUE_CG_POINT_TYPE {
+        AttrValue::Point {
+            x: pair.0,
+            y: pair.1,
+        }
+    } else {
+        AttrValue::Size {
+            width: pair.0,
+            height: pair.1,
+        }
+    })
+}
+
+/// A CFString's text, with any unpaired UTF-16 surrogates replaced by U+FFFD.
+///
+/// `CFString::to_string` converts through UTF-8, which such strings have no
+/// encoding in, and panics on them; apps can hold them in text they were given.
+fn cf_string_lossy(string: &CFString) -> String {
+    String::from_utf16_lossy(&cf_string_units(string))
+}
+
+/// Whether `unit` is the first half of a character encoded as two UTF-16 units
+fn is_high_surrogate(unit: &u16) -> bool {
+    (0xD800..0xDC00).contains(unit)
+}
+
+/// The UTF-16 code units of a CFString, as it stores them
+fn cf_string_units(string: &CFString) -> Vec<u16> {
+    unsafe {
+        let length = CFStringGetLength(string.as_concrete_TypeRef());
+        let mut units = vec![0u16; length.max(0) as usize];
+        CFStringGetCharacters(
+            string.as_concrete_TypeRef(),
+            CFRange {
+                location: 0,
+                length,
+            },
+            units.as_mut_ptr(),
+        );
+        units
+    }
+}
+
+/// An AX element used as a map key.
+///
+/// AXAPI hands out a fresh reference each time an element is read, so keys
+/// hash and compare by CFHash/CFEqual to treat those references as one element.
+struct ElementKey(CFType);
+
+impl PartialEq for ElementKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for ElementKey {}
+
+impl std::hash::Hash for ElementKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_usize(unsafe { CFHash(self.0.as_CFTypeRef()) } as usize);
+    }
+}
+
+/// Most elements cached at once. Past it the longest-cached are dropped, and
+/// their ids stop resolving until the element is read again.
+const MAX_CACHED_ELEMENTS: usize = 100_000;
+
+/// The AX element a cached CFType holds
+fn element_ref(element: &CFType) -> AXUIElementRef {
+    element.as_CFTypeRef() as AXUIElementRef
+}
+
+/// Both directions of the mapping between elements and the ids handed out for them
+#[derive(Default)]
+struct ElementIds {
+    /// Elements by id, retained for as long as they are cached
+    elements: HashMap<String, CFType>,
+    ids: HashMap<ElementKey, NodeId>,
+    /// Ids in the order they were cached, oldest first
+    order: VecDeque<NodeId>,
+    /// The last id handed out under [`IdScheme::Sequential`]
+    last_sequential: u64,
+}
+
+impl ElementIds {
+    /// Cache `element` under `node_id`, or under `node_id#2`, `node_id#3`, ...
+    /// if another element already has it, evicting the oldest entry when full.
+    ///
+    /// Two elements can share a role path, e.g. when one isn't listed among
+    /// its parent's children, and an id must never resolve to the wrong one.
+    fn insert(&mut self, node_id: NodeId, element: CFType) -> NodeId {
+        let node_id = if self.elements.contains_key(node_id.as_str()) {
+            (2..)
+                .map(|n| NodeId::from(format!("{}#{}", node_id.as_str(), n)))
+                .find(|id| !self.elements.contains_key(id.as_str()))
+                .expect("some suffix is free")
+        } else {
+            node_id
+        };
+
+        while self.order.len() >= MAX_CACHED_ELEMENTS {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+
+        let previous = self
+            .elements
+            .insert(node_id.as_str().to_string(), element.clone());
+        assert!(
+            previous.is_none(),
+            "id {} handed out twice",
+            node_id.as_str()
+        );
+        self.ids.insert(ElementKey(element), node_id.clone());
+        self.order.push_back(node_id.clone());
+        node_id
+    }
+
+    /// Drop an element, so its id no longer resolves
+    fn remove(&mut self, node_id: &NodeId) {
+        if let Some(element) = self.elements.remove(node_id.as_str()) {
+            self.ids.remove(&ElementKey(element));
+        }
+    }
+}
 
 pub struct MacOSProvider {
     root: AXUIElementRef,
-    /// Cache mapping NodeId strings to AXUIElementRef pointers
-    element_cache: Mutex<HashMap<String, AXUIElementRef>>,
+    id_scheme: IdScheme,
+    /// Read far more often than written: every id lookup reads it, while only
+    /// the first sighting of an element writes, so lookups share a read lock
+    element_cache: RwLock<ElementIds>,
 }
 
 impl MacOSProvider {
     pub fn new() -> Result<Self> {
+        Self::with_id_scheme(IdScheme::Pointer)
+    }
+
+    /// Create a provider that identifies elements using `id_scheme`
+    pub fn with_id_scheme(id_scheme: IdScheme) -> Result<Self> {
         // Try to get the root element with retry logic
         let root = unsafe { AXUIElementCreateApplication(std::process::id() as i32) };
 
@@ -53,35 +473,131 @@ impl MacOSProvider {
 
         Ok(Self {
             root,
-            element_cache: Mutex::new(HashMap::new()),
+            id_scheme,
+            element_cache: RwLock::new(ElementIds::default()),
         })
     }
 
-    /// Convert AXUIElementRef pointer to NodeId
-    fn element_to_node_id(&self, element: AXUIElementRef) -> NodeId {
-        let id = format!("{:p}", element);
-        NodeId::from(id)
-    }
-
-    /// Look up AXUIElementRef from NodeId
-    fn node_id_to_element(&self, node_id: &NodeId) -> Result<AXUIElementRef> {
-        let cache = self.element_cache.lock().unwrap();
+    /// Look up the element for a NodeId.
+    ///
+    /// The element is retained, so it stays valid while held even if the
+    /// cache evicts it meanwhile; use it through [`element_ref`].
+    fn node_id_to_element(&self, node_id: &NodeId) -> Result<CFType> {
+        let cache = self.element_cache.read().unwrap();
         cache
+            .elements
             .get(node_id.as_str())
-            .copied()
+            .cloned()
             .context("Node ID not found in cache")
     }
 
-    /// Cache an element with its NodeId
+    /// Drop a destroyed element from the cache, so its id no longer resolves
+    fn forget_element(&self, node_id: &NodeId) {
+        let mut cache = self.element_cache.write().unwrap();
+        cache.remove(node_id);
+        cache.order.retain(|id| id != node_id);
+    }
+
+    /// Cache an element, returning its existing NodeId if it was seen before
     fn cache_element(&self, element: AXUIElementRef) -> NodeId {
-        let node_id = self.element_to_node_id(element);
-        let mut cache = self.element_cache.lock().unwrap();
-        cache.insert(node_id.as_str().to_string(), element);
-        node_id
+        // Retained by the cache for as long as the provider lives
+        let key = ElementKey(unsafe { CFType::wrap_under_get_rule(element as CFTypeRef) });
+        if let Some(id) = self.element_cache.read().unwrap().ids.get(&key) {
+            return id.clone();
+        }
+
+        // Computed outside the lock, since it reads the element's ancestors
+        let structural_id = match self.id_scheme {
+            IdScheme::Structural => Some(unsafe { self.structural_id(&key.0) }),
+            IdScheme::Pointer | IdScheme::Sequential => None,
+        };
+
+        // Another thread may have cached it since the read above
+        let mut cache = self.element_cache.write().unwrap();
+        if let Some(id) = cache.ids.get(&key) {
+            return id.clone();
+        }
+        let node_id = match self.id_scheme {
+            IdScheme::Pointer => NodeId::from(format!("{:p}", element)),
+            IdScheme::Sequential => {
+                cache.last_sequential += 1;
+                NodeId::from(cache.last_sequential.to_string())
+            }
+            IdScheme::Structural => NodeId::from(structural_id.unwrap_or_default()),
+        };
+        cache.insert(node_id, key.0)
     }
 
-    /// Get a string attribute from an AX element
-    unsafe fn get_string_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<String> {
+    /// The role path from the application down to `element`, e.g.
+    /// `AXApplication/AXWindow[0]/AXButton[2]`.
+    ///
+    /// Each index counts only the siblings that share the element's role.
+    /// Elements are read directly rather than through the cache, which this
+    /// is used to populate.
+    unsafe fn structural_id(&self, element: &CFType) -> String {
+        use core_foundation::array::{CFArray, CFArrayRef};
+
+        // Guards against parent cycles in misbehaving AX trees
+        const MAX_DEPTH: usize = 256;
+
+        let role_of = |element: &CFType| {
+            self.get_string_attribute(
+                element.as_CFTypeRef() as AXUIElementRef,
+                K_AX_ROLE_ATTRIBUTE,
+            )
+            .unwrap_or_else(|| "unknown".to_string())
+        };
+
+        let mut segments = Vec::new();
+        let mut current = element.clone();
+        for _ in 0..MAX_DEPTH {
+            let role = role_of(&current);
+            let parent = self
+                .copy_attribute(
+                    current.as_CFTypeRef() as AXUIElementRef,
+                    K_AX_PARENT_ATTRIBUTE,
+                )
+                .filter(|parent| parent.type_of() == AXUIElementGetTypeID());
+            let Some(parent) = parent else {
+                segments.push(role);
+                break;
+            };
+
+            let index = self
+                .copy_attribute(
+                    parent.as_CFTypeRef() as AXUIElementRef,
+                    K_AX_CHILDREN_ATTRIBUTE,
+                )
+                .filter(|children| children.type_of() == CFArray::<CFType>::type_id())
+                .and_then(|children| {
+                    CFArray::<CFType>::wrap_under_get_rule(children.as_CFTypeRef() as CFArrayRef)
+                        .iter()
+                        .filter(|sibling| role_of(sibling) == role)
+                        .position(|sibling| *sibling == current)
+                })
+                .unwrap_or(0);
+            segments.push(format!("{}[{}]", role, index));
+            current = parent;
+        }
+
+        segments.reverse();
+        segments.join("/")
+    }
+
+    /// Whether the application element no longer refers to a running app
+    unsafe fn app_is_gone(&self) -> bool {
+        let attr_name = CFString::new(K_AX_ROLE_ATTRIBUTE);
+        let mut value: CFTypeRef = std::ptr::null();
+        let result =
+            AXUIElementCopyAttributeValue(self.root, attr_name.as_concrete_TypeRef(), &mut value);
+        if !value.is_null() {
+            drop(CFType::wrap_under_create_rule(value));
+        }
+        result == K_AX_ERROR_INVALID_UI_ELEMENT
+    }
+
+    /// Copy an attribute value from an AX element, logging unexpected failures
+    unsafe fn copy_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<CFType> {
         let attr_name = CFString::new(attr);
         let mut value: CFTypeRef = std::ptr::null();
 
@@ -107,183 +623,220 @@ impl MacOSProvider {
         }
 
         if result == K_AX_ERROR_SUCCESS && !value.is_null() {
-            let cf_value = CFType::wrap_under_create_rule(value);
-
-            // Try to downcast to CFString
-            if let Some(string) = cf_value.downcast::<CFString>() {
-                return Some(string.to_string());
-            } else {
-                // Debug: what type did we get?
-                tracing::debug!("Attribute {} returned non-string type", attr);
-            }
+            return Some(CFType::wrap_under_create_rule(value));
         } else if result != K_AX_ERROR_SUCCESS {
-            tracing::debug!("Failed to get attribute {}: error {}", attr, result);
+            tracing::debug!("Failed to get attribute {}: {}", attr, AxErrorCode(result));
         }
 
         None
     }
 
-    /// Get a point attribute (position) from an AX element
-    unsafe fn get_point_attribute(
-        &self,
-        element: AXUIElementRef,
-        attr: &str,
-    ) -> Option<(f64, f64)> {
-        use core_foundation::base::TCFType;
-
-        let attr_name = CFString::new(attr);
-        let mut value: CFTypeRef = std::ptr::null();
-
-        let result =
-            AXUIElementCopyAttributeValue(element, attr_name.as_concrete_TypeRef(), &mut value);
-
-        if result != K_AX_ERROR_SUCCESS || value.is_null() {
-            return None;
-        }
-
-        // The value is an AXValue containing a CGPoint
-        // We need to extract x and y coordinates
-        // For simplicity, try to get it as a CFType and inspect it
-        let _cf_value = CFType::wrap_under_create_rule(value);
-
-        // AXValue is a CFType but not directly exposed in core-foundation
-        // We'll use a workaround: extract the raw bytes
-        // CGPoint is {CGFloat x; CGFloat y;} where CGFloat is f64 on 64-bit systems
-        #[repr(C)]
-        struct CGPoint {
-            x: f64,
-            y: f64,
-        }
-
-        // Use AXValueGetValue to extract the point
-        extern "C" {
-            fn AXValueGetValue(
-                value: CFTypeRef,
-                type_: i32,
-                value_ptr: *mut std::ffi::c_void,
-            ) -> bool;
+    /// Read an attribute of any supported type
+    unsafe fn get_attribute_value(&self, element: AXUIElementRef, attr: &str) -> Option<AttrValue> {
+        let cf_value = self.copy_attribute(element, attr)?;
+        let decoded = self.decode_value(&cf_value);
+        if decoded.is_none() {
+            tracing::debug!("Attribute {} returned unsupported value type", attr);
         }
+        decoded
+    }
 
-        const K_AX_VALUE_CG_POINT_TYPE: i32 = 1;
-
-        let mut point = CGPoint { x: 0.0, y: 0.0 };
-        let success = AXValueGetValue(
-            value,
-            K_AX_VALUE_CG_POINT_TYPE,
-            &mut point as *mut _ as *mut std::ffi::c_void,
-        );
+    /// Convert a CoreFoundation value returned by AXAPI into an [`AttrValue`]
+    unsafe fn decode_value(&self, value: &CFType) -> Option<AttrValue> {
+        use core_foundation::array::{CFArray, CFArrayRef};
 
-        if success {
-            // Convert from macOS coordinates (bottom-left origin) to screen coordinates (top-left origin)
-            // We need the screen height to do this conversion
-            // For now, return raw coordinates - caller can convert if needed
-            Some((point.x, point.y))
+        let type_id = value.type_of();
+
+        if let Some(string) = value.downcast::<CFString>() {
+            Some(AttrValue::String(cf_string_lossy(&string)))
+        } else if let Some(boolean) = value.downcast::<CFBoolean>() {
+            Some(AttrValue::Bool(bool::from(boolean)))
+        } else if let Some(number) = value.downcast::<CFNumber>() {
+            number.to_f64().map(AttrValue::Number)
+        } else if type_id == CFArray::<CFType>::type_id() {
+            let array = CFArray::<CFType>::wrap_under_get_rule(value.as_CFTypeRef() as CFArrayRef);
+            Some(AttrValue::Array(
+                array
+                    .iter()
+                    .filter_map(|item| self.decode_value(&item))
+                    .collect(),
+            ))
+        } else if type_id == AXUIElementGetTypeID() {
+            Some(AttrValue::Element(
+                self.cache_element(value.as_CFTypeRef() as AXUIElementRef),
+            ))
+        } else if type_id == AXValueGetTypeID() {
+            decode_ax_value(value.as_CFTypeRef())
         } else {
             None
         }
     }
 
-    /// Get a size attribute from an AX element
-    unsafe fn get_size_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<(f64, f64)> {
-        use core_foundation::base::TCFType;
-
+    /// Whether an attribute of an AX element can be set; false if it can't be determined
+    unsafe fn is_attribute_settable(&self, element: AXUIElementRef, attr: &str) -> bool {
         let attr_name = CFString::new(attr);
-        let mut value: CFTypeRef = std::ptr::null();
-
+        let mut settable: u8 = 0;
         let result =
-            AXUIElementCopyAttributeValue(element, attr_name.as_concrete_TypeRef(), &mut value);
-
-        if result != K_AX_ERROR_SUCCESS || value.is_null() {
-            return None;
-        }
-
-        let _cf_value = CFType::wrap_under_create_rule(value);
+            AXUIElementIsAttributeSettable(element, attr_name.as_concrete_TypeRef(), &mut settable);
+        result == K_AX_ERROR_SUCCESS && settable != 0
+    }
 
-        #[repr(C)]
-        struct CGSize {
-            width: f64,
-            height: f64,
+    /// Get a string attribute from an AX element
+    unsafe fn get_string_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<String> {
+        match self.get_attribute_value(element, attr)? {
+            AttrValue::String(string) => Some(string),
+            other => {
+                tracing::debug!("Attribute {} returned non-string value {:?}", attr, other);
+                None
+            }
         }
+    }
 
-        extern "C" {
-            fn AXValueGetValue(
-                value: CFTypeRef,
-                type_: i32,
-                value_ptr: *mut std::ffi::c_void,
-            ) -> bool;
+    /// Get an attribute rendered as a string, accepting strings, numbers, and booleans.
+    ///
+    /// `AXValue` is a CFNumber for checkboxes and sliders, so reading it as a
+    /// plain string would drop it.
+    unsafe fn get_value_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<String> {
+        match self.get_attribute_value(element, attr)? {
+            AttrValue::String(string) => Some(string),
+            // f64's Display renders whole numbers without a fraction ("1", not "1.0")
+            AttrValue::Number(number) => Some(number.to_string()),
+            AttrValue::Bool(boolean) => Some(if boolean { "1" } else { "0" }.to_string()),
+            other => {
+                tracing::debug!("Attribute {} returned unsupported value {:?}", attr, other);
+                None
+            }
         }
+    }
 
-        const K_AX_VALUE_CG_SIZE_TYPE: i32 = 2;
-
-        let mut size = CGSize {
-            width: 0.0,
-            height: 0.0,
-        };
-        let success = AXValueGetValue(
-            value,
-            K_AX_VALUE_CG_SIZE_TYPE,
-            &mut size as *mut _ as *mut std::ffi::c_void,
-        );
-
-        if success {
-            Some((size.width, size.height))
-        } else {
-            None
+    /// Get a boolean attribute from an AX element
+    unsafe fn get_bool_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<bool> {
+        match self.get_attribute_value(element, attr)? {
+            AttrValue::Bool(boolean) => Some(boolean),
+            AttrValue::Number(number) => Some(number != 0.0),
+            other => {
+                tracing::debug!("Attribute {} returned non-boolean value {:?}", attr, other);
+                None
+            }
         }
     }
 
-    /// Get children elements from an AX element
-    unsafe fn get_children_elements(&self, element: AXUIElementRef) -> Vec<AXUIElementRef> {
-        use core_foundation::array::{CFArray, CFArrayRef};
-        use core_foundation::base::TCFType;
-
-        let attr_name = CFString::new(K_AX_CHILDREN_ATTRIBUTE);
-        let mut value: CFTypeRef = std::ptr::null();
-
-        let result =
-            AXUIElementCopyAttributeValue(element, attr_name.as_concrete_TypeRef(), &mut value);
-
-        if result == K_AX_ERROR_NO_VALUE {
-            // No children, which is normal
-            return Vec::new();
+    /// Get a point attribute (position) from an AX element.
+    ///
+    /// Coordinates are returned as AXAPI reports them, without conversion.
+    unsafe fn get_point_attribute(
+        &self,
+        element: AXUIElementRef,
+        attr: &str,
+    ) -> Option<(f64, f64)> {
+        match self.get_attribute_value(element, attr)? {
+            AttrValue::Point { x, y } => Some((x, y)),
+            _ => None,
         }
+    }
 
-        if result != K_AX_ERROR_SUCCESS || value.is_null() {
-            tracing::debug!("Failed to get children: error {}", result);
-            return Vec::new();
+    /// Get a size attribute from an AX element
+    unsafe fn get_size_attribute(&self, element: AXUIElementRef, attr: &str) -> Option<(f64, f64)> {
+        match self.get_attribute_value(element, attr)? {
+            AttrValue::Size { width, height } => Some((width, height)),
+            _ => None,
         }
+    }
 
-        // Cast to CFArray
-        let array_ref = value as CFArrayRef;
-        let array = CFArray::<CFType>::wrap_under_get_rule(array_ref);
+    /// Get the ids of an element's children, caching each child element
+    unsafe fn get_children_ids(&self, element: AXUIElementRef) -> Vec<NodeId> {
+        self.get_element_ids_attribute(element, K_AX_CHILDREN_ATTRIBUTE)
+    }
 
-        let mut children = Vec::new();
-        for i in 0..array.len() {
-            if let Some(item) = array.get(i) {
-                // The item should be an AXUIElementRef
-                let child_element = item.as_CFTypeRef() as AXUIElementRef;
-                children.push(child_element);
-            }
+    /// Get an attribute holding an array of elements, such as children or selection
+    unsafe fn get_element_ids_attribute(&self, element: AXUIElementRef, attr: &str) -> Vec<NodeId> {
+        match self.get_attribute_value(element, attr) {
+            Some(AttrValue::Array(items)) => items
+                .into_iter()
+                .filter_map(|item| match item {
+                    AttrValue::Element(id) => Some(id),
+                    _ => None,
+                })
+                .collect(),
+            // Attribute not present, which is normal
+            _ => Vec::new(),
         }
+    }
 
-        children
+    /// A window's stacking level, from its position in the application's
+    /// AXWindows, which lists windows front to back
+    unsafe fn window_level(&self, window_id: &NodeId) -> Option<i64> {
+        let windows = self.get_element_ids_attribute(self.root, K_AX_WINDOWS_ATTRIBUTE);
+        let position = windows.iter().position(|id| id == window_id)?;
+        Some((windows.len() - 1 - position) as i64)
     }
 
-    /// Convert AXUIElementRef to Node
-    fn element_to_node(&self, element: AXUIElementRef) -> Result<Node> {
+    /// The radio button's parent, if it's the `AXRadioGroup` that makes its
+    /// options mutually exclusive
+    unsafe fn radio_group(&self, element: AXUIElementRef) -> Option<NodeId> {
+        let Some(AttrValue::Element(parent_id)) =
+            self.get_attribute_value(element, K_AX_PARENT_ATTRIBUTE)
+        else {
+            return None;
+        };
+        let parent = self.node_id_to_element(&parent_id).ok()?;
+        (self
+            .get_string_attribute(element_ref(&parent), K_AX_ROLE_ATTRIBUTE)
+            .as_deref()
+            == Some("AXRadioGroup"))
+        .then_some(parent_id)
+    }
+
+    /// Convert AXUIElementRef to Node, reading only the attributes in `fields`
+    fn element_to_node(&self, element: AXUIElementRef, fields: NodeFields) -> Result<Node> {
         let node_id = self.cache_element(element);
+        // Each attribute is a separate cross-process call, so skip unwanted ones
+        let wants = |field| fields.contains(field);
 
         unsafe {
-            let role = self
-                .get_string_attribute(element, K_AX_ROLE_ATTRIBUTE)
-                .unwrap_or_else(|| "unknown".to_string());
-
-            let name = self.get_string_attribute(element, K_AX_TITLE_ATTRIBUTE);
-            let value = self.get_string_attribute(element, K_AX_VALUE_ATTRIBUTE);
-            let description = self.get_string_attribute(element, K_AX_DESCRIPTION_ATTRIBUTE);
+            let role = match self.get_string_attribute(element, K_AX_ROLE_ATTRIBUTE) {
+                Some(role) => role,
+                // Every element has a role, so a missing one may mean the app has quit
+                None if self.app_is_gone() => return Err(AppTerminated.into()),
+                None => "unknown".to_string(),
+            };
+            // Always read, like the role: redaction of secure fields depends on it
+            let subrole = self.get_string_attribute(element, K_AX_SUBROLE_ATTRIBUTE);
+
+            let name = wants(NodeFields::NAME)
+                .then(|| self.get_string_attribute(element, K_AX_TITLE_ATTRIBUTE))
+                .flatten();
+            let (value, value_description) = if wants(NodeFields::VALUE) {
+                (
+                    self.get_value_attribute(element, K_AX_VALUE_ATTRIBUTE),
+                    self.get_string_attribute(element, K_AX_VALUE_DESCRIPTION_ATTRIBUTE),
+                )
+            } else {
+                (None, None)
+            };
+            let description = wants(NodeFields::DESCRIPTION)
+                .then(|| self.get_string_attribute(element, K_AX_DESCRIPTION_ATTRIBUTE))
+                .flatten();
+
+            // Text elements report their length in UTF-16 units; lines are
+            // counted from the value, when it was read
+            let (char_count, line_count) = if wants(NodeFields::TEXT_STATS)
+                && super::TEXT_ROLES.contains(&role.as_str())
+            {
+                let char_count =
+                    match self.get_attribute_value(element, K_AX_NUMBER_OF_CHARACTERS_ATTRIBUTE) {
+                        Some(AttrValue::Number(count)) if count >= 0.0 => Some(count as usize),
+                        _ => value.as_ref().map(|value| value.encode_utf16().count()),
+                    };
+                (char_count, value.as_deref().map(super::line_count))
+            } else {
+                (None, None)
+            };
 
             // Get bounds (position and size)
-            let bounds = if let (Some((x, y)), Some((width, height))) = (
+            let bounds = if !wants(NodeFields::BOUNDS) {
+                None
+            } else if let (Some((x, y)), Some((width, height))) = (
                 self.get_point_attribute(element, K_AX_POSITION_ATTRIBUTE),
                 self.get_size_attribute(element, K_AX_SIZE_ATTRIBUTE),
             ) {
@@ -297,25 +850,91 @@ impl MacOSProvider {
                 None
             };
 
-            // Get children
-            let child_elements = self.get_children_elements(element);
-            let children: Vec<NodeId> = child_elements
-                .iter()
-                .map(|&e| self.cache_element(e))
-                .collect();
+            let children = if wants(NodeFields::CHILDREN) {
+                self.get_children_ids(element)
+            } else {
+                Vec::new()
+            };
+
+            // Lists, tab groups, and radio groups report their current selection
+            let selected_children = if wants(NodeFields::SELECTED_CHILDREN) {
+                self.get_element_ids_attribute(element, K_AX_SELECTED_CHILDREN_ATTRIBUTE)
+            } else {
+                Vec::new()
+            };
+
+            let enabled = wants(NodeFields::ENABLED)
+                .then(|| self.get_bool_attribute(element, K_AX_ENABLED_ATTRIBUTE))
+                .flatten();
+
+            // Menu items report their checkmark as a mark character, with no
+            // mark meaning unchecked; radio buttons report selection as a 0/1 value
+            let (checked, radio_group) = match role.as_str() {
+                _ if !wants(NodeFields::CHECKED) => (None, None),
+                "AXMenuItem" => (
+                    Some(
+                        self.get_string_attribute(element, K_AX_MENU_ITEM_MARK_CHAR_ATTRIBUTE)
+                            .is_some_and(|mark| !mark.is_empty()),
+                    ),
+                    None,
+                ),
+                "AXRadioButton" => (
+                    super::parse_checked(
+                        self.get_value_attribute(element, K_AX_VALUE_ATTRIBUTE)
+                            .as_deref(),
+                    ),
+                    self.radio_group(element),
+                ),
+                _ => (None, None),
+            };
+
+            // Some elements can take focus without settling it in AXFocused
+            let focusable = wants(NodeFields::FOCUSABLE).then(|| {
+                super::is_focusable_role(&role)
+                    || self.is_attribute_settable(element, K_AX_FOCUSED_ATTRIBUTE)
+            });
 
             // Determine available actions based on role
             let actions = self.determine_actions(&role);
+            let is_web_content = super::is_web_content_role(&role);
+            let is_frontmost = role == "AXWindow"
+                && self.get_bool_attribute(element, K_AX_MAIN_ATTRIBUTE) == Some(true);
+            let window_level = if role == "AXWindow" && wants(NodeFields::WINDOW_LEVEL) {
+                self.window_level(&node_id)
+            } else {
+                None
+            };
 
             Ok(Node {
                 id: node_id,
                 role,
+                subrole,
+                akit_role: None,
                 name,
                 value,
+                value_description,
                 description,
                 bounds,
+                visual: None,
                 actions,
                 children,
+                collapsed_from: Vec::new(),
+                visible: None,
+                selected_children,
+                enabled,
+                focusable,
+                checked,
+                radio_group,
+                child_count: None,
+                char_count,
+                line_count,
+                depth: None,
+                index_in_parent: None,
+                epoch: 0,
+                fingerprint: None,
+                is_web_content,
+                is_frontmost,
+                window_level,
             })
         }
     }
@@ -329,7 +948,12 @@ impl MacOSProvider {
                     value: String::new(),
                 },
             ],
-            "AXCheckBox" => vec![Action::Press, Action::Focus],
+            // Switches are AXCheckBox elements with an AXSwitch subrole
+            "AXCheckBox" | "AXRadioButton" => vec![
+                Action::Press,
+                Action::Focus,
+                Action::SetChecked { checked: None },
+            ],
             "AXSlider" => vec![Action::Focus, Action::Increment, Action::Decrement],
             _ => vec![Action::Focus],
         }
@@ -338,28 +962,39 @@ impl MacOSProvider {
 
 impl super::AccessibilityProvider for MacOSProvider {
     fn get_root(&self) -> Result<Node> {
-        self.element_to_node(self.root)
+        self.element_to_node(self.root, NodeFields::ALL)
     }
 
     fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
-        let element = self.node_id_to_element(node_id)?;
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
 
         unsafe {
-            let child_elements = self.get_children_elements(element);
-            child_elements
+            self.get_children_ids(element)
                 .iter()
-                .map(|&e| self.element_to_node(e))
+                .map(|id| {
+                    let held = self.node_id_to_element(id)?;
+                    self.element_to_node(element_ref(&held), NodeFields::ALL)
+                })
                 .collect()
         }
     }
 
     fn get_node(&self, node_id: &NodeId) -> Result<Node> {
-        let element = self.node_id_to_element(node_id)?;
-        self.element_to_node(element)
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+        self.element_to_node(element, NodeFields::ALL)
+    }
+
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+        self.element_to_node(element, fields)
     }
 
     fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
-        let element = self.node_id_to_element(node_id)?;
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
 
         match action {
             Action::Press => unsafe {
@@ -368,7 +1003,7 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to perform press action: error code {}", result)
+                    anyhow::bail!("Failed to perform press action: {}", AxErrorCode(result))
                 }
             },
             Action::Focus => unsafe {
@@ -377,7 +1012,7 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to perform focus action: error code {}", result)
+                    anyhow::bail!("Failed to perform focus action: {}", AxErrorCode(result))
                 }
             },
             Action::Increment => unsafe {
@@ -386,7 +1021,10 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to perform increment action: error code {}", result)
+                    anyhow::bail!(
+                        "Failed to perform increment action: {}",
+                        AxErrorCode(result)
+                    )
                 }
             },
             Action::Decrement => unsafe {
@@ -395,21 +1033,16 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to perform decrement action: error code {}", result)
+                    anyhow::bail!(
+                        "Failed to perform decrement action: {}",
+                        AxErrorCode(result)
+                    )
                 }
             },
             Action::SetValue { value } => unsafe {
                 let attr_name = CFString::new(K_AX_VALUE_ATTRIBUTE);
                 let cf_value = CFString::new(value);
 
-                extern "C" {
-                    fn AXUIElementSetAttributeValue(
-                        element: AXUIElementRef,
-                        attribute: CFStringRef,
-                        value: CFTypeRef,
-                    ) -> AXError;
-                }
-
                 let result = AXUIElementSetAttributeValue(
                     element,
                     attr_name.as_concrete_TypeRef(),
@@ -419,7 +1052,7 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to set value: error code {}", result)
+                    anyhow::bail!("Failed to set value: {}", AxErrorCode(result))
                 }
             },
             Action::Scroll { x: _, y: _ } => {
@@ -434,9 +1067,14 @@ impl super::AccessibilityProvider for MacOSProvider {
                 if result == K_AX_ERROR_SUCCESS {
                     Ok(())
                 } else {
-                    anyhow::bail!("Failed to show context menu: error code {}", result)
+                    anyhow::bail!("Failed to show context menu: {}", AxErrorCode(result))
                 }
             },
+            Action::SetChecked { checked } => {
+                let checked = checked.context("set_checked needs `checked`")?;
+                let node = self.get_node(node_id)?;
+                super::set_checked(self, &node, checked).map(drop)
+            }
             Action::Custom { name } => unsafe {
                 let cf_action = CFString::new(name);
                 let result = AXUIElementPerformAction(element, cf_action.as_concrete_TypeRef());
@@ -444,15 +1082,650 @@ impl super::AccessibilityProvider for MacOSProvider {
                     Ok(())
                 } else {
                     anyhow::bail!(
-                        "Failed to perform custom action '{}': error code {}",
+                        "Failed to perform custom action '{}': {}",
                         name,
-                        result
+                        AxErrorCode(result)
                     )
                 }
             },
+            Action::PressAt { dx, dy } => unsafe {
+                // A click outside the element could land in another app
+                anyhow::ensure!(
+                    (0.0..=1.0).contains(dx) && (0.0..=1.0).contains(dy),
+                    "press_at offsets must be between 0 and 1, got {}, {}",
+                    dx,
+                    dy
+                );
+                // AXPress can't target a point, so synthesize a real click there
+                let (Some((x, y)), Some((width, height))) = (
+                    self.get_point_attribute(element, K_AX_POSITION_ATTRIBUTE),
+                    self.get_size_attribute(element, K_AX_SIZE_ATTRIBUTE),
+                ) else {
+                    anyhow::bail!("Element has no bounds to press within");
+                };
+                let bounds = crate::protocol::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                };
+                let (x, y) = bounds.point_at(*dx, *dy);
+                click_at(x, y)
+            },
+            // The server focuses the element first; keys go wherever focus is now
+            Action::TypeText { text } => type_text(text),
+        }
+    }
+
+    fn get_menu_bar(&self) -> Result<Option<Node>> {
+        // Decoding caches the element, so its items can be pressed later
+        match unsafe { self.get_attribute_value(self.root, K_AX_MENU_BAR_ATTRIBUTE) } {
+            Some(AttrValue::Element(menu_bar_id)) => self.get_node(&menu_bar_id).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        cursor_location().map(Some)
+    }
+
+    fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        // Hit-testing on the application element only finds this app's elements
+        let mut element: AXUIElementRef = std::ptr::null();
+        let result = unsafe {
+            AXUIElementCopyElementAtPosition(self.root, x as f32, y as f32, &mut element)
+        };
+        if result == K_AX_ERROR_NO_VALUE || (result == K_AX_ERROR_SUCCESS && element.is_null()) {
+            return Ok(None);
+        }
+        if result != K_AX_ERROR_SUCCESS {
+            anyhow::bail!("Failed to hit-test ({}, {}): {}", x, y, AxErrorCode(result));
+        }
+
+        // Released once the cache has taken its own reference
+        let element = unsafe { CFType::wrap_under_create_rule(element as CFTypeRef) };
+        self.element_to_node(element.as_CFTypeRef() as AXUIElementRef, NodeFields::ALL)
+            .map(Some)
+    }
+
+    fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        // The provider inspects its own process, so the current locale is the app's
+        unsafe {
+            let locale = CFType::wrap_under_create_rule(CFLocaleCopyCurrent());
+            let component = |key| {
+                let value = CFLocaleGetValue(locale.as_CFTypeRef(), key);
+                if value.is_null() {
+                    return None;
+                }
+                CFType::wrap_under_get_rule(value)
+                    .downcast::<CFString>()
+                    .map(|code| code.to_string())
+            };
+            Ok((
+                component(kCFLocaleLanguageCode),
+                component(kCFLocaleCountryCode),
+            ))
+        }
+    }
+
+    fn is_frontmost_app(&self) -> Result<bool> {
+        // Unreadable counts as not frontmost, erring towards refusing input
+        let frontmost = unsafe { self.get_bool_attribute(self.root, K_AX_FRONTMOST_ATTRIBUTE) };
+        Ok(frontmost == Some(true))
+    }
+
+    fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+        let focused = CFString::new(K_AX_FOCUSED_ATTRIBUTE);
+        let result = unsafe {
+            AXUIElementSetAttributeValue(
+                element,
+                focused.as_concrete_TypeRef(),
+                CFBoolean::true_value().as_CFTypeRef(),
+            )
+        };
+        if result != K_AX_ERROR_SUCCESS {
+            anyhow::bail!("Failed to focus element: {}", AxErrorCode(result));
+        }
+        Ok(())
+    }
+
+    /// Ranges count UTF-16 code units, as AX does, which differ from
+    /// characters only outside the Basic Multilingual Plane (e.g. emoji)
+    fn text_range(
+        &self,
+        node_id: &NodeId,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+
+        unsafe {
+            // Elements without a character count don't take ranges
+            let total = match self.get_attribute_value(element, K_AX_NUMBER_OF_CHARACTERS_ATTRIBUTE)
+            {
+                Some(AttrValue::Number(total)) if total >= 0.0 => total as usize,
+                _ => return Ok(None),
+            };
+            let start = offset.min(total);
+            let range = CFRange {
+                location: start as isize,
+                length: length.min(total - start) as isize,
+            };
+            let range = CFType::wrap_under_create_rule(AXValueCreate(
+                K_AX_VALUE_CF_RANGE_TYPE,
+                &range as *const CFRange as *const std::ffi::c_void,
+            ));
+
+            let attr = CFString::new(K_AX_STRING_FOR_RANGE_PARAMETERIZED_ATTRIBUTE);
+            let mut value: CFTypeRef = std::ptr::null();
+            let result = AXUIElementCopyParameterizedAttributeValue(
+                element,
+                attr.as_concrete_TypeRef(),
+                range.as_CFTypeRef(),
+                &mut value,
+            );
+            if value.is_null() {
+                return Ok(None);
+            }
+            let value = CFType::wrap_under_create_rule(value);
+            if result != K_AX_ERROR_SUCCESS {
+                return Ok(None);
+            }
+            // Ranges count UTF-16 code units and may end inside a surrogate
+            // pair; stop before it, so the next chunk starts with the whole pair
+            Ok(value.downcast::<CFString>().map(|text| {
+                let mut units = cf_string_units(&text);
+                if units.len() > 1 && units.last().is_some_and(is_high_surrogate) {
+                    units.pop();
+                }
+                (String::from_utf16_lossy(&units), total)
+            }))
+        }
+    }
+
+    /// Reads only `AXRole`, since `element_to_node` turns a failed read into
+    /// an `unknown` role rather than an error. A destroyed element is
+    /// dropped from the cache.
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        let Ok(held) = self.node_id_to_element(node_id) else {
+            return Ok(false);
+        };
+        let element = element_ref(&held);
+        let result = unsafe {
+            let attr_name = CFString::new(K_AX_ROLE_ATTRIBUTE);
+            let mut value: CFTypeRef = std::ptr::null();
+            let result =
+                AXUIElementCopyAttributeValue(element, attr_name.as_concrete_TypeRef(), &mut value);
+            if !value.is_null() {
+                drop(CFType::wrap_under_create_rule(value));
+            }
+            result
+        };
+        match result {
+            K_AX_ERROR_SUCCESS => Ok(true),
+            K_AX_ERROR_INVALID_UI_ELEMENT if unsafe { self.app_is_gone() } => {
+                Err(AppTerminated.into())
+            }
+            K_AX_ERROR_INVALID_UI_ELEMENT => {
+                self.forget_element(node_id);
+                Ok(false)
+            }
+            result => anyhow::bail!("Failed to read role: {}", AxErrorCode(result)),
+        }
+    }
+
+    fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+        Ok(unsafe { self.get_string_attribute(element, K_AX_SELECTED_TEXT_ATTRIBUTE) })
+    }
+
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        let held_window = self.node_id_to_element(window_id)?;
+        let window = element_ref(&held_window);
+
+        unsafe {
+            let raise = CFString::new("AXRaise");
+            let result = AXUIElementPerformAction(window, raise.as_concrete_TypeRef());
+            if result != K_AX_ERROR_SUCCESS {
+                anyhow::bail!("Failed to raise window: {}", AxErrorCode(result));
+            }
+
+            let main = CFString::new(K_AX_MAIN_ATTRIBUTE);
+            let result = AXUIElementSetAttributeValue(
+                window,
+                main.as_concrete_TypeRef(),
+                CFBoolean::true_value().as_CFTypeRef(),
+            );
+            if result != K_AX_ERROR_SUCCESS {
+                anyhow::bail!("Failed to make window main: {}", AxErrorCode(result));
+            }
+
+            // Keyboard focus follows the main window only once the app says so
+            let focused_window = CFString::new(K_AX_FOCUSED_WINDOW_ATTRIBUTE);
+            let result = AXUIElementSetAttributeValue(
+                self.root,
+                focused_window.as_concrete_TypeRef(),
+                window as CFTypeRef,
+            );
+            if result != K_AX_ERROR_SUCCESS {
+                anyhow::bail!("Failed to focus window: {}", AxErrorCode(result));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        // The application element is the root even if AX reports something
+        // above it. AX hands out new references for the same element, so
+        // pointers can't be compared, but the cache gives it one id
+        if *node_id == self.cache_element(self.root) {
+            return Ok(None);
+        }
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+
+        match unsafe { self.get_attribute_value(element, K_AX_PARENT_ATTRIBUTE) } {
+            Some(AttrValue::Element(parent_id)) => self.get_node(&parent_id).map(Some),
+            _ => Ok(None),
         }
     }
+
+    fn get_focused(&self) -> Result<Option<Node>> {
+        let Some(focused) =
+            (unsafe { self.copy_attribute(self.root, K_AX_FOCUSED_UI_ELEMENT_ATTRIBUTE) })
+        else {
+            return Ok(None);
+        };
+
+        // AX hands out a new reference for the same element; the cache maps
+        // it back to the element's existing NodeId
+        let element = focused.as_CFTypeRef() as AXUIElementRef;
+        self.element_to_node(element, NodeFields::ALL).map(Some)
+    }
+
+    fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+
+        if unsafe { self.get_string_attribute(element, K_AX_ROLE_ATTRIBUTE) }.as_deref()
+            == Some("AXWindow")
+        {
+            return self.get_node(node_id).map(Some);
+        }
+        match unsafe { self.get_attribute_value(element, K_AX_WINDOW_ATTRIBUTE) } {
+            Some(AttrValue::Element(window_id)) => self.get_node(&window_id).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn get_focused_window(&self) -> Result<Option<Node>> {
+        let Some(window) =
+            (unsafe { self.copy_attribute(self.root, K_AX_FOCUSED_WINDOW_ATTRIBUTE) })
+        else {
+            return Ok(None);
+        };
+
+        let element = window.as_CFTypeRef() as AXUIElementRef;
+        self.element_to_node(element, NodeFields::ALL).map(Some)
+    }
+
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+
+        unsafe {
+            let title_ui_element =
+                match self.get_attribute_value(element, K_AX_TITLE_UI_ELEMENT_ATTRIBUTE) {
+                    Some(AttrValue::Element(id)) => Some(id),
+                    _ => None,
+                };
+            Ok(NameSources {
+                title: self.get_string_attribute(element, K_AX_TITLE_ATTRIBUTE),
+                title_ui_element,
+                description: self.get_string_attribute(element, K_AX_DESCRIPTION_ATTRIBUTE),
+                value: self.get_value_attribute(element, K_AX_VALUE_ATTRIBUTE),
+                help: self.get_string_attribute(element, K_AX_HELP_ATTRIBUTE),
+            })
+        }
+    }
+
+    fn roles(&self) -> Vec<RoleEntry> {
+        KNOWN_ROLES
+            .iter()
+            .map(|role| RoleEntry {
+                role: role.to_string(),
+                normalized_role: None,
+                actions: self
+                    .determine_actions(role)
+                    .iter()
+                    .map(|a| a.action_type().to_string())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+
+        unsafe {
+            let mut names: CFTypeRef = std::ptr::null();
+            let result = AXUIElementCopyActionNames(element, &mut names);
+            if result != K_AX_ERROR_SUCCESS || names.is_null() {
+                anyhow::bail!("Failed to read action names: {}", AxErrorCode(result));
+            }
+
+            match self.decode_value(&CFType::wrap_under_create_rule(names)) {
+                Some(AttrValue::Array(items)) => Ok(Some(
+                    items
+                        .into_iter()
+                        .filter_map(|item| match item {
+                            AttrValue::String(name) => Some(name),
+                            _ => None,
+                        })
+                        .collect(),
+                )),
+                _ => Ok(None),
+            }
+        }
+    }
+
+    fn wait_for_value_change(&self, node_id: &NodeId, timeout: Duration) -> Result<Option<bool>> {
+        let held = self.node_id_to_element(node_id)?;
+        let element = element_ref(&held);
+        let changed = Cell::new(false);
+        let deadline = Instant::now() + timeout;
+
+        unsafe {
+            let mut observer: AXObserverRef = std::ptr::null();
+            let result =
+                AXObserverCreate(std::process::id() as i32, note_value_changed, &mut observer);
+            if result != K_AX_ERROR_SUCCESS || observer.is_null() {
+                anyhow::bail!("Failed to create observer: {}", AxErrorCode(result));
+            }
+            // Released on return, after the notification is removed
+            let held_observer = CFType::wrap_under_create_rule(observer as CFTypeRef);
+            let observer = held_observer.as_CFTypeRef() as AXObserverRef;
+
+            let notification = CFString::new(K_AX_VALUE_CHANGED_NOTIFICATION);
+            let result = AXObserverAddNotification(
+                observer,
+                element,
+                notification.as_concrete_TypeRef(),
+                &changed as *const Cell<bool> as *mut std::ffi::c_void,
+            );
+            match result {
+                K_AX_ERROR_SUCCESS => {}
+                // Not every element announces value changes; the server polls those
+                K_AX_ERROR_NOTIFICATION_UNSUPPORTED => return Ok(None),
+                _ => anyhow::bail!("Failed to observe value changes: {}", AxErrorCode(result)),
+            }
+
+            // Notifications are delivered through this thread's run loop
+            let source = CFRunLoopSource::wrap_under_get_rule(AXObserverGetRunLoopSource(observer));
+            let run_loop = CFRunLoop::get_current();
+            run_loop.add_source(&source, kCFRunLoopDefaultMode);
+            while !changed.get() {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                CFRunLoop::run_in_mode(kCFRunLoopDefaultMode, deadline - now, true);
+            }
+            run_loop.remove_source(&source, kCFRunLoopDefaultMode);
+            AXObserverRemoveNotification(observer, element, notification.as_concrete_TypeRef());
+        }
+
+        Ok(Some(changed.get()))
+    }
+}
+
+/// Marks the `Cell<bool>` a value-change observer was registered with
+unsafe extern "C" fn note_value_changed(
+    _observer: AXObserverRef,
+    _element: AXUIElementRef,
+    _notification: CFStringRef,
+    refcon: *mut std::ffi::c_void,
+) {
+    (*(refcon as *const Cell<bool>)).set(true);
 }
 
 unsafe impl Send for MacOSProvider {}
 unsafe impl Sync for MacOSProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::AccessibilityProvider;
+
+    #[test]
+    fn locale_reports_language_code() {
+        let provider = MacOSProvider::new().unwrap();
+        let (language, region) = provider.locale().unwrap();
+
+        let language = language.expect("the current locale always has a language");
+        assert!(
+            language.len() >= 2 && language.chars().all(|c| c.is_ascii_lowercase()),
+            "{language:?}"
+        );
+        if let Some(region) = region {
+            assert!(!region.is_empty(), "{region:?}");
+        }
+    }
+
+    #[test]
+    fn refresh_rereads_cached_element() {
+        let provider = MacOSProvider::new().unwrap();
+        let root = provider.get_root().unwrap();
+
+        let refreshed = provider.refresh(&root.id).unwrap();
+        assert_eq!(refreshed.id, root.id);
+        assert_eq!(refreshed.role, root.role);
+    }
+
+    fn decode(provider: &MacOSProvider, value: CFType) -> Option<AttrValue> {
+        unsafe { provider.decode_value(&value) }
+    }
+
+    #[test]
+    fn decodes_scalar_values() {
+        let provider = MacOSProvider::new().unwrap();
+        assert_eq!(
+            decode(&provider, CFString::new("Save").as_CFType()),
+            Some(AttrValue::String("Save".to_string()))
+        );
+        assert_eq!(
+            decode(&provider, CFNumber::from(0.73).as_CFType()),
+            Some(AttrValue::Number(0.73))
+        );
+        assert_eq!(
+            decode(&provider, CFBoolean::true_value().as_CFType()),
+            Some(AttrValue::Bool(true))
+        );
+    }
+
+    #[test]
+    fn decodes_points_and_sizes() {
+        let ax_value = |type_, pair: CGPair| unsafe {
+            CFType::wrap_under_create_rule(AXValueCreate(type_, &pair as *const _ as *const _))
+        };
+
+        let provider = MacOSProvider::new().unwrap();
+        assert_eq!(
+            decode(
+                &provider,
+                ax_value(K_AX_VALUE_CG_POINT_TYPE, CGPair(10.0, 20.0))
+            ),
+            Some(AttrValue::Point { x: 10.0, y: 20.0 })
+        );
+        assert_eq!(
+            decode(
+                &provider,
+                ax_value(K_AX_VALUE_CG_SIZE_TYPE, CGPair(80.0, 24.0))
+            ),
+            Some(AttrValue::Size {
+                width: 80.0,
+                height: 24.0
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_arrays_and_elements() {
+        use core_foundation::array::CFArray;
+
+        let provider = MacOSProvider::new().unwrap();
+        let array = CFArray::from_CFTypes(&[
+            CFString::new("a").as_CFType(),
+            CFNumber::from(2).as_CFType(),
+        ]);
+        assert_eq!(
+            decode(&provider, array.as_CFType()),
+            Some(AttrValue::Array(vec![
+                AttrValue::String("a".to_string()),
+                AttrValue::Number(2.0),
+            ]))
+        );
+
+        let app = unsafe {
+            CFType::wrap_under_create_rule(AXUIElementCreateApplication(std::process::id() as i32))
+        };
+        match decode(&provider, app.clone()) {
+            Some(AttrValue::Element(id)) => {
+                assert_eq!(
+                    element_ref(&provider.node_id_to_element(&id).unwrap()),
+                    element_ref(&app)
+                )
+            }
+            other => panic!("expected an element, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn elements_sharing_an_id_are_told_apart() {
+        let mut cache = ElementIds::default();
+        let path = || NodeId::from("AXApplication/AXWindow[0]/AXButton[0]");
+        let first = CFString::new("first").as_CFType();
+        let second = CFString::new("second").as_CFType();
+
+        assert_eq!(cache.insert(path(), first.clone()), path());
+        let id = cache.insert(path(), second.clone());
+        assert_eq!(id.as_str(), "AXApplication/AXWindow[0]/AXButton[0]#2");
+        assert!(cache.elements[path().as_str()] == first);
+        assert!(cache.elements[id.as_str()] == second);
+
+        cache.remove(&path());
+        assert!(!cache.elements.contains_key(path().as_str()));
+        assert_eq!(cache.ids.len(), 1);
+    }
+
+    #[test]
+    fn concurrent_reads_agree_on_ids() {
+        let provider = MacOSProvider::with_id_scheme(IdScheme::Sequential).unwrap();
+        let root = provider.get_root().unwrap();
+        let children = provider.get_children(&root.id).unwrap();
+
+        // Every thread re-reads the same elements; lookups and the occasional
+        // insert race, but each element must keep a single id
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..200 {
+                        assert_eq!(provider.get_root().unwrap().id, root.id);
+                        for child in &children {
+                            assert_eq!(provider.get_node(&child.id).unwrap().id, child.id);
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn ax_errors_are_named() {
+        let cases = [
+            (K_AX_ERROR_SUCCESS, "AXErrorSuccess"),
+            (-25200, "AXErrorFailure"),
+            (-25201, "AXErrorIllegalArgument"),
+            (K_AX_ERROR_INVALID_UI_ELEMENT, "AXErrorInvalidUIElement"),
+            (-25203, "AXErrorInvalidUIElementObserver"),
+            (-25204, "AXErrorCannotComplete"),
+            (-25205, "AXErrorAttributeUnsupported"),
+            (-25206, "AXErrorActionUnsupported"),
+            (-25207, "AXErrorNotificationUnsupported"),
+            (-25208, "AXErrorNotImplemented"),
+            (-25209, "AXErrorNotificationAlreadyRegistered"),
+            (-25210, "AXErrorNotificationNotRegistered"),
+            (K_AX_ERROR_API_DISABLED, "AXErrorAPIDisabled"),
+            (K_AX_ERROR_NO_VALUE, "AXErrorNoValue"),
+            (-25213, "AXErrorParameterizedAttributeUnsupported"),
+            (-25214, "AXErrorNotEnoughPrecision"),
+        ];
+        for (code, name) in cases {
+            assert_eq!(ax_error_name(code), Some(name), "{code}");
+        }
+        assert_eq!(ax_error_name(-1), None);
+
+        assert_eq!(
+            AxErrorCode(-25206).to_string(),
+            "error code -25206 (AXErrorActionUnsupported)"
+        );
+        assert_eq!(AxErrorCode(-1).to_string(), "error code -1");
+    }
+
+    #[test]
+    fn refresh_unknown_node_fails() {
+        let provider = MacOSProvider::new().unwrap();
+        assert!(provider.refresh(&NodeId::from("0xdead")).is_err());
+    }
+
+    #[test]
+    fn sequential_ids_are_stable_and_round_trip() {
+        let provider = MacOSProvider::with_id_scheme(IdScheme::Sequential).unwrap();
+        let root = provider.get_root().unwrap();
+        assert_eq!(root.id, NodeId::from("1"));
+
+        // Re-reading hands out new element references but the same ids
+        assert_eq!(provider.get_root().unwrap().id, root.id);
+        let children = provider.get_children(&root.id).unwrap();
+        let again = provider.get_children(&root.id).unwrap();
+        assert_eq!(
+            children.iter().map(|c| &c.id).collect::<Vec<_>>(),
+            again.iter().map(|c| &c.id).collect::<Vec<_>>()
+        );
+
+        for node in std::iter::once(&root).chain(&children) {
+            assert!(node.id.as_str().parse::<u64>().is_ok());
+            let fetched = provider.get_node(&node.id).unwrap();
+            assert_eq!(fetched.id, node.id);
+            assert_eq!(fetched.role, node.role);
+        }
+    }
+
+    #[test]
+    fn structural_ids_are_role_paths() {
+        let provider = MacOSProvider::with_id_scheme(IdScheme::Structural).unwrap();
+        let root = provider.get_root().unwrap();
+        assert_eq!(root.id.as_str(), root.role);
+
+        for child in provider.get_children(&root.id).unwrap() {
+            assert!(child
+                .id
+                .as_str()
+                .starts_with(&format!("{}/{}[", root.role, child.role)));
+            assert_eq!(provider.get_node(&child.id).unwrap().id, child.id);
+        }
+    }
+
+    #[test]
+    fn role_map_lists_default_actions() {
+        let provider = MacOSProvider::new().unwrap();
+        let roles = provider.roles();
+        let button = roles.iter().find(|entry| entry.role == "AXButton").unwrap();
+        assert_eq!(button.actions, ["press", "focus"]);
+    }
+}
@@ -0,0 +1,468 @@
+//! In-memory accessibility backend used by unit tests
+
+use super::{AppTerminated, NodeFields};
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// A provider backed by a fixed set of nodes.
+///
+/// Clones share the same state, so a test can keep a handle to inspect
+/// the performed actions after handing a clone to the server.
+#[derive(Clone)]
+pub struct MockProvider {
+    root: NodeId,
+    nodes: Arc<Mutex<HashMap<NodeId, Node>>>,
+    performed: Arc<Mutex<Vec<(NodeId, Action)>>>,
+    get_node_calls: Arc<AtomicUsize>,
+    attribute_reads: Arc<AtomicUsize>,
+    focused: Arc<Mutex<Option<NodeId>>>,
+    native_actions: Arc<Mutex<HashMap<NodeId, Vec<String>>>>,
+    native_actions_failing: Arc<Mutex<HashSet<NodeId>>>,
+    fields_failing: Arc<Mutex<HashSet<NodeId>>>,
+    selected_text: Arc<Mutex<HashMap<NodeId, String>>>,
+    title_elements: Arc<Mutex<HashMap<NodeId, NodeId>>>,
+    delay: Arc<Mutex<Duration>>,
+    terminated: Arc<AtomicBool>,
+    frontmost: Arc<AtomicBool>,
+    cursor: Arc<Mutex<Option<(f64, f64)>>>,
+    removed: Arc<Mutex<HashSet<NodeId>>>,
+    observable: Arc<AtomicBool>,
+    /// How many times each node's value has changed, signalled on every change
+    value_changes: Arc<(Mutex<HashMap<NodeId, usize>>, Condvar)>,
+}
+
+impl MockProvider {
+    /// Create a provider from a list of nodes; the first node is the root
+    pub fn new(nodes: Vec<Node>) -> Self {
+        let root = nodes.first().expect("mock tree needs a root").id.clone();
+        Self {
+            root,
+            nodes: Arc::new(Mutex::new(
+                nodes.into_iter().map(|n| (n.id.clone(), n)).collect(),
+            )),
+            performed: Arc::new(Mutex::new(Vec::new())),
+            get_node_calls: Arc::new(AtomicUsize::new(0)),
+            attribute_reads: Arc::new(AtomicUsize::new(0)),
+            focused: Arc::new(Mutex::new(None)),
+            native_actions: Arc::new(Mutex::new(HashMap::new())),
+            native_actions_failing: Arc::new(Mutex::new(HashSet::new())),
+            fields_failing: Arc::new(Mutex::new(HashSet::new())),
+            selected_text: Arc::new(Mutex::new(HashMap::new())),
+            title_elements: Arc::new(Mutex::new(HashMap::new())),
+            delay: Arc::new(Mutex::new(Duration::ZERO)),
+            terminated: Arc::new(AtomicBool::new(false)),
+            frontmost: Arc::new(AtomicBool::new(true)),
+            cursor: Arc::new(Mutex::new(None)),
+            removed: Arc::new(Mutex::new(HashSet::new())),
+            observable: Arc::new(AtomicBool::new(false)),
+            value_changes: Arc::new((Mutex::new(HashMap::new()), Condvar::new())),
+        }
+    }
+
+    /// Insert a node, replacing any existing node with the same id
+    pub fn set_node(&self, node: Node) {
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes
+            .get(&node.id)
+            .is_some_and(|old| old.value != node.value)
+        {
+            self.note_value_change(&node.id);
+        }
+        nodes.insert(node.id.clone(), node);
+    }
+
+    /// Announce value changes to `wait_for_value_change`, as an
+    /// `AXObserver` would; by default it reports that it can't
+    pub fn observe_value_changes(&self) {
+        self.observable.store(true, Ordering::SeqCst);
+    }
+
+    fn note_value_change(&self, node_id: &NodeId) {
+        let (changes, changed) = &*self.value_changes;
+        *changes.lock().unwrap().entry(node_id.clone()).or_default() += 1;
+        changed.notify_all();
+    }
+
+    /// Destroy a node's element. Like macOS, reading it still succeeds, with
+    /// an `unknown` role and nothing else; parents still list it.
+    pub fn remove_node(&self, node_id: &str) {
+        self.removed.lock().unwrap().insert(NodeId::from(node_id));
+    }
+
+    /// Actions performed so far, in order
+    pub fn performed(&self) -> Vec<(NodeId, Action)> {
+        self.performed.lock().unwrap().clone()
+    }
+
+    /// Give keyboard focus to a node without recording an action
+    pub fn set_focused(&self, node_id: Option<&str>) {
+        *self.focused.lock().unwrap() = node_id.map(NodeId::from);
+    }
+
+    /// Report a node's native action names; nodes without any set report none
+    pub fn set_native_actions(&self, node_id: &str, names: &[&str]) {
+        self.native_actions.lock().unwrap().insert(
+            NodeId::from(node_id),
+            names.iter().map(|n| n.to_string()).collect(),
+        );
+    }
+
+    /// Fail every read of a node's native action names
+    pub fn fail_native_actions(&self, node_id: &str) {
+        self.native_actions_failing
+            .lock()
+            .unwrap()
+            .insert(NodeId::from(node_id));
+    }
+
+    /// Fail every `get_node_fields` read of a node; `get_node` still succeeds
+    pub fn fail_node_fields(&self, node_id: &str) {
+        self.fields_failing
+            .lock()
+            .unwrap()
+            .insert(NodeId::from(node_id));
+    }
+
+    /// Report `text` as selected in a node; nodes without any set report no selection
+    pub fn set_selected_text(&self, node_id: &str, text: &str) {
+        self.selected_text
+            .lock()
+            .unwrap()
+            .insert(NodeId::from(node_id), text.to_string());
+    }
+
+    /// Name a node by a linked label element, as `AXTitleUIElement` does
+    pub fn set_title_element(&self, node_id: &str, label_id: &str) {
+        self.title_elements
+            .lock()
+            .unwrap()
+            .insert(NodeId::from(node_id), NodeId::from(label_id));
+    }
+
+    /// Set whether the app reports itself frontmost; it does by default
+    pub fn set_frontmost(&self, frontmost: bool) {
+        self.frontmost.store(frontmost, Ordering::SeqCst);
+    }
+
+    /// Put the mouse cursor at a screen point; it isn't reported until set
+    pub fn set_cursor(&self, x: f64, y: f64) {
+        *self.cursor.lock().unwrap() = Some((x, y));
+    }
+
+    /// Make every `get_node` call block for `delay`, like a slow platform API
+    pub fn set_delay(&self, delay: Duration) {
+        *self.delay.lock().unwrap() = delay;
+    }
+
+    /// Simulate the application quitting: every later call fails with [`AppTerminated`]
+    pub fn terminate(&self) {
+        self.terminated.store(true, Ordering::SeqCst);
+    }
+
+    /// Number of `get_node` calls made so far, including internal ones
+    pub fn get_node_calls(&self) -> usize {
+        self.get_node_calls.load(Ordering::SeqCst)
+    }
+
+    /// Attributes read so far, counting the role and each requested
+    /// [`NodeFields`] flag once per node, as a platform backend would
+    pub fn attribute_reads(&self) -> usize {
+        self.attribute_reads.load(Ordering::SeqCst)
+    }
+
+    fn read_node(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        self.get_node_calls.fetch_add(1, Ordering::SeqCst);
+        std::thread::sleep(*self.delay.lock().unwrap());
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(AppTerminated.into());
+        }
+        let mut node = self
+            .nodes
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .cloned()
+            .context("Node ID not found in mock tree")?;
+        if self.removed.lock().unwrap().contains(node_id) {
+            node = Node {
+                id: node.id,
+                role: "unknown".to_string(),
+                ..Node::default()
+            };
+        }
+
+        let reads = 1 + fields.0.count_ones() as usize;
+        self.attribute_reads.fetch_add(reads, Ordering::SeqCst);
+
+        if !fields.contains(NodeFields::NAME) {
+            node.name = None;
+        }
+        // Radio buttons report selection through their value, and belong to
+        // an enclosing radio group
+        if node.role == "AXRadioButton" {
+            node.checked = node
+                .checked
+                .or_else(|| super::parse_checked(node.value.as_deref()));
+            node.radio_group = self
+                .nodes
+                .lock()
+                .unwrap()
+                .values()
+                .find(|parent| parent.role == "AXRadioGroup" && parent.children.contains(node_id))
+                .map(|parent| parent.id.clone());
+        }
+        if fields.contains(NodeFields::TEXT_STATS)
+            && super::TEXT_ROLES.contains(&node.role.as_str())
+        {
+            node.char_count = node
+                .value
+                .as_ref()
+                .map(|value| value.encode_utf16().count());
+            if fields.contains(NodeFields::VALUE) {
+                node.line_count = node.value.as_deref().map(super::line_count);
+            }
+        }
+        if !fields.contains(NodeFields::VALUE) {
+            node.value = None;
+            node.value_description = None;
+        }
+        if !fields.contains(NodeFields::DESCRIPTION) {
+            node.description = None;
+        }
+        if !fields.contains(NodeFields::BOUNDS) {
+            node.bounds = None;
+        }
+        if !fields.contains(NodeFields::CHILDREN) {
+            node.children = Vec::new();
+        }
+        if !fields.contains(NodeFields::SELECTED_CHILDREN) {
+            node.selected_children = Vec::new();
+        }
+        if !fields.contains(NodeFields::ENABLED) {
+            node.enabled = None;
+        }
+        if !fields.contains(NodeFields::CHECKED) {
+            node.checked = None;
+            node.radio_group = None;
+        }
+        if !fields.contains(NodeFields::WINDOW_LEVEL) {
+            node.window_level = None;
+        }
+        // Nodes may override what their role implies
+        node.focusable = fields.contains(NodeFields::FOCUSABLE).then(|| {
+            node.focusable
+                .unwrap_or(super::is_focusable_role(&node.role))
+        });
+        Ok(node)
+    }
+}
+
+/// Build a node with the given id and role and no other attributes
+pub fn node(id: &str, role: &str) -> Node {
+    Node {
+        id: NodeId::from(id),
+        role: role.to_string(),
+        actions: vec![Action::Focus],
+        is_web_content: super::is_web_content_role(role),
+        ..Default::default()
+    }
+}
+
+/// A small named tree: `root -> (a -> (a1, a2), b -> (b1))`
+pub fn sample_tree() -> MockProvider {
+    let group = |id: &str, children: &[&str]| Node {
+        name: Some(id.to_string()),
+        children: children.iter().map(|c| NodeId::from(*c)).collect(),
+        ..node(id, "AXGroup")
+    };
+    MockProvider::new(vec![
+        group("root", &["a", "b"]),
+        group("a", &["a1", "a2"]),
+        group("a1", &[]),
+        group("a2", &[]),
+        group("b", &["b1"]),
+        group("b1", &[]),
+    ])
+}
+
+impl super::AccessibilityProvider for MockProvider {
+    fn get_root(&self) -> Result<Node> {
+        self.get_node(&self.root)
+    }
+
+    fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        self.get_node(node_id)?
+            .children
+            .iter()
+            .map(|id| self.get_node(id))
+            .collect()
+    }
+
+    fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        self.read_node(node_id, NodeFields::ALL)
+    }
+
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        if self.fields_failing.lock().unwrap().contains(node_id) {
+            anyhow::bail!("Attribute read failed");
+        }
+        self.read_node(node_id, fields)
+    }
+
+    fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(AppTerminated.into());
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes
+            .get_mut(node_id)
+            .context("Node ID not found in mock tree")?;
+        let old_value = node.value.clone();
+
+        match action {
+            Action::Press if matches!(node.role.as_str(), "AXCheckBox" | "AXRadioButton") => {
+                let checked = node.value.as_deref() == Some("1");
+                node.value = Some(if checked { "0" } else { "1" }.to_string());
+            }
+            Action::SetValue { value } => node.value = Some(value.clone()),
+            Action::TypeText { text } => {
+                node.value = Some(node.value.take().unwrap_or_default() + text);
+            }
+            Action::Focus => *self.focused.lock().unwrap() = Some(node_id.clone()),
+            _ => {}
+        }
+        if node.value != old_value {
+            self.note_value_change(node_id);
+        }
+
+        self.performed
+            .lock()
+            .unwrap()
+            .push((node_id.clone(), action.clone()));
+        Ok(())
+    }
+
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(AppTerminated.into());
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        anyhow::ensure!(
+            nodes.contains_key(window_id),
+            "Node ID not found in mock tree"
+        );
+        // The raised window goes above every other
+        let top = nodes.values().filter_map(|n| n.window_level).max();
+        for node in nodes.values_mut().filter(|n| n.role == "AXWindow") {
+            node.is_frontmost = node.id == *window_id;
+            if node.is_frontmost {
+                node.window_level = Some(top.map_or(0, |top| top + 1));
+            }
+        }
+        Ok(())
+    }
+
+    fn get_focused(&self) -> Result<Option<Node>> {
+        let focused = self.focused.lock().unwrap().clone();
+        focused.map(|id| self.get_node(&id)).transpose()
+    }
+
+    fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        anyhow::ensure!(
+            !self
+                .native_actions_failing
+                .lock()
+                .unwrap()
+                .contains(node_id),
+            "Failed to copy action names"
+        );
+        Ok(self.native_actions.lock().unwrap().get(node_id).cloned())
+    }
+
+    fn wait_for_value_change(&self, node_id: &NodeId, timeout: Duration) -> Result<Option<bool>> {
+        if !self.observable.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let (changes, changed) = &*self.value_changes;
+        let changes = changes.lock().unwrap();
+        let before = changes.get(node_id).copied().unwrap_or_default();
+        let (changes, _) = changed
+            .wait_timeout_while(changes, timeout, |changes| {
+                changes.get(node_id).copied().unwrap_or_default() == before
+            })
+            .unwrap();
+        Ok(Some(
+            changes.get(node_id).copied().unwrap_or_default() != before,
+        ))
+    }
+
+    fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        Ok(self.selected_text.lock().unwrap().get(node_id).cloned())
+    }
+
+    fn is_frontmost_app(&self) -> Result<bool> {
+        Ok(self.frontmost.load(Ordering::SeqCst))
+    }
+
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        let node = self.get_node(node_id)?;
+        Ok(NameSources {
+            title: node.name,
+            title_ui_element: self.title_elements.lock().unwrap().get(node_id).cloned(),
+            description: node.description,
+            value: node.value,
+            ..NameSources::default()
+        })
+    }
+
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Err(AppTerminated.into());
+        }
+        self.attribute_reads.fetch_add(1, Ordering::SeqCst);
+        Ok(self.nodes.lock().unwrap().contains_key(node_id)
+            && !self.removed.lock().unwrap().contains(node_id))
+    }
+
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        Ok(*self.cursor.lock().unwrap())
+    }
+
+    /// Descends from the root through the last child whose bounds hold the
+    /// point, as later children are drawn on top
+    fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        let contains = |node: &Node| node.bounds.is_some_and(|b| b.contains_point(x, y));
+        let mut hit = None;
+        let mut candidates = vec![self.get_root()?];
+        while let Some(node) = candidates.into_iter().rev().find(|n| contains(n)) {
+            candidates = self.get_children(&node.id)?;
+            hit = Some(node);
+        }
+        Ok(hit)
+    }
+
+    /// Each role in the tree, with the actions of the first node found with it
+    fn roles(&self) -> Vec<RoleEntry> {
+        let nodes = self.nodes.lock().unwrap();
+        let mut roles: Vec<RoleEntry> = Vec::new();
+        for node in nodes.values() {
+            if roles.iter().all(|entry| entry.role != node.role) {
+                roles.push(RoleEntry {
+                    role: node.role.clone(),
+                    normalized_role: None,
+                    actions: node
+                        .actions
+                        .iter()
+                        .map(|a| a.action_type().to_string())
+                        .collect(),
+                });
+            }
+        }
+        roles.sort_by(|a, b| a.role.cmp(&b.role));
+        roles
+    }
+}
@@ -1,14 +1,220 @@
 //! Platform-specific accessibility backends
 
-use crate::protocol::{Action, Node, NodeId};
+use crate::config::Config;
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
 use anyhow::Result;
+use std::ops::{BitOr, ControlFlow};
+use std::time::Duration;
 
+mod async_provider;
+mod defaults;
 #[cfg(target_os = "macos")]
 mod macos;
 
+pub use async_provider::{AsyncAccessibilityProvider, BlockingAdapter, SyncAdapter};
+/// For implementing [`AsyncAccessibilityProvider`]
+pub use async_trait::async_trait;
+
 #[cfg(target_os = "macos")]
 pub use macos::MacOSProvider;
 
+#[cfg(test)]
+pub(crate) mod mock;
+
+/// Whether a node with this role hosts web content, whose subtree is
+/// typically large and structured differently from native controls
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+pub(crate) fn is_web_content_role(role: &str) -> bool {
+    role == "AXWebArea"
+}
+
+/// Roles holding editable or static text, whose text can be selected and
+/// whose nodes report `char_count` and `line_count`
+pub(crate) const TEXT_ROLES: &[&str] = &[
+    "AXTextField",
+    "AXTextArea",
+    "AXSearchField",
+    "AXComboBox",
+    "AXStaticText",
+];
+
+/// Whether elements with this role take keyboard focus whatever they report
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+pub(crate) fn is_focusable_role(role: &str) -> bool {
+    matches!(
+        role,
+        "AXButton"
+            | "AXCheckBox"
+            | "AXRadioButton"
+            | "AXSlider"
+            | "AXPopUpButton"
+            | "AXMenuButton"
+            | "AXTextField"
+            | "AXTextArea"
+            | "AXSearchField"
+            | "AXComboBox"
+            | "AXLink"
+    )
+}
+
+/// The type of the [`Action`] that performs a native action, if one does.
+///
+/// Native actions without one can still be performed as [`Action::Custom`].
+pub(crate) fn action_for_native_name(name: &str) -> Option<&'static str> {
+    match name {
+        "AXPress" => Some("press"),
+        "AXRaise" => Some("focus"),
+        "AXIncrement" => Some("increment"),
+        "AXDecrement" => Some("decrement"),
+        "AXShowMenu" => Some("context_menu"),
+        _ => None,
+    }
+}
+
+/// Parse the checked state out of a toggle's value ("0"/"1"/"2", as AXValue
+/// reports it, or "checked"/"unchecked"/"mixed" when synthesized).
+///
+/// A mixed checkbox counts as unchecked, since pressing it is what checks it.
+pub(crate) fn parse_checked(value: Option<&str>) -> Option<bool> {
+    match value?.trim() {
+        "true" | "checked" => Some(true),
+        "false" | "unchecked" | "mixed" | "2" => Some(false),
+        v => v.parse::<f64>().ok().map(|n| n != 0.0),
+    }
+}
+
+/// Why `node` can't be brought to the `checked` state, if it can't
+pub(crate) fn set_checked_refusal(node: &Node, checked: bool) -> Option<String> {
+    match parse_checked(node.value.as_deref()) {
+        None => Some(format!("Node {} has no checked state", node.id.as_str())),
+        // Pressing a selected radio button leaves it selected
+        Some(true) if !checked && node.role == "AXRadioButton" => Some(
+            "a radio button can't be unchecked; check another one in its group instead".to_string(),
+        ),
+        Some(_) => None,
+    }
+}
+
+/// Bring a toggle to the `checked` state by pressing it, unless it's
+/// already there; returns whether it was pressed
+pub(crate) fn set_checked<P: AccessibilityProvider + ?Sized>(
+    provider: &P,
+    node: &Node,
+    checked: bool,
+) -> Result<bool> {
+    if let Some(reason) = set_checked_refusal(node, checked) {
+        anyhow::bail!(reason);
+    }
+    if parse_checked(node.value.as_deref()) == Some(checked) {
+        return Ok(false);
+    }
+    provider.perform_action(&node.id, &Action::Press)?;
+    Ok(true)
+}
+
+/// Number of lines in `text`: one more than its newline count
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+pub(crate) fn line_count(text: &str) -> usize {
+    text.split('\n').count()
+}
+
+/// Error reported by a provider once the application it inspects has quit.
+///
+/// Providers return it (wrapped in `anyhow::Error`) from calls on a dead
+/// application so the server can tell agents the app is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppTerminated;
+
+impl std::fmt::Display for AppTerminated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the target application has terminated")
+    }
+}
+
+impl std::error::Error for AppTerminated {}
+
+/// Whether `error` (or anything it wraps) is [`AppTerminated`]
+pub(crate) fn is_app_terminated(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<AppTerminated>())
+}
+
+/// Node attributes a provider may skip reading when the caller doesn't need them.
+///
+/// `id`, `role`, `subrole`, and what is derived from the role (`actions`,
+/// `is_web_content`) are always filled in. Skipped attributes are left empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeFields(u16);
+
+impl NodeFields {
+    pub const NONE: Self = Self(0);
+    pub const NAME: Self = Self(1 << 0);
+    /// `value` and `value_description`
+    pub const VALUE: Self = Self(1 << 1);
+    pub const DESCRIPTION: Self = Self(1 << 2);
+    pub const BOUNDS: Self = Self(1 << 3);
+    pub const CHILDREN: Self = Self(1 << 4);
+    pub const SELECTED_CHILDREN: Self = Self(1 << 5);
+    pub const ENABLED: Self = Self(1 << 6);
+    /// `checked`, and `radio_group` for radio buttons
+    pub const CHECKED: Self = Self(1 << 7);
+    /// `char_count` of text roles, and `line_count` when the value is read too
+    pub const TEXT_STATS: Self = Self(1 << 8);
+    /// Every attribute read by default
+    pub const ALL: Self = Self((1 << 9) - 1);
+    /// `focusable`. Opt-in, so not part of [`ALL`](Self::ALL): it costs an
+    /// extra query per node and is only read when a projection names it
+    pub const FOCUSABLE: Self = Self(1 << 9);
+    /// `window_level`. Opt-in like [`FOCUSABLE`](Self::FOCUSABLE): each
+    /// window reads the application's whole window list to find its place
+    pub const WINDOW_LEVEL: Self = Self(1 << 10);
+    /// What [`Node::content_fingerprint`] covers
+    pub const FINGERPRINT: Self = Self(Self::NAME.0 | Self::BOUNDS.0 | Self::CHILDREN.0);
+
+    /// Whether every attribute in `other` is included
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The attributes needed to fill in the named fields of a projection
+    pub fn for_projection(fields: &[String]) -> Self {
+        fields
+            .iter()
+            .map(|field| match field.as_str() {
+                "name" => Self::NAME,
+                "value" | "value_description" => Self::VALUE,
+                "description" => Self::DESCRIPTION,
+                // Visibility is worked out from bounds
+                "bounds" | "visible" | "visual" => Self::BOUNDS,
+                "children" | "child_count" => Self::CHILDREN,
+                "selected_children" => Self::SELECTED_CHILDREN,
+                "enabled" => Self::ENABLED,
+                "checked" | "radio_group" => Self::CHECKED,
+                "char_count" => Self::TEXT_STATS,
+                // Lines are counted from the value
+                "line_count" => Self::TEXT_STATS | Self::VALUE,
+                "focusable" => Self::FOCUSABLE,
+                "window_level" => Self::WINDOW_LEVEL,
+                "fingerprint" => Self::FINGERPRINT,
+                _ => Self::NONE,
+            })
+            .fold(Self::NONE, BitOr::bitor)
+    }
+}
+
+impl Default for NodeFields {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for NodeFields {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
 /// Trait for consuming accessibility data from platform APIs
 pub trait AccessibilityProvider: Send + Sync {
     /// Get the root accessibility node for this process
@@ -20,19 +226,277 @@ pub trait AccessibilityProvider: Send + Sync {
     /// Get a specific node by ID
     fn get_node(&self, node_id: &NodeId) -> Result<Node>;
 
+    /// Get a node, reading only the given attributes where the platform allows.
+    ///
+    /// Defaults to [`get_node`](Self::get_node), which reads everything.
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        let _ = fields;
+        self.get_node(node_id)
+    }
+
     /// Perform an accessibility action on a node
     fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()>;
+
+    /// Re-read the attributes of a node that has already been resolved.
+    ///
+    /// Use this after changing a node (e.g. performing an action on it) to
+    /// observe its new state. Defaults to [`get_node`](Self::get_node).
+    fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        self.get_node(node_id)
+    }
+
+    /// Get a node's parent; `None` for the root.
+    ///
+    /// Defaults to searching down from the root, giving up with an error
+    /// after [`DEFAULT_MAX_NODES`](crate::traversal::DEFAULT_MAX_NODES) nodes.
+    fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let mut search = defaults::ParentSearch::new(node_id, self.get_root()?);
+        loop {
+            match search.next()? {
+                ControlFlow::Continue(id) => search.push_children(self.get_children(&id)?),
+                ControlFlow::Break(parent) => return Ok(parent),
+            }
+        }
+    }
+
+    /// Get the node that currently has keyboard focus, if the platform reports one
+    fn get_focused(&self) -> Result<Option<Node>> {
+        Ok(None)
+    }
+
+    /// Get the window that holds `node_id`, or the node itself if it is a window.
+    ///
+    /// Defaults to walking up through [`get_parent`](Self::get_parent).
+    fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let mut walk = defaults::WindowWalk::default();
+        let mut node = self.get_node(node_id)?;
+        loop {
+            match walk.step(node) {
+                ControlFlow::Continue(id) => match self.get_parent(&id)? {
+                    Some(parent) => node = parent,
+                    None => return Ok(None),
+                },
+                ControlFlow::Break(window) => return Ok(window),
+            }
+        }
+    }
+
+    /// Get the window that holds keyboard focus, if any.
+    ///
+    /// Defaults to the window holding [`get_focused`](Self::get_focused)'s node.
+    fn get_focused_window(&self) -> Result<Option<Node>> {
+        match self.get_focused()? {
+            Some(focused) => self.get_window(&focused.id),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the application's menu bar, if it has one.
+    ///
+    /// Defaults to the first child of the root with role `AXMenuBar`.
+    fn get_menu_bar(&self) -> Result<Option<Node>> {
+        let children = self.get_children(&self.get_root()?.id)?;
+        Ok(defaults::menu_bar(children))
+    }
+
+    /// The mouse cursor's position in screen coordinates, with the top-left
+    /// origin that bounds use; `None` if the platform can't tell
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        Ok(None)
+    }
+
+    /// The deepest node at a screen point; `None` if the application has
+    /// nothing there or the platform can't hit-test
+    fn node_at_point(&self, _x: f64, _y: f64) -> Result<Option<Node>> {
+        Ok(None)
+    }
+
+    /// Whether the application is the frontmost one, and so receives synthesized input.
+    ///
+    /// Defaults to `true`, for platforms that can't tell.
+    fn is_frontmost_app(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Whether `node_id` still refers to a live element, reading as little as
+    /// the platform allows.
+    ///
+    /// Defaults to whether the node can be read at all; platforms that
+    /// report destroyed elements with placeholder attributes must override it.
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        Ok(self.get_node_fields(node_id, NodeFields::NONE).is_ok())
+    }
+
+    /// Up to `length` UTF-16 code units of a text element's value from
+    /// `offset`, with the length of the whole value in the same units, read
+    /// without transferring all of it; `None` if the platform can't read
+    /// ranges of this element. A range ending inside a surrogate pair stops
+    /// before the pair.
+    fn text_range(
+        &self,
+        _node_id: &NodeId,
+        _offset: usize,
+        _length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        Ok(None)
+    }
+
+    /// The language and region codes of the application's locale, each
+    /// `None` if it can't be determined
+    fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        Ok((None, None))
+    }
+
+    /// Give a node keyboard focus, so typed keys go to it.
+    ///
+    /// Defaults to performing [`Action::Focus`] on it.
+    fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        self.perform_action(node_id, &Action::Focus)
+    }
+
+    /// The text selected in a text element; `None` if nothing is selected or
+    /// the platform doesn't report it
+    fn selected_text(&self, _node_id: &NodeId) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Bring a window to the front and make it the application's main, focused window.
+    ///
+    /// Defaults to performing [`Action::Focus`] on it.
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        self.perform_action(window_id, &Action::Focus)
+    }
+
+    /// The attributes that could give a node its accessible name.
+    ///
+    /// Defaults to the node's name (as the title), description, and value.
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        Ok(defaults::name_sources(self.get_node(node_id)?))
+    }
+
+    /// The roles this backend knows, with the actions it gives each by default.
+    ///
+    /// Nodes may still report roles not listed here. Defaults to none.
+    fn roles(&self) -> Vec<RoleEntry> {
+        Vec::new()
+    }
+
+    /// The platform's own names for the actions a node supports (e.g. `AXPress`).
+    ///
+    /// Used to check [`Action::Custom`] names before performing them. `None`
+    /// means the platform can't list them, and custom actions are passed through.
+    fn native_action_names(&self, _node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+
+    /// Block until `node_id`'s value changes or `timeout` passes, returning
+    /// whether it changed; `None` if the platform can't be notified of
+    /// changes, and the caller has to poll instead.
+    ///
+    /// A change that is undone before the caller looks still counts.
+    fn wait_for_value_change(&self, _node_id: &NodeId, _timeout: Duration) -> Result<Option<bool>> {
+        Ok(None)
+    }
+}
+
+/// Whether this process has been granted accessibility access.
+///
+/// With `prompt`, macOS shows its dialog pointing the user at System Settings
+/// if access hasn't been granted yet; the result still reflects the state
+/// before they respond. Platforms without such a gate always return `true`.
+pub fn request_accessibility_permission(prompt: bool) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        macos::request_accessibility_permission(prompt)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = prompt;
+        true
+    }
 }
 
 /// Create the appropriate provider for the current platform
 pub fn create_provider() -> Result<Box<dyn AccessibilityProvider>> {
+    create_provider_with_config(&Config::default())
+}
+
+/// Create the platform provider, honouring the provider options in `config`
+pub fn create_provider_with_config(config: &Config) -> Result<Box<dyn AccessibilityProvider>> {
     #[cfg(target_os = "macos")]
     {
-        Ok(Box::new(MacOSProvider::new()?))
+        Ok(Box::new(MacOSProvider::with_id_scheme(config.id_scheme)?))
     }
 
     #[cfg(not(target_os = "macos"))]
     {
+        let _ = config;
         anyhow::bail!("Unsupported platform")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::mock::{node, MockProvider};
+    use super::*;
+    use crate::traversal::DEFAULT_MAX_NODES;
+
+    #[test]
+    fn refresh_defaults_to_get_node() {
+        let provider = MockProvider::new(vec![node("field", "AXTextField")]);
+        let mut updated = node("field", "AXTextField");
+        updated.value = Some("typed".to_string());
+        provider.set_node(updated);
+
+        let refreshed = provider.refresh(&NodeId::from("field")).unwrap();
+        assert_eq!(refreshed.value.as_deref(), Some("typed"));
+        assert!(provider.refresh(&NodeId::from("missing")).is_err());
+    }
+
+    #[test]
+    fn parent_search_is_bounded() {
+        let tree = super::mock::sample_tree();
+        assert_eq!(
+            tree.get_parent(&NodeId::from("a1")).unwrap().unwrap().id,
+            NodeId::from("a")
+        );
+        assert!(tree.get_parent(&NodeId::from("root")).unwrap().is_none());
+
+        // A chain longer than the limit, looping back on itself at the end
+        let count = DEFAULT_MAX_NODES + 10;
+        let chain: Vec<_> = (0..count)
+            .map(|i| Node {
+                children: vec![NodeId::from(((i + 1) % count).to_string())],
+                ..node(&i.to_string(), "AXGroup")
+            })
+            .collect();
+        let provider = MockProvider::new(chain);
+        assert!(provider.get_parent(&NodeId::from("missing")).is_err());
+    }
+
+    #[test]
+    fn permission_check_returns_without_prompting() {
+        let trusted = request_accessibility_permission(false);
+        if cfg!(not(target_os = "macos")) {
+            assert!(trusted);
+        }
+    }
+
+    #[test]
+    fn projections_map_to_the_attributes_they_need() {
+        let fields = |names: &[&str]| {
+            NodeFields::for_projection(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(fields(&["role", "actions"]), NodeFields::NONE);
+        assert_eq!(
+            fields(&["name", "value_description", "child_count"]),
+            NodeFields::NAME | NodeFields::VALUE | NodeFields::CHILDREN
+        );
+        assert!(NodeFields::ALL.contains(fields(&["bounds", "checked", "enabled"])));
+        assert_eq!(fields(&["char_count"]), NodeFields::TEXT_STATS);
+        assert!(fields(&["line_count"]).contains(NodeFields::VALUE));
+        assert!(!NodeFields::NAME.contains(NodeFields::VALUE));
+    }
+}
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 ///
 /// The format is platform-specific but guaranteed to be stable
 /// for the lifetime of the node.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NodeId(String);
 
 impl NodeId {
@@ -41,6 +41,54 @@ pub struct Rect {
     pub height: f64,
 }
 
+impl Rect {
+    /// The screen point at fractions `dx`, `dy` of the width and height,
+    /// measured from the top-left corner
+    pub fn point_at(&self, dx: f64, dy: f64) -> (f64, f64) {
+        (self.x + self.width * dx, self.y + self.height * dy)
+    }
+
+    /// Whether the point `x`, `y` lies within this rectangle, edges included
+    pub fn contains_point(&self, x: f64, y: f64) -> bool {
+        x >= self.x && y >= self.y && x <= self.x + self.width && y <= self.y + self.height
+    }
+
+    /// Whether `other` lies entirely within this rectangle, edges included
+    pub fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// Smallest width and height, in points, a control should have to be easy
+/// to hit, per Apple's Human Interface Guidelines
+pub const MIN_TARGET_SIZE: f64 = 44.0;
+
+/// Visual properties of a node for accessibility audits, worked out from
+/// its bounds alone; nothing is captured from the screen
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VisualInfo {
+    /// The smaller of the node's width and height, in points
+    pub min_target_dimension: f64,
+    /// Narrower or shorter than [`MIN_TARGET_SIZE`], for a node with actions;
+    /// static content is never flagged, since it isn't a target
+    pub below_min_target: bool,
+}
+
+impl VisualInfo {
+    /// What `node`'s bounds say, if it has any
+    pub fn for_node(node: &Node) -> Option<Self> {
+        let bounds = node.bounds.as_ref()?;
+        let min_target_dimension = bounds.width.min(bounds.height);
+        Some(Self {
+            min_target_dimension,
+            below_min_target: !node.actions.is_empty() && min_target_dimension < MIN_TARGET_SIZE,
+        })
+    }
+}
+
 /// Actions that can be performed on accessibility nodes.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -61,19 +109,340 @@ pub enum Action {
     ContextMenu,
     /// Platform-specific custom action
     Custom { name: String },
+    /// Set a checkbox, radio button, or switch to a known state,
+    /// pressing it only when the current state differs. `checked` is
+    /// required to perform it; nodes list it without one
+    SetChecked {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        checked: Option<bool>,
+    },
+    /// Click at a point inside the element, given as fractions (0..1) of its
+    /// bounds from the top-left corner, for controls whose parts aren't
+    /// exposed as separate elements
+    PressAt { dx: f64, dy: f64 },
+    /// Focus the element, then type `text` one key event per character, for
+    /// fields that only react to real keystrokes. Requires the `keystrokes` feature
+    TypeText { text: String },
+}
+
+impl Action {
+    /// Every action type name, as used in the `type` tag on the wire
+    pub const TYPES: &'static [&'static str] = &[
+        "focus",
+        "press",
+        "increment",
+        "decrement",
+        "set_value",
+        "scroll",
+        "context_menu",
+        "custom",
+        "set_checked",
+        "press_at",
+        "type_text",
+    ];
+
+    /// The type name of this action, as used in the `type` tag on the wire
+    pub fn action_type(&self) -> &'static str {
+        match self {
+            Action::Focus => "focus",
+            Action::Press => "press",
+            Action::Increment => "increment",
+            Action::Decrement => "decrement",
+            Action::SetValue { .. } => "set_value",
+            Action::Scroll { .. } => "scroll",
+            Action::ContextMenu => "context_menu",
+            Action::Custom { .. } => "custom",
+            Action::SetChecked { .. } => "set_checked",
+            Action::PressAt { .. } => "press_at",
+            Action::TypeText { .. } => "type_text",
+        }
+    }
+
+    /// Encode this action as a terse string for logging or command-line entry.
+    ///
+    /// Simple actions are a single character (`f`ocus, `p`ress, `i`ncrement,
+    /// `d`ecrement, context `m`enu); parameterized ones append their argument
+    /// after a colon: `v:<text>`, `s:<x>,<y>`, `c:<name>`, `k:<0|1>`,
+    /// `a:<dx>,<dy>` (press `a`t), `t:<text>`. This is
+    /// independent of the JSON wire format.
+    pub fn to_compact(&self) -> String {
+        match self {
+            Action::Focus => "f".to_string(),
+            Action::Press => "p".to_string(),
+            Action::Increment => "i".to_string(),
+            Action::Decrement => "d".to_string(),
+            Action::ContextMenu => "m".to_string(),
+            Action::SetValue { value } => format!("v:{}", value),
+            Action::Scroll { x, y } => format!("s:{},{}", x, y),
+            Action::Custom { name } => format!("c:{}", name),
+            Action::SetChecked {
+                checked: Some(checked),
+            } => format!("k:{}", u8::from(*checked)),
+            Action::SetChecked { checked: None } => "k".to_string(),
+            Action::PressAt { dx, dy } => format!("a:{},{}", dx, dy),
+            Action::TypeText { text } => format!("t:{}", text),
+        }
+    }
+
+    /// Parse a string produced by [`Action::to_compact`]
+    pub fn from_compact(code: &str) -> anyhow::Result<Self> {
+        let (tag, arg) = match code.split_once(':') {
+            Some((tag, arg)) => (tag, Some(arg)),
+            None => (code, None),
+        };
+
+        Ok(match (tag, arg) {
+            ("f", None) => Action::Focus,
+            ("p", None) => Action::Press,
+            ("i", None) => Action::Increment,
+            ("d", None) => Action::Decrement,
+            ("m", None) => Action::ContextMenu,
+            ("v", Some(value)) => Action::SetValue {
+                value: value.to_string(),
+            },
+            ("s", Some(amounts)) => {
+                let (x, y) = amounts
+                    .split_once(',')
+                    .ok_or_else(|| anyhow::anyhow!("scroll needs x,y: {}", code))?;
+                Action::Scroll {
+                    x: x.trim().parse()?,
+                    y: y.trim().parse()?,
+                }
+            }
+            ("c", Some(name)) => Action::Custom {
+                name: name.to_string(),
+            },
+            ("t", Some(text)) => Action::TypeText {
+                text: text.to_string(),
+            },
+            ("k", None) => Action::SetChecked { checked: None },
+            ("k", Some("1")) => Action::SetChecked {
+                checked: Some(true),
+            },
+            ("k", Some("0")) => Action::SetChecked {
+                checked: Some(false),
+            },
+            ("a", Some(offsets)) => {
+                let (dx, dy) = offsets
+                    .split_once(',')
+                    .ok_or_else(|| anyhow::anyhow!("press_at needs dx,dy: {}", code))?;
+                Action::PressAt {
+                    dx: dx.trim().parse()?,
+                    dy: dy.trim().parse()?,
+                }
+            }
+            _ => anyhow::bail!("Unknown compact action: {}", code),
+        })
+    }
 }
 
 /// An accessibility tree node with normalized properties.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Node {
     pub id: NodeId,
     pub role: String,
+    /// Platform refinement of `role`, e.g. `AXSecureTextField` for a password field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subrole: Option<String>,
+    /// The AccessKit equivalent of `role`, e.g. `"CheckBox"`. Only reported
+    /// when built with the `accesskit` feature and `normalize_roles` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub akit_role: Option<String>,
     pub name: Option<String>,
     pub value: Option<String>,
+    /// Human-friendly rendering of `value` (e.g. "73%" for a slider at 0.73)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_description: Option<String>,
     pub description: Option<String>,
     pub bounds: Option<Rect>,
+    /// Audit info worked out from `bounds`. Only reported when built with
+    /// the `visual` feature and `Config::visual` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visual: Option<VisualInfo>,
     pub actions: Vec<Action>,
     pub children: Vec<NodeId>,
+    /// Anonymous wrapper groups spliced out above this node, outermost first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collapsed_from: Vec<NodeId>,
+    /// Whether the node is on screen: inside every enclosing window and scroll
+    /// area, in the frontmost window. Set by `query_tree` and `is_visible`;
+    /// `None` when unknown, e.g. for nodes without bounds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    /// Currently selected children, for lists, tab groups, and radio groups
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selected_children: Vec<NodeId>,
+    /// Whether the element accepts interaction (`false` for disabled/grayed controls)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Whether the element can take keyboard focus at all, independent of
+    /// `enabled`. Only read when named in a request's `fields`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focusable: Option<bool>,
+    /// Checkmark state for menu items; for radio buttons, whether this is
+    /// the selected option of its group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// The `AXRadioGroup` a radio button belongs to. Radio buttons sharing
+    /// one are mutually exclusive options
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub radio_group: Option<NodeId>,
+    /// Number of children, reported in place of `children` when they were left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child_count: Option<usize>,
+    /// Length of a text element's value in UTF-16 code units (the unit
+    /// `get_text_chunk` offsets use), for text field, text area, and static
+    /// text roles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_count: Option<usize>,
+    /// Number of lines in a text element's value, counted from its newlines.
+    /// Only read along with the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_count: Option<usize>,
+    /// Levels below the node a query or search started from (which is depth 0).
+    /// Set by tree walks only, never on a bare `get_node`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<usize>,
+    /// Position in the parent's `children`, for rebuilding a tree from a flat
+    /// list. Set by tree walks only; `None` for the node the walk started from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_in_parent: Option<usize>,
+    /// Server epoch this node was reported in. Ids from an older epoch may
+    /// since have been reused for different elements and should be re-queried.
+    #[serde(default)]
+    pub epoch: u64,
+    /// Digest of the node's role, name, bounds, and child count (see
+    /// [`Node::content_fingerprint`]), for cheaply checking whether a cached
+    /// node still looks the same. Filled in by the server on responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Whether this node is the root of embedded web content (e.g. a WKWebView)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_web_content: bool,
+    /// Whether this window is the application's main (frontmost) window.
+    /// Only ever set on windows
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_frontmost: bool,
+    /// Stacking position among the application's windows: higher is nearer
+    /// the front. Only ever set on windows, by `list_windows`, `focus_window`,
+    /// and projections naming it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_level: Option<i64>,
+}
+
+impl Node {
+    /// Serialize this node keeping only the named fields.
+    ///
+    /// `id` is always included; unknown field names are ignored.
+    pub fn project(&self, fields: &[String]) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("Node always serializes");
+        if let serde_json::Value::Object(map) = &mut value {
+            map.retain(|key, _| key == "id" || fields.iter().any(|f| f == key));
+        }
+        value
+    }
+
+    /// A 16-digit hex digest of the fields that identify what a node looks
+    /// like: role, name, bounds, and number of children.
+    ///
+    /// Uses FNV-1a, so the value is the same across runs and builds.
+    pub fn content_fingerprint(&self) -> String {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(self.role.as_bytes());
+        bytes.push(0xff);
+        bytes.extend_from_slice(self.name.as_deref().unwrap_or_default().as_bytes());
+        bytes.push(0xff);
+        if let Some(b) = self.bounds {
+            for v in [b.x, b.y, b.width, b.height] {
+                bytes.extend_from_slice(&v.to_bits().to_le_bytes());
+            }
+        }
+        bytes.push(0xff);
+        let child_count = self.child_count.unwrap_or(self.children.len());
+        bytes.extend_from_slice(&(child_count as u64).to_le_bytes());
+        format!("{:016x}", fnv1a(&bytes))
+    }
+
+    /// The names of the serialized fields that differ between two nodes, sorted.
+    ///
+    /// `id`, `epoch`, and `fingerprint` are not compared, so two different elements, or two
+    /// readings of the same one, can be compared alike.
+    pub fn changed_fields(&self, other: &Node) -> Vec<String> {
+        let to_map = |node: &Node| match serde_json::to_value(node) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => unreachable!("Node always serializes to an object"),
+        };
+        let (a, b) = (to_map(self), to_map(other));
+
+        let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| !matches!(key.as_str(), "id" | "epoch" | "fingerprint"))
+            .filter(|key| a.get(*key) != b.get(*key))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Order in which tree traversals visit nodes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraversalOrder {
+    /// Stack-based depth-first search; siblings are visited last to first
+    DepthFirst,
+    /// Level by level, following child order within each level
+    BreadthFirst,
+    /// Pre-order depth-first search following child order (reading order of the tree)
+    #[default]
+    DocumentOrder,
+}
+
+/// How search results are ordered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// The order the traversal visited them in
+    #[default]
+    Traversal,
+    /// Visual reading order: top to bottom, then left to right, by bounds.
+    /// Nodes without bounds come last, in traversal order.
+    ReadingOrder,
+}
+
+/// How `find_by_name` compares its pattern with node names; every mode ignores case
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// The name contains the pattern
+    #[default]
+    Substring,
+    /// The whole name matches a shell-style glob, e.g. `*Button`
+    Glob,
+    /// The name contains a match for a regular expression, e.g. `^(OK|Cancel)$`
+    Regex,
+}
+
+/// Direction for moving keyboard focus among focusable controls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusDirection {
+    /// The control after the focused one, wrapping to the first
+    Next,
+    /// The control before the focused one, wrapping to the last
+    Previous,
+    First,
+    Last,
+}
+
+/// Screen direction for finding a node beside another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Left,
+    Right,
+    Above,
+    Below,
 }
 
 /// MCP request types
@@ -96,16 +465,275 @@ pub enum Request {
         max_depth: Option<usize>,
         #[serde(default)]
         max_nodes: Option<usize>,
+        /// Only include these node fields in the response (`id` is always kept)
+        #[serde(default)]
+        fields: Option<Vec<String>>,
+        #[serde(default)]
+        order: TraversalOrder,
+        /// Only traverse this window's subtree (optional)
+        #[serde(default)]
+        window_id: Option<NodeId>,
+        /// Return what was found so far, marked `truncated`, after this many milliseconds
+        #[serde(default)]
+        deadline_ms: Option<u64>,
     },
     /// Get a specific node by ID
-    GetNode { node_id: NodeId },
+    GetNode {
+        node_id: NodeId,
+        /// Only include these node fields in the response (`id` is always kept)
+        #[serde(default)]
+        fields: Option<Vec<String>>,
+        /// Return child ids; when false `children` is empty and `child_count` is set
+        #[serde(default = "default_true")]
+        include_children: bool,
+        /// Return each action with a `description` of what it does for this node's role
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        describe_actions: bool,
+    },
     /// Perform an action on a node
-    PerformAction { node_id: NodeId, action: Action },
-    /// Find nodes by name (substring match)
-    FindByName { name: String },
+    PerformAction {
+        node_id: NodeId,
+        action: Action,
+        /// Retries carrying the same key within a minute get the first
+        /// success back instead of performing the action again
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        idempotency_key: Option<String>,
+    },
+    /// Find nodes by name (substring match unless `match_mode` says otherwise)
+    FindByName {
+        name: String,
+        #[serde(default)]
+        match_mode: MatchMode,
+        #[serde(default)]
+        order: TraversalOrder,
+        /// Only search this window's subtree (optional)
+        #[serde(default)]
+        window_id: Option<NodeId>,
+        #[serde(default)]
+        sort: SortOrder,
+        /// Return what was found so far, marked `truncated`, after this many milliseconds
+        #[serde(default)]
+        deadline_ms: Option<u64>,
+        /// Return child ids; by default `children` is empty and `child_count` is set
+        #[serde(default)]
+        include_children: bool,
+    },
+    /// Poll the tree until a node matching all given criteria appears
+    WaitFor {
+        /// Exact role to match (optional)
+        #[serde(default)]
+        role: Option<String>,
+        /// Case-insensitive substring of the node's name (optional)
+        #[serde(default)]
+        name_contains: Option<String>,
+        timeout_ms: u64,
+        /// Hard limit in milliseconds that also cuts short a tree scan in
+        /// progress; fails with `transient` when reached
+        #[serde(default)]
+        deadline_ms: Option<u64>,
+    },
+    /// Move keyboard focus to another focusable control, like pressing Tab
+    MoveFocus { direction: FocusDirection },
+    /// Get the selected children of a list, tab group, or radio group
+    GetSelection { node_id: NodeId },
+    /// Find nodes that offer an action of the given type (e.g. `press`)
+    FindByAction {
+        action_type: String,
+        /// Return child ids; by default `children` is empty and `child_count` is set
+        #[serde(default)]
+        include_children: bool,
+    },
+    /// Summarize a node in one human-readable sentence
+    DescribeNode { node_id: NodeId },
+    /// Get recently performed actions, oldest first
+    GetActionLog {
+        /// Return at most this many of the latest entries
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Get a node with `visible` filled in, to check it isn't scrolled away or occluded
+    IsVisible { node_id: NodeId },
+    /// Get a window's frame in screen coordinates
+    GetWindowBounds {
+        /// The window to measure; the focused window if omitted
+        #[serde(default)]
+        window_id: Option<NodeId>,
+    },
+    /// List the applications this server can target
+    ListTargets,
+    /// Check that each step's node exists and supports its action, without performing anything
+    ValidatePlan { steps: Vec<ActionStep> },
+    /// Wait for one node's value to change, returning the node with its new value
+    WatchNode { node_id: NodeId, timeout_ms: u64 },
+    /// Count the nodes in a subtree without returning them
+    CountNodes {
+        /// Where to start counting; the application root if omitted
+        #[serde(default)]
+        root: Option<NodeId>,
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// Render the tree as a GraphViz DOT digraph, returned as text
+    RenderDot {
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// Bring a window to the front and make it the application's main window
+    FocusWindow { window_id: NodeId },
+    /// List the fields that differ between two nodes
+    CompareNodes { a: NodeId, b: NodeId },
+    /// Get the text currently selected in a text element
+    GetSelectedText { node_id: NodeId },
+    /// Get the other children of a node's parent
+    GetSiblings { node_id: NodeId },
+    /// Write the tree, with metadata, to a new JSON file in the server's export directory
+    ExportTree {
+        path: String,
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// Read the tree `count` times, `interval_ms` apart, to catch transient states
+    CaptureSnapshots { count: usize, interval_ms: u64 },
+    /// List the nodes that can be acted on with more than a focus
+    QueryInteractive {
+        /// Maximum number of nodes to visit (default 1000)
+        #[serde(default)]
+        max_nodes: Option<usize>,
+    },
+    /// List the roles the platform backend reports, with the actions each gets
+    ListRoles,
+    /// Report which attributes could name a node and whether it has an accessible name
+    AuditNode { node_id: NodeId },
+    /// Check the whole tree for common accessibility problems
+    AuditTree {
+        /// Maximum number of nodes to visit (default 1000)
+        #[serde(default)]
+        max_nodes: Option<usize>,
+    },
+    /// Find every node whose bounds lie entirely within `rect`
+    FindInRect { rect: Rect },
+    /// Read the application's menu bar and its menus
+    GetMenuBar {
+        /// Levels below the menu bar to include (default 3: menu bar items,
+        /// their menus, and the menu items)
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// List the platform's own names for a node's actions, and which
+    /// [`Action`] performs each
+    GetNativeActions { node_id: NodeId },
+    /// Describe where a node sits as the names of its ancestors, e.g.
+    /// "Main > Login > Submit"
+    GetBreadcrumb { node_id: NodeId },
+    /// Get the node under the mouse cursor, without moving it
+    HoveredNode,
+    /// Hash a subtree's node fingerprints into one value, to cheaply check
+    /// whether anything changed since an earlier call
+    TreeHash {
+        /// Where to start hashing; the application root if omitted
+        #[serde(default)]
+        root: Option<NodeId>,
+        #[serde(default)]
+        max_depth: Option<usize>,
+    },
+    /// Perform one action on every node matching a query, e.g. check all the checkboxes
+    PerformOnMatches {
+        find: FindByPredicate,
+        action: Action,
+    },
+    /// List the application's windows, front to back
+    ListWindows,
+    /// Get the language and region the application's UI is presented in
+    GetLocale,
+    /// Read part of a text element's value, to page through one too large to
+    /// fetch whole. `offset` and `length` count UTF-16 code units, as macOS
+    /// does; a chunk ends early rather than split a character encoded as two
+    /// units, so advance `offset` by the UTF-16 length of the text returned
+    GetTextChunk {
+        node_id: NodeId,
+        offset: usize,
+        length: usize,
+    },
+    /// Summarize the tree's shape, to choose traversal limits before querying it
+    TreeStats {
+        /// Stop after this many nodes (default 1000)
+        #[serde(default)]
+        max_nodes: Option<usize>,
+    },
+    /// Find the nearest node on one side of `anchor`, by their bounds
+    FindRelative {
+        anchor: NodeId,
+        direction: Direction,
+        /// Only consider nodes with this role
+        #[serde(default)]
+        role: Option<String>,
+    },
+    /// Check whether a node id still refers to a live element, reading only its role
+    ValidateNode { node_id: NodeId },
+}
+
+impl Request {
+    /// Every request method name, as used in the `method` tag on the wire
+    pub const METHODS: &'static [&'static str] = &[
+        "initialize",
+        "tools/list",
+        "query_tree",
+        "get_node",
+        "perform_action",
+        "find_by_name",
+        "wait_for",
+        "move_focus",
+        "get_selection",
+        "find_by_action",
+        "describe_node",
+        "get_action_log",
+        "is_visible",
+        "get_window_bounds",
+        "list_targets",
+        "validate_plan",
+        "watch_node",
+        "count_nodes",
+        "render_dot",
+        "focus_window",
+        "compare_nodes",
+        "get_selected_text",
+        "get_siblings",
+        "export_tree",
+        "capture_snapshots",
+        "query_interactive",
+        "list_roles",
+        "audit_node",
+        "audit_tree",
+        "find_in_rect",
+        "get_menu_bar",
+        "get_native_actions",
+        "get_breadcrumb",
+        "hovered_node",
+        "tree_hash",
+        "perform_on_matches",
+        "list_windows",
+        "get_locale",
+        "get_text_chunk",
+        "tree_stats",
+        "find_relative",
+        "validate_node",
+    ];
+}
+
+/// One action in a plan of several
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionStep {
+    pub node_id: NodeId,
+    pub action: Action,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 /// MCP response types
+// Responses are short-lived and serialized right away, so boxing the payload buys nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum Response {
@@ -113,11 +741,25 @@ pub enum Response {
     Error { error: ErrorInfo },
 }
 
+impl Response {
+    /// A failed response with the given code and message
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Error {
+            error: ErrorInfo::new(code, message),
+        }
+    }
+}
+
+// See `Response`: the single-node payload is deliberately stored inline
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ResponseData {
     Initialize {
         protocol_version: String,
+        /// Every protocol version the server speaks, so a client can downgrade
+        #[serde(default)]
+        supported_versions: Vec<String>,
         capabilities: Capabilities,
         server_info: ServerInfo,
     },
@@ -126,16 +768,270 @@ pub enum ResponseData {
     },
     Tree {
         nodes: Vec<Node>,
+        /// The traversal stopped early, at `max_nodes` or the request's deadline
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
     },
     Node {
         node: Node,
     },
     ActionResult {
         success: bool,
+        /// The node's value after the action, when the action reports one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        new_value: Option<String>,
     },
     Nodes {
         nodes: Vec<Node>,
+        /// The search stopped early, at the node limit or the request's deadline
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// Nodes reduced to a requested set of fields
+    ProjectedNodes {
+        nodes: Vec<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
     },
+    /// A node reduced to a requested set of fields
+    ProjectedNode {
+        node: serde_json::Value,
+    },
+    /// Part of a text element's value
+    TextChunk {
+        text: String,
+        /// Length of the whole value, in UTF-16 code units
+        total_length: usize,
+        /// More of the value follows this chunk
+        has_more: bool,
+    },
+    /// Free-form text, such as a node description. Must follow `TextChunk`,
+    /// or untagged decoding would read every chunk as plain text
+    Text {
+        text: String,
+    },
+    ActionLog {
+        entries: Vec<ActionLogEntry>,
+    },
+    /// A screen-space rectangle, such as a window's frame
+    Bounds {
+        bounds: Rect,
+    },
+    Targets {
+        targets: Vec<TargetInfo>,
+    },
+    Count {
+        count: usize,
+        /// Counting stopped at the traversal's node limit
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// Per-step results of `validate_plan`, in plan order
+    PlanValidation {
+        /// Whether every step passed
+        valid: bool,
+        steps: Vec<StepValidation>,
+    },
+    /// Fields that differ between two compared nodes; empty if they're equivalent
+    NodeDiff {
+        changed_fields: Vec<String>,
+    },
+    /// Where an exported tree was written, and how many nodes it holds
+    Exported {
+        path: String,
+        node_count: usize,
+        /// Export stopped at the node limit, so the file holds part of the tree
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// Successive reads of the tree, oldest first
+    Snapshots {
+        snapshots: Vec<Vec<Node>>,
+    },
+    /// The roles a backend reports
+    RoleMap {
+        roles: Vec<RoleEntry>,
+    },
+    NameAudit {
+        audit: NameAudit,
+    },
+    NativeActions {
+        actions: Vec<NativeAction>,
+    },
+    /// Problems found by a tree audit
+    AuditReport {
+        issues: Vec<AuditIssue>,
+        /// The audit stopped early at the node limit, so more issues may exist
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// Outcome of acting on several nodes, with why each failure failed
+    BatchActionResult {
+        total: usize,
+        succeeded: usize,
+        failures: Vec<(NodeId, String)>,
+        /// The search for matches stopped at the node limit, so nodes past
+        /// it were not acted on
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// A combined digest of a subtree, equal whenever the subtree is unchanged
+    Hash {
+        hash: String,
+        node_count: usize,
+        /// Hashing stopped at the traversal's node limit
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    TreeStats {
+        node_count: usize,
+        /// Depth of the deepest node, the root being 0
+        max_depth: usize,
+        /// Most children any one node has
+        max_children: usize,
+        /// How many nodes have each role
+        role_histogram: std::collections::BTreeMap<String, usize>,
+        /// Counting stopped at `max_nodes`, so the stats cover part of the tree
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        truncated: bool,
+    },
+    /// Whether a node id still refers to a live element
+    Validity {
+        valid: bool,
+        /// Why the id is no longer valid
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// The application's locale; `null` parts couldn't be determined.
+    /// Kept last: with every field optional it would match any result
+    Locale {
+        /// ISO 639 language code, e.g. `"en"`
+        language: Option<String>,
+        /// ISO 3166 region code, e.g. `"US"`
+        region: Option<String>,
+    },
+}
+
+/// Criteria a node must all meet; omitted ones match anything
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FindByPredicate {
+    /// Exact role to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Case-insensitive substring of the node's name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_contains: Option<String>,
+}
+
+impl FindByPredicate {
+    /// Whether no criteria are given, so every node matches
+    pub fn is_empty(&self) -> bool {
+        self.role.is_none() && self.name_contains.is_none()
+    }
+
+    pub fn matches(&self, node: &Node) -> bool {
+        self.role.as_ref().is_none_or(|role| &node.role == role)
+            && self.name_contains.as_ref().is_none_or(|needle| {
+                node.name
+                    .as_ref()
+                    .is_some_and(|name| name.to_lowercase().contains(&needle.to_lowercase()))
+            })
+    }
+}
+
+/// Whether one step of a plan would be accepted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepValidation {
+    pub ok: bool,
+    /// Why the step would fail, when it would
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// An application registered with the server, as listed by `list_targets`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetInfo {
+    /// The key to pass as a message's `target`, e.g. a PID or app name
+    pub name: String,
+    /// Whether requests without a `target` go to this application
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+}
+
+/// The attributes a node's accessible name can come from; absent ones are left out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NameSources {
+    /// `AXTitle`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// `AXTitleUIElement`: the separate label element naming this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_ui_element: Option<NodeId>,
+    /// `AXDescription`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `AXValue`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// `AXHelp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+/// Whether a node has an accessible name, and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NameAudit {
+    pub sources: NameSources,
+    pub has_accessible_name: bool,
+    /// The attribute the name is taken from, e.g. `AXDescription`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_source: Option<String>,
+    /// The verdict in a sentence
+    pub explanation: String,
+}
+
+/// One problem found by a tree audit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditIssue {
+    pub node_id: NodeId,
+    /// Which check failed, e.g. `missing-name`
+    pub rule: String,
+    pub message: String,
+}
+
+/// A platform role and what nodes with it can do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoleEntry {
+    /// The role as the platform names it, e.g. `AXButton`
+    pub role: String,
+    /// The AccessKit role, as in a node's `akit_role`, when roles are normalized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized_role: Option<String>,
+    /// Types of the actions nodes with this role offer by default, e.g. `press`
+    pub actions: Vec<String>,
+}
+
+/// An action as the platform names it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NativeAction {
+    /// The platform's name, e.g. `AXPress`
+    pub name: String,
+    /// Type of the [`Action`] that performs it, e.g. `press`; `None` if only
+    /// [`Action::Custom`] with `name` can
+    pub maps_to: Option<String>,
+}
+
+/// A successfully performed action, as recorded in the action log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionLogEntry {
+    /// When the action completed, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub node_id: NodeId,
+    pub action: Action,
+    /// The node's value before the action, for actions that change it;
+    /// replaying it with `set_value` undoes the action
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_value: Option<String>,
 }
 
 /// Server capabilities
@@ -169,6 +1065,19 @@ pub struct Tool {
 pub struct ErrorInfo {
     pub code: ErrorCode,
     pub message: String,
+    /// The protocol versions the server speaks, when it rejected the client's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_versions: Option<Vec<String>>,
+}
+
+impl ErrorInfo {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            supported_versions: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -178,17 +1087,104 @@ pub enum ErrorCode {
     PermissionDenied,
     Transient,
     InvalidAction,
+    /// The message was malformed or not a request this server understands
+    InvalidRequest,
     Internal,
+    /// The application being inspected has quit; no further request can succeed
+    AppTerminated,
+}
+
+impl ErrorCode {
+    /// The stable JSON-RPC error code for this category.
+    ///
+    /// Server-defined categories use the implementation-defined range from
+    /// -32001; `InvalidRequest` and `Internal` reuse JSON-RPC's own codes.
+    pub fn as_i32(self) -> i32 {
+        match self {
+            ErrorCode::NotFound => -32001,
+            ErrorCode::PermissionDenied => -32002,
+            ErrorCode::Transient => -32003,
+            ErrorCode::InvalidAction => -32004,
+            ErrorCode::AppTerminated => -32005,
+            ErrorCode::InvalidRequest => -32600,
+            ErrorCode::Internal => -32603,
+        }
+    }
+
+    /// The category for a code produced by [`ErrorCode::as_i32`]
+    pub fn from_i32(code: i32) -> Option<Self> {
+        Some(match code {
+            -32001 => ErrorCode::NotFound,
+            -32002 => ErrorCode::PermissionDenied,
+            -32003 => ErrorCode::Transient,
+            -32004 => ErrorCode::InvalidAction,
+            -32005 => ErrorCode::AppTerminated,
+            -32600 => ErrorCode::InvalidRequest,
+            -32603 => ErrorCode::Internal,
+            _ => return None,
+        })
+    }
+}
+
+/// An error in JSON-RPC's shape: an integer `code` (see [`ErrorCode::as_i32`])
+/// instead of the category name used by [`ErrorInfo`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i32,
+    pub message: String,
+    /// Structured detail, e.g. `{"supported_versions": [...]}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl From<ErrorInfo> for JsonRpcError {
+    fn from(error: ErrorInfo) -> Self {
+        Self {
+            code: error.code.as_i32(),
+            message: error.message,
+            data: error
+                .supported_versions
+                .map(|versions| serde_json::json!({ "supported_versions": versions })),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, stable across runs and builds unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+pub(crate) fn supported_versions() -> Vec<String> {
+    Message::SUPPORTED_VERSIONS
+        .iter()
+        .map(|version| version.to_string())
+        .collect()
+}
+
+fn current_protocol_version() -> String {
+    Message::PROTOCOL_VERSION.to_string()
 }
 
 /// MCP protocol envelope
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
+    /// The current version if omitted
+    #[serde(default = "current_protocol_version")]
     pub protocol_version: String,
+    /// Which registered application a request is for; the primary one if omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
     #[serde(flatten)]
     pub content: MessageContent,
 }
 
+// See `Response`: the single-node payload is deliberately stored inline
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MessageContent {
@@ -198,17 +1194,27 @@ pub enum MessageContent {
 
 impl Message {
     pub const PROTOCOL_VERSION: &'static str = "1.0";
+    /// Every version a client may ask for, newest first
+    pub const SUPPORTED_VERSIONS: &'static [&'static str] = &[Self::PROTOCOL_VERSION];
 
     pub fn request(req: Request) -> Self {
         Self {
             protocol_version: Self::PROTOCOL_VERSION.to_string(),
+            target: None,
             content: MessageContent::Request(req),
         }
     }
 
+    /// Address this message to the application registered as `target`
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
     pub fn response(resp: Response) -> Self {
         Self {
             protocol_version: Self::PROTOCOL_VERSION.to_string(),
+            target: None,
             content: MessageContent::Response(resp),
         }
     }
@@ -218,11 +1224,273 @@ impl Message {
     }
 
     pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::response(Response::error(code, message))
+    }
+
+    /// Reject a client's protocol version, listing the ones this server speaks
+    pub fn unsupported_version(version: &str) -> Self {
         Self::response(Response::Error {
             error: ErrorInfo {
-                code,
-                message: message.into(),
+                code: ErrorCode::InvalidRequest,
+                message: format!("Unsupported protocol version: {}", version),
+                supported_versions: Some(supported_versions()),
             },
         })
     }
+
+    /// Parse a message from JSON, describing what was wrong with it on failure.
+    ///
+    /// A `method` this server doesn't know is reported as such, with the
+    /// supported methods listed, rather than as a generic parse error.
+    pub fn from_json(json: &[u8]) -> Result<Self, String> {
+        let error = match serde_json::from_slice(json) {
+            Ok(message) => return Ok(message),
+            Err(e) => e,
+        };
+
+        let value: serde_json::Value = serde_json::from_slice(json).unwrap_or_default();
+        match value.get("method").and_then(|m| m.as_str()) {
+            Some(method) if !Request::METHODS.contains(&method) => Err(format!(
+                "unknown method '{}'; supported: [{}]",
+                method,
+                Request::METHODS.join(", ")
+            )),
+            _ => Err(format!("Invalid JSON: {}", error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_action_round_trips() {
+        let actions = [
+            (Action::Focus, "f"),
+            (Action::Press, "p"),
+            (Action::Increment, "i"),
+            (Action::Decrement, "d"),
+            (Action::ContextMenu, "m"),
+            (
+                Action::SetValue {
+                    value: "a:b, c".to_string(),
+                },
+                "v:a:b, c",
+            ),
+            (Action::Scroll { x: 0.0, y: -2.5 }, "s:0,-2.5"),
+            (
+                Action::Custom {
+                    name: "AXShowAlternateUI".to_string(),
+                },
+                "c:AXShowAlternateUI",
+            ),
+            (
+                Action::SetChecked {
+                    checked: Some(true),
+                },
+                "k:1",
+            ),
+            (
+                Action::SetChecked {
+                    checked: Some(false),
+                },
+                "k:0",
+            ),
+            (Action::SetChecked { checked: None }, "k"),
+            (Action::PressAt { dx: 0.25, dy: 1.0 }, "a:0.25,1"),
+            (
+                Action::TypeText {
+                    text: "héllo, world".to_string(),
+                },
+                "t:héllo, world",
+            ),
+        ];
+
+        for (action, code) in actions {
+            assert_eq!(action.to_compact(), code);
+            assert_eq!(Action::from_compact(code).unwrap(), action);
+
+            // The type name matches the serialized tag
+            let json = serde_json::to_value(&action).unwrap();
+            assert_eq!(json["type"], action.action_type());
+            assert!(Action::TYPES.contains(&action.action_type()));
+        }
+
+        // An empty value is still a valid set_value
+        let empty = Action::SetValue {
+            value: String::new(),
+        };
+        assert_eq!(Action::from_compact(&empty.to_compact()).unwrap(), empty);
+    }
+
+    #[test]
+    fn text_chunks_keep_their_paging_fields() {
+        let chunk = ResponseData::TextChunk {
+            text: "hello".to_string(),
+            total_length: 11,
+            has_more: true,
+        };
+        let json = serde_json::to_string(&chunk).unwrap();
+        match serde_json::from_str(&json).unwrap() {
+            ResponseData::TextChunk {
+                text,
+                total_length,
+                has_more,
+            } => assert_eq!((text.as_str(), total_length, has_more), ("hello", 11, true)),
+            other => panic!("decoded as {:?}", other),
+        }
+
+        let text = ResponseData::Text {
+            text: "plain".to_string(),
+        };
+        let json = serde_json::to_string(&text).unwrap();
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            ResponseData::Text { .. }
+        ));
+    }
+
+    #[test]
+    fn point_at_scales_fractions_of_the_bounds() {
+        let bounds = Rect {
+            x: 100.0,
+            y: 50.0,
+            width: 200.0,
+            height: 40.0,
+        };
+        assert_eq!(bounds.point_at(0.0, 0.0), (100.0, 50.0));
+        assert_eq!(bounds.point_at(0.5, 0.5), (200.0, 70.0));
+        assert_eq!(bounds.point_at(0.9, 0.25), (280.0, 60.0));
+        assert_eq!(bounds.point_at(1.0, 1.0), (300.0, 90.0));
+    }
+
+    #[test]
+    fn error_codes_round_trip_through_integers() {
+        let codes = [
+            (ErrorCode::NotFound, -32001),
+            (ErrorCode::PermissionDenied, -32002),
+            (ErrorCode::Transient, -32003),
+            (ErrorCode::InvalidAction, -32004),
+            (ErrorCode::AppTerminated, -32005),
+            (ErrorCode::InvalidRequest, -32600),
+            (ErrorCode::Internal, -32603),
+        ];
+        for (code, number) in codes {
+            assert_eq!(code.as_i32(), number, "{code:?}");
+            assert_eq!(ErrorCode::from_i32(number), Some(code));
+        }
+        assert_eq!(ErrorCode::from_i32(-32700), None);
+
+        let error = JsonRpcError::from(ErrorInfo::new(ErrorCode::NotFound, "Node not found: 42"));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({"code": -32001, "message": "Node not found: 42"})
+        );
+    }
+
+    #[test]
+    fn methods_list_every_request() {
+        // serde names every variant it expected when given an unknown tag
+        let unknown = serde_json::from_str::<Request>(r#"{"method": "fly_to_moon"}"#)
+            .unwrap_err()
+            .to_string();
+        let expected: Vec<_> = Request::METHODS.iter().map(|m| format!("`{m}`")).collect();
+        assert!(
+            unknown.contains(&format!("expected one of {}", expected.join(", "))),
+            "{unknown}"
+        );
+    }
+
+    #[test]
+    fn unknown_methods_are_named_in_parse_errors() {
+        let error = Message::from_json(br#"{"protocol_version": "1.0", "method": "fly_to_moon"}"#)
+            .unwrap_err();
+        assert!(
+            error.starts_with("unknown method 'fly_to_moon'; supported: [initialize, tools/list, "),
+            "{error}"
+        );
+        assert!(error.contains("get_node"));
+
+        // Known methods with bad arguments, and malformed JSON, are still parse errors
+        for json in [
+            r#"{"protocol_version": "1.0", "method": "get_node"}"#,
+            r#"{"protocol_version": "1.0", "method": "#,
+        ] {
+            let error = Message::from_json(json.as_bytes()).unwrap_err();
+            assert!(error.starts_with("Invalid JSON: "), "{error}");
+        }
+    }
+
+    #[test]
+    fn fingerprints_track_role_name_bounds_and_child_count() {
+        let base = Node {
+            id: NodeId::from("save"),
+            role: "AXButton".to_string(),
+            name: Some("Save".to_string()),
+            bounds: Some(Rect {
+                x: 10.0,
+                y: 20.0,
+                width: 80.0,
+                height: 24.0,
+            }),
+            children: vec![NodeId::from("icon")],
+            ..Default::default()
+        };
+        let fingerprint = base.content_fingerprint();
+        assert_eq!(fingerprint.len(), 16);
+
+        // Identity and volatile state don't contribute
+        let same = Node {
+            id: NodeId::from("other"),
+            value: Some("1".to_string()),
+            epoch: 7,
+            ..base.clone()
+        };
+        assert_eq!(same.content_fingerprint(), fingerprint);
+
+        let changed = [
+            Node {
+                role: "AXLink".to_string(),
+                ..base.clone()
+            },
+            Node {
+                name: Some("Save As".to_string()),
+                ..base.clone()
+            },
+            Node {
+                name: None,
+                ..base.clone()
+            },
+            Node {
+                bounds: Some(Rect {
+                    x: 11.0,
+                    ..base.bounds.unwrap()
+                }),
+                ..base.clone()
+            },
+            Node {
+                children: Vec::new(),
+                ..base.clone()
+            },
+        ];
+        for node in changed {
+            assert_ne!(node.content_fingerprint(), fingerprint, "{node:?}");
+        }
+
+        // Leaving children out in favour of a count keeps the fingerprint
+        let counted = Node {
+            children: Vec::new(),
+            child_count: Some(1),
+            ..base.clone()
+        };
+        assert_eq!(counted.content_fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn compact_action_rejects_malformed_codes() {
+        for code in ["", "x", "p:1", "v", "s:1", "s:a,b", "k:yes", "a:0.5"] {
+            assert!(Action::from_compact(code).is_err(), "{code:?} should fail");
+        }
+    }
 }
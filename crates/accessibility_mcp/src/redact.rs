@@ -0,0 +1,321 @@
+//! Redaction of sensitive values, and optional sanitizing of text, before
+//! nodes leave the provider
+
+use crate::config::Config;
+use crate::platform::{AccessibilityProvider, NodeFields};
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
+use anyhow::Result;
+use std::time::Duration;
+
+/// What a secure text field's value is replaced with
+pub(crate) const SECURE_VALUE_MASK: &str = "••••";
+
+/// `text` without control characters, except tab, newline, and carriage return
+pub(crate) fn sanitize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+/// Wraps a provider, redacting values on every node it returns.
+///
+/// Redacting at the provider means cached nodes, projections, and values
+/// recorded in the action log never see the originals.
+pub(crate) struct RedactingProvider {
+    inner: Box<dyn AccessibilityProvider>,
+    secure_fields: bool,
+    roles: Vec<String>,
+    sanitize_strings: bool,
+}
+
+/// Whether `node` is a password-style field, whose value is masked when
+/// `Config::redact_secure_fields` is on
+pub(crate) fn is_secure_field(node: &Node) -> bool {
+    node.subrole.as_deref() == Some("AXSecureTextField")
+}
+
+impl RedactingProvider {
+    /// Wrap `provider` if `config` asks for any redaction, otherwise return it as is
+    pub fn wrap(
+        provider: Box<dyn AccessibilityProvider>,
+        config: &Config,
+    ) -> Box<dyn AccessibilityProvider> {
+        if !config.redact_secure_fields
+            && config.redact_roles.is_empty()
+            && !config.sanitize_strings
+        {
+            return provider;
+        }
+
+        Box::new(Self {
+            inner: provider,
+            secure_fields: config.redact_secure_fields,
+            roles: config.redact_roles.clone(),
+            sanitize_strings: config.sanitize_strings,
+        })
+    }
+
+    /// Whether `node`'s value is withheld, masked or left out
+    fn redacts(&self, node: &Node) -> bool {
+        (self.secure_fields && is_secure_field(node)) || self.roles.contains(&node.role)
+    }
+
+    fn redact(&self, mut node: Node) -> Node {
+        if self.secure_fields && is_secure_field(&node) {
+            node.value = node.value.map(|_| SECURE_VALUE_MASK.to_string());
+            node.value_description = None;
+            // The length would give away the length of the secret
+            node.char_count = None;
+            node.line_count = None;
+        } else if self.roles.contains(&node.role) {
+            node.value = None;
+            node.value_description = None;
+            node.char_count = None;
+            node.line_count = None;
+        }
+        if self.sanitize_strings {
+            node.name = node.name.as_deref().map(sanitize);
+            node.value = node.value.as_deref().map(sanitize);
+            node.value_description = node.value_description.as_deref().map(sanitize);
+            node.description = node.description.as_deref().map(sanitize);
+        }
+        node
+    }
+}
+
+impl AccessibilityProvider for RedactingProvider {
+    fn get_root(&self) -> Result<Node> {
+        self.inner.get_root().map(|node| self.redact(node))
+    }
+
+    fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        let children = self.inner.get_children(node_id)?;
+        Ok(children.into_iter().map(|node| self.redact(node)).collect())
+    }
+
+    fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        self.inner.get_node(node_id).map(|node| self.redact(node))
+    }
+
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        self.inner
+            .get_node_fields(node_id, fields)
+            .map(|node| self.redact(node))
+    }
+
+    fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        self.inner.perform_action(node_id, action)
+    }
+
+    fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        self.inner.refresh(node_id).map(|node| self.redact(node))
+    }
+
+    fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let parent = self.inner.get_parent(node_id)?;
+        Ok(parent.map(|node| self.redact(node)))
+    }
+
+    fn get_focused(&self) -> Result<Option<Node>> {
+        let focused = self.inner.get_focused()?;
+        Ok(focused.map(|node| self.redact(node)))
+    }
+
+    fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let window = self.inner.get_window(node_id)?;
+        Ok(window.map(|node| self.redact(node)))
+    }
+
+    fn get_focused_window(&self) -> Result<Option<Node>> {
+        let window = self.inner.get_focused_window()?;
+        Ok(window.map(|node| self.redact(node)))
+    }
+
+    fn get_menu_bar(&self) -> Result<Option<Node>> {
+        let menu_bar = self.inner.get_menu_bar()?;
+        Ok(menu_bar.map(|node| self.redact(node)))
+    }
+
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        self.inner.cursor_position()
+    }
+
+    fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        let node = self.inner.node_at_point(x, y)?;
+        Ok(node.map(|node| self.redact(node)))
+    }
+
+    fn is_frontmost_app(&self) -> Result<bool> {
+        self.inner.is_frontmost_app()
+    }
+
+    fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        self.inner.locale()
+    }
+
+    fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        self.inner.set_keyboard_focus(node_id)
+    }
+
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        self.inner.is_alive(node_id)
+    }
+
+    fn text_range(
+        &self,
+        node_id: &NodeId,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        // Without a range, callers fall back to the redacted value. Offsets
+        // into raw text would not line up with sanitized text, so that too
+        let node = self.inner.get_node_fields(node_id, NodeFields::NONE)?;
+        if self.redacts(&node) || self.sanitize_strings {
+            return Ok(None);
+        }
+        self.inner.text_range(node_id, offset, length)
+    }
+
+    fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        let Some(text) = self.inner.selected_text(node_id)? else {
+            return Ok(None);
+        };
+        // Redacted like the value the selection is part of
+        let node = self.inner.get_node_fields(node_id, NodeFields::NONE)?;
+        let redacted = self.redact(Node {
+            value: Some(text),
+            ..node
+        });
+        Ok(redacted.value)
+    }
+
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        self.inner.focus_window(window_id)
+    }
+
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        let sources = self.inner.name_sources(node_id)?;
+        // Redacted like the node's own value
+        let node = self.inner.get_node_fields(node_id, NodeFields::NONE)?;
+        let redacted = self.redact(Node {
+            value: sources.value.clone(),
+            ..node
+        });
+        let sanitize = |text: Option<String>| {
+            if self.sanitize_strings {
+                text.as_deref().map(sanitize)
+            } else {
+                text
+            }
+        };
+        Ok(NameSources {
+            title: sanitize(sources.title),
+            description: sanitize(sources.description),
+            value: redacted.value,
+            help: sanitize(sources.help),
+            ..sources
+        })
+    }
+
+    fn roles(&self) -> Vec<RoleEntry> {
+        self.inner.roles()
+    }
+
+    fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        self.inner.native_action_names(node_id)
+    }
+
+    fn wait_for_value_change(&self, node_id: &NodeId, timeout: Duration) -> Result<Option<bool>> {
+        self.inner.wait_for_value_change(node_id, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, MockProvider};
+
+    fn field(id: &str, subrole: Option<&str>, value: &str) -> Node {
+        Node {
+            subrole: subrole.map(str::to_string),
+            value: Some(value.to_string()),
+            ..node(id, "AXTextField")
+        }
+    }
+
+    #[test]
+    fn secure_fields_are_masked_and_others_left_intact() {
+        let mock = MockProvider::new(vec![
+            field("password", Some("AXSecureTextField"), "hunter2"),
+            field("username", None, "alice"),
+            Node {
+                value: Some("4111 1111 1111 1111".to_string()),
+                ..node("card", "AXStaticText")
+            },
+        ]);
+        let config = Config {
+            redact_roles: vec!["AXStaticText".to_string()],
+            ..Config::default()
+        };
+        mock.set_selected_text("password", "hunt");
+        let provider = RedactingProvider::wrap(Box::new(mock), &config);
+        let value = |id: &str| provider.get_node(&NodeId::from(id)).unwrap().value;
+
+        assert_eq!(value("password").as_deref(), Some(SECURE_VALUE_MASK));
+        assert_eq!(value("username").as_deref(), Some("alice"));
+        assert_eq!(value("card"), None);
+
+        // A selection is part of the value, and masked with it
+        let selected = provider.selected_text(&NodeId::from("password")).unwrap();
+        assert_eq!(selected.as_deref(), Some(SECURE_VALUE_MASK));
+
+        // The node itself is still reported
+        let password = provider.get_node(&NodeId::from("password")).unwrap();
+        assert_eq!(password.role, "AXTextField");
+        assert_eq!(password.char_count, None);
+    }
+
+    #[test]
+    fn redaction_can_be_turned_off() {
+        let mock = MockProvider::new(vec![field(
+            "password",
+            Some("AXSecureTextField"),
+            "hunter2",
+        )]);
+        let config = Config {
+            redact_secure_fields: false,
+            ..Config::default()
+        };
+        let provider = RedactingProvider::wrap(Box::new(mock), &config);
+        let password = provider.get_node(&NodeId::from("password")).unwrap();
+        assert_eq!(password.value.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn control_characters_are_stripped_when_sanitizing() {
+        let mock = MockProvider::new(vec![Node {
+            name: Some("Total\u{7}".to_string()),
+            value: Some("line one\nline\u{0}two\u{1b}[0m\tend".to_string()),
+            ..node("notes", "AXTextArea")
+        }]);
+        let read = |config: &Config| {
+            let provider = RedactingProvider::wrap(Box::new(mock.clone()), config);
+            let node = provider.get_node(&NodeId::from("notes")).unwrap();
+            (node.name.unwrap(), node.value.unwrap())
+        };
+
+        let config = Config {
+            sanitize_strings: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            read(&config),
+            ("Total".to_string(), "line one\nlinetwo[0m\tend".to_string())
+        );
+
+        // Raw by default
+        let (name, value) = read(&Config::default());
+        assert_eq!(name, "Total\u{7}");
+        assert!(value.contains('\u{0}'));
+    }
+}
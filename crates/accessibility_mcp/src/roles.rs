@@ -0,0 +1,90 @@
+//! Mapping of platform roles onto AccessKit's cross-platform role enum
+
+use accesskit::Role;
+
+/// The AccessKit role for a raw platform role such as `AXButton`.
+///
+/// Roles with no AccessKit counterpart map to [`Role::Unknown`].
+pub(crate) fn accesskit_role(role: &str) -> Role {
+    match role {
+        "AXApplication" => Role::Application,
+        "AXWindow" => Role::Window,
+        "AXSheet" => Role::Dialog,
+        "AXButton" | "AXMenuButton" => Role::Button,
+        "AXCheckBox" => Role::CheckBox,
+        "AXRadioButton" => Role::RadioButton,
+        "AXRadioGroup" => Role::RadioGroup,
+        "AXTextField" => Role::TextInput,
+        "AXTextArea" => Role::MultilineTextInput,
+        "AXStaticText" => Role::Label,
+        "AXHeading" => Role::Heading,
+        "AXImage" => Role::Image,
+        "AXLink" => Role::Link,
+        "AXGroup" => Role::Group,
+        "AXSlider" => Role::Slider,
+        "AXIncrementor" => Role::SpinButton,
+        "AXPopUpButton" => Role::ComboBox,
+        "AXComboBox" => Role::EditableComboBox,
+        "AXMenuBar" => Role::MenuBar,
+        "AXMenu" => Role::Menu,
+        "AXMenuItem" | "AXMenuBarItem" => Role::MenuItem,
+        "AXList" => Role::List,
+        "AXTable" => Role::Table,
+        "AXOutline" => Role::Tree,
+        "AXGrid" => Role::Grid,
+        "AXRow" => Role::Row,
+        "AXCell" => Role::Cell,
+        "AXScrollArea" => Role::ScrollView,
+        "AXScrollBar" => Role::ScrollBar,
+        "AXSplitter" => Role::Splitter,
+        "AXTabGroup" => Role::TabList,
+        "AXToolbar" => Role::Toolbar,
+        "AXProgressIndicator" | "AXBusyIndicator" => Role::ProgressIndicator,
+        "AXLevelIndicator" => Role::Meter,
+        "AXDisclosureTriangle" => Role::DisclosureTriangle,
+        "AXColorWell" => Role::ColorWell,
+        "AXDateField" => Role::DateInput,
+        "AXTimeField" => Role::TimeInput,
+        "AXHelpTag" => Role::Tooltip,
+        "AXWebArea" => Role::WebView,
+        _ => Role::Unknown,
+    }
+}
+
+/// The name of the AccessKit role variant, e.g. `"CheckBox"`, as reported in `akit_role`
+pub(crate) fn accesskit_role_name(role: &str) -> String {
+    format!("{:?}", accesskit_role(role))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_common_roles() {
+        let cases = [
+            ("AXApplication", Role::Application),
+            ("AXWindow", Role::Window),
+            ("AXButton", Role::Button),
+            ("AXCheckBox", Role::CheckBox),
+            ("AXRadioButton", Role::RadioButton),
+            ("AXTextField", Role::TextInput),
+            ("AXTextArea", Role::MultilineTextInput),
+            ("AXStaticText", Role::Label),
+            ("AXGroup", Role::Group),
+            ("AXSlider", Role::Slider),
+            ("AXPopUpButton", Role::ComboBox),
+            ("AXMenuItem", Role::MenuItem),
+            ("AXList", Role::List),
+            ("AXScrollArea", Role::ScrollView),
+            ("AXTabGroup", Role::TabList),
+            ("AXWebArea", Role::WebView),
+        ];
+        for (ax, expected) in cases {
+            assert_eq!(accesskit_role(ax), expected, "{ax}");
+        }
+
+        assert_eq!(accesskit_role("AXSomethingNew"), Role::Unknown);
+        assert_eq!(accesskit_role_name("AXCheckBox"), "CheckBox");
+    }
+}
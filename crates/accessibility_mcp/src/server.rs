@@ -1,32 +1,68 @@
 //! MCP server implementation
 
-use crate::platform::{create_provider, AccessibilityProvider};
-use crate::protocol::{ErrorCode, Message, MessageContent, Request, Response, ResponseData};
+use crate::action_log::ActionLog;
+use crate::cache::NodeCache;
+use crate::config::Config;
+use crate::idempotency::{IdempotencyCache, IdempotencyKey, IDEMPOTENCY_TTL};
+use crate::platform::{
+    create_provider_with_config, is_app_terminated, AccessibilityProvider,
+    AsyncAccessibilityProvider, NodeFields, SyncAdapter,
+};
+use crate::protocol::{
+    Direction, ErrorCode, FocusDirection, MatchMode, Message, MessageContent, Node, Request,
+    Response, ResponseData, SortOrder, TraversalOrder,
+};
+use crate::redact::RedactingProvider;
+use crate::synthesize::SynthesizingProvider;
+use crate::traversal::{Walk, DEFAULT_MAX_NODES};
+use crate::visibility::VisibilityTracker;
 use anyhow::{Context, Result};
 use axum::{
     extract::State,
     http::StatusCode,
-    response::{IntoResponse, Response as AxumResponse},
-    routing::post,
+    response::{Html, IntoResponse, Response as AxumResponse},
+    routing::{get, post},
     Json, Router,
 };
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch, Semaphore};
 use tower_http::cors::CorsLayer;
 
 /// Handle for controlling the MCP server
 pub struct McpHandle {
-    shutdown_tx: Option<oneshot::Sender<()>>,
+    shutdown_tx: Option<Arc<watch::Sender<bool>>>,
+    /// Resolve as each server task finishes
+    stopped: Vec<oneshot::Receiver<()>>,
     /// The port the HTTP server is listening on
     pub port: u16,
+    /// The Unix socket path, when the socket transport is enabled
+    pub socket_path: Option<PathBuf>,
 }
 
 impl McpHandle {
     /// Shutdown the server gracefully
     pub fn shutdown(mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(());
+            let _ = tx.send(true);
+        }
+    }
+
+    /// Shut the server down and wait until it has stopped: every transport
+    /// has stopped accepting, open HTTP requests have been answered, and the
+    /// Unix socket file (if any) has been removed.
+    pub async fn shutdown_and_wait(mut self) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(true);
+        }
+        for stopped in std::mem::take(&mut self.stopped) {
+            // An error means the task ended without reporting, which is also stopped
+            let _ = stopped.await;
         }
     }
 }
@@ -34,7 +70,7 @@ impl McpHandle {
 impl Drop for McpHandle {
     fn drop(&mut self) {
         if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(());
+            let _ = tx.send(true);
         }
     }
 }
@@ -66,51 +102,331 @@ pub fn start_all() -> Result<(Runtime, McpHandle)> {
 /// # Arguments
 ///
 /// * `port` - The port to bind to. If 0, the OS will assign an arbitrary available port.
-///            If the specified port is unavailable, will try successive ports up to port+100.
+///   If the specified port is unavailable, will try successive ports up to port+100.
 pub fn start_mcp_server(port: u16) -> Result<McpHandle> {
-    tracing::info!("Starting accessibility MCP server");
+    start_mcp_server_with_config(Config {
+        port,
+        ..Default::default()
+    })
+}
+
+/// Start the MCP server with the given configuration
+pub fn start_mcp_server_with_config(config: Config) -> Result<McpHandle> {
+    prompt_for_permission(&config);
 
     // Create the accessibility provider
-    let provider = create_provider().context("Failed to create accessibility provider")?;
+    let provider =
+        create_provider_with_config(&config).context("Failed to create accessibility provider")?;
+
+    start_mcp_server_with_provider(provider, config)
+}
+
+/// Ask for accessibility access when `Config::prompt_for_permission` is set
+fn prompt_for_permission(config: &Config) {
+    if config.prompt_for_permission && !crate::platform::request_accessibility_permission(true) {
+        tracing::warn!(
+            "Accessibility access has not been granted yet; requests will fail until it is"
+        );
+    }
+}
+
+/// Start the MCP server backed by a caller-supplied provider
+///
+/// Behaves like [`start_mcp_server_with_config`], but serves `provider` instead
+/// of the platform backend. Useful for tests and for custom backends.
+pub fn start_mcp_server_with_provider(
+    provider: Box<dyn AccessibilityProvider>,
+    config: Config,
+) -> Result<McpHandle> {
+    start_mcp_server_with_providers(vec![(default_target_name(), provider)], config)
+}
+
+/// Start the MCP server backed by an async provider
+///
+/// Behaves like [`start_mcp_server_with_provider`]. The provider is driven
+/// by a runtime of its own; see [`SyncAdapter`].
+pub fn start_mcp_server_with_async_provider(
+    provider: Box<dyn AsyncAccessibilityProvider>,
+    config: Config,
+) -> Result<McpHandle> {
+    start_mcp_server_with_provider(Box::new(SyncAdapter::new(provider)), config)
+}
+
+/// Start one MCP server in front of several applications' providers
+///
+/// Each provider is registered under a name (such as the app's PID) that
+/// requests select with their `target`. The first provider is the primary
+/// one, used for requests without a target.
+pub fn start_mcp_server_with_providers(
+    providers: Vec<(String, Box<dyn AccessibilityProvider>)>,
+    config: Config,
+) -> Result<McpHandle> {
+    tracing::info!("Starting accessibility MCP server");
+
+    if providers.is_empty() {
+        anyhow::bail!("At least one provider is required");
+    }
+    let mut state = AppState::with_targets(providers, &config);
+    if config.init_timeout_ms > 0 {
+        state.wait_for_window(Duration::from_millis(config.init_timeout_ms));
+    }
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shutdown_tx = Arc::new(shutdown_tx);
+    if config.shutdown_on_app_exit {
+        state.shutdown_on_app_exit = Some(shutdown_tx.clone());
+    }
+
+    let mut stopped = Vec::new();
+    let socket_path = match &config.unix_socket {
+        Some(path) => {
+            let (path, socket_stopped) = start_unix_socket(
+                &state,
+                path,
+                config.abstract_socket,
+                config.quiet,
+                shutdown_rx.clone(),
+            )?;
+            stopped.push(socket_stopped);
+            Some(path)
+        }
+        None => None,
+    };
+    if let Some(paths) = &config.fifo {
+        stopped.push(start_fifo(
+            &state,
+            paths,
+            config.quiet,
+            shutdown_rx.clone(),
+        )?);
+    }
 
-    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let bind_addr = config
+        .bind_addr
+        .unwrap_or(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+    if !bind_addr.is_loopback() {
+        tracing::warn!(
+            "HTTP server is binding to {}: anyone who can reach it on the network can \
+             inspect and control this application",
+            bind_addr
+        );
+    }
 
     // Determine the actual port to use
-    let actual_port = if port == 0 {
+    let actual_port = if config.port == 0 {
         // Let the OS assign an arbitrary port
         0
     } else {
         // Try to find an available port starting from the requested port
-        find_available_port(port, port + 100)
+        find_available_port(bind_addr, config.port, config.port + 100)
     };
 
     // Spawn the HTTP server
     let (port_tx, port_rx) = oneshot::channel();
-    tokio::spawn(run_http_server(
-        Arc::new(provider),
+    stopped.push(spawn_server(run_http_server(
+        state,
         shutdown_rx,
-        actual_port,
+        SocketAddr::new(bind_addr, actual_port),
         port_tx,
-    ));
+    )));
 
     // Wait for the server to bind and get the actual port
     let bound_port = port_rx
         .blocking_recv()
         .context("Failed to get bound port")?;
 
-    tracing::info!("HTTP server listening on http://127.0.0.1:{}", bound_port);
-    eprintln!("[MCP] listening on http://127.0.0.1:{}", bound_port);
+    let bound_addr = SocketAddr::new(bind_addr, bound_port);
+    tracing::info!("HTTP server listening on http://{}", bound_addr);
+    announce(config.quiet, &format!("http://{}", bound_addr));
 
     Ok(McpHandle {
         shutdown_tx: Some(shutdown_tx),
+        stopped,
         port: bound_port,
+        socket_path,
     })
 }
 
-/// Find an available port in the given range
-fn find_available_port(start: u16, end: u16) -> u16 {
+/// Serve requests on stdin, writing responses to stdout, until stdin closes
+/// (or the app quits, under `Config::shutdown_on_app_exit`)
+///
+/// Messages are framed according to `config.stdio_framing`. The options of
+/// `config` that don't concern the network apply as they do to the other
+/// transports, and no HTTP server is started. Nothing but responses is ever
+/// written to stdout, and no banner is printed.
+pub async fn run_stdio_server(
+    provider: Box<dyn AccessibilityProvider>,
+    config: Config,
+) -> Result<()> {
+    prompt_for_permission(&config);
+    let mut state = AppState::new(provider, &config);
+    if config.init_timeout_ms > 0 {
+        state.wait_for_window(Duration::from_millis(config.init_timeout_ms));
+    }
+    if config.shutdown_on_app_exit {
+        state.shutdown_on_app_exit = Some(Arc::new(watch::channel(false).0));
+    }
+    let reader = tokio::io::BufReader::new(tokio::io::stdin());
+    crate::transport::serve_stdio(&state, reader, tokio::io::stdout(), config.stdio_framing)
+        .await
+        .context("stdio transport failed")
+}
+
+/// Spawn a server task, returning a receiver that resolves once it has finished
+fn spawn_server(task: impl Future<Output = ()> + Send + 'static) -> oneshot::Receiver<()> {
+    let (done_tx, done_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        task.await;
+        let _ = done_tx.send(());
+    });
+    done_rx
+}
+
+/// Bind the Unix socket and spawn its accept loop, which resolves the
+/// returned receiver once it has stopped and cleaned up
+#[cfg(unix)]
+fn start_unix_socket(
+    state: &AppState,
+    path: &Path,
+    abstract_namespace: bool,
+    quiet: bool,
+    shutdown_rx: watch::Receiver<bool>,
+) -> Result<(PathBuf, oneshot::Receiver<()>)> {
+    #[cfg(target_os = "linux")]
+    if abstract_namespace {
+        let listener = bind_abstract(path)
+            .with_context(|| format!("Failed to bind abstract socket {}", path.display()))?;
+
+        // Nothing on the filesystem to clean up afterwards
+        let stopped = spawn_server(crate::transport::run_unix_socket_server(
+            state.clone(),
+            listener,
+            None,
+            shutdown_rx,
+        ));
+
+        tracing::info!(
+            "Unix socket server listening on abstract socket {}",
+            path.display()
+        );
+        announce(quiet, &format!("unix:@{}", path.display()));
+
+        return Ok((path.to_path_buf(), stopped));
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    if abstract_namespace {
+        tracing::warn!(
+            "Abstract sockets are Linux-only; binding {} on the filesystem",
+            path.display()
+        );
+    }
+
+    // A previous process with the same pid may have left its socket behind
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind Unix socket {}", path.display()))?;
+
+    let stopped = spawn_server(crate::transport::run_unix_socket_server(
+        state.clone(),
+        listener,
+        Some(path.to_path_buf()),
+        shutdown_rx,
+    ));
+
+    tracing::info!("Unix socket server listening on {}", path.display());
+    announce(quiet, &format!("unix:{}", path.display()));
+
+    Ok((path.to_path_buf(), stopped))
+}
+
+/// Bind a listener named `name` in the abstract socket namespace
+#[cfg(target_os = "linux")]
+fn bind_abstract(name: &Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_os_str().as_bytes())?;
+    let listener = std::os::unix::net::UnixListener::bind_addr(&addr)?;
+    listener.set_nonblocking(true)?;
+    tokio::net::UnixListener::from_std(listener)
+}
+
+#[cfg(not(unix))]
+fn start_unix_socket(
+    _state: &AppState,
+    _path: &Path,
+    _abstract_namespace: bool,
+    _quiet: bool,
+    _shutdown_rx: watch::Receiver<bool>,
+) -> Result<(PathBuf, oneshot::Receiver<()>)> {
+    anyhow::bail!("Unix sockets are not supported on this platform")
+}
+
+/// Open the FIFO pair and spawn a server reading requests from one and
+/// writing responses to the other, which resolves the returned receiver once
+/// it has stopped
+#[cfg(unix)]
+fn start_fifo(
+    state: &AppState,
+    paths: &crate::config::FifoPaths,
+    quiet: bool,
+    shutdown_rx: watch::Receiver<bool>,
+) -> Result<oneshot::Receiver<()>> {
+    let (requests, responses) = crate::transport::open_fifo_pair(paths).with_context(|| {
+        format!(
+            "Failed to open FIFOs {} and {}",
+            paths.requests.display(),
+            paths.responses.display()
+        )
+    })?;
+
+    let stopped = spawn_server(crate::transport::run_fifo_server(
+        state.clone(),
+        requests,
+        responses,
+        shutdown_rx,
+    ));
+
+    tracing::info!(
+        "FIFO server reading {} and writing {}",
+        paths.requests.display(),
+        paths.responses.display()
+    );
+    announce(quiet, &format!("fifo:{}", paths.requests.display()));
+
+    Ok(stopped)
+}
+
+#[cfg(not(unix))]
+fn start_fifo(
+    _state: &AppState,
+    _paths: &crate::config::FifoPaths,
+    _quiet: bool,
+    _shutdown_rx: watch::Receiver<bool>,
+) -> Result<oneshot::Receiver<()>> {
+    anyhow::bail!("FIFOs are not supported on this platform")
+}
+
+/// Print where the server is listening to stderr, unless `quiet`
+fn announce(quiet: bool, address: &str) {
+    announce_to(&mut std::io::stderr(), quiet, address);
+}
+
+fn announce_to(out: &mut impl std::io::Write, quiet: bool, address: &str) {
+    if !quiet {
+        // Nothing useful to do if stderr is gone
+        let _ = writeln!(out, "[MCP] listening on {}", address);
+    }
+}
+
+/// Find a port in the given range that is available on `addr`
+fn find_available_port(addr: IpAddr, start: u16, end: u16) -> u16 {
     for port in start..=end {
-        if std::net::TcpListener::bind(("127.0.0.1", port)).is_ok() {
+        if std::net::TcpListener::bind((addr, port)).is_ok() {
             return port;
         }
     }
@@ -118,38 +434,228 @@ fn find_available_port(start: u16, end: u16) -> u16 {
     start
 }
 
+/// Handle a request on Tokio's blocking pool, as every transport does.
+///
+/// Handlers call the provider synchronously, and each call may be a
+/// cross-process round trip, so they must not run on the runtime's workers.
+/// A handler that panics fails only its request, with an `internal` error.
+pub(crate) async fn handle_request_blocking(state: &AppState, message: Message) -> Message {
+    let state = state.clone();
+    let runtime = tokio::runtime::Handle::current();
+    let handler =
+        tokio::task::spawn_blocking(move || runtime.block_on(handle_request(&state, message)));
+    match handler.await {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Request handler failed: {}", e);
+            Message::error(ErrorCode::Internal, "request handler failed")
+        }
+    }
+}
+
 /// Handle a single MCP request
-async fn handle_request(
-    provider: &Arc<Box<dyn AccessibilityProvider>>,
-    message: Message,
-) -> Message {
+pub(crate) async fn handle_request(state: &AppState, message: Message) -> Message {
     // Check protocol version
     if message.protocol_version != Message::PROTOCOL_VERSION {
-        return Message::error(
-            ErrorCode::Internal,
-            format!("Unsupported protocol version: {}", message.protocol_version),
-        );
+        return Message::unsupported_version(&message.protocol_version);
     }
 
+    // Requests for another registered application swap in its provider
+    let target_name = match &message.target {
+        Some(target) => target.clone(),
+        None => state.targets[0].0.clone(),
+    };
+    let targeted = match &message.target {
+        Some(target) => match state.for_target(target) {
+            Some(targeted) => Some(targeted),
+            None => {
+                return Message::error(ErrorCode::NotFound, format!("Unknown target: {}", target));
+            }
+        },
+        None => None,
+    };
+    let state = targeted.as_ref().unwrap_or(state);
+
     // Extract request
     let request = match message.content {
         MessageContent::Request(req) => req,
         MessageContent::Response(_) => {
-            return Message::error(ErrorCode::Internal, "Expected request, got response");
+            return Message::error(ErrorCode::InvalidRequest, "Expected request, got response");
         }
     };
 
+    // Requests that never touch the application needn't wait for its tree
+    let reads_app = !matches!(
+        request,
+        Request::Initialize { .. } | Request::ToolsList | Request::ListTargets
+    );
+    if let Some(ready) = state.ready.as_ref().filter(|_| reads_app) {
+        // Fails only if the poll is gone, in which case there's nothing to wait for
+        let _ = ready.clone().wait_for(|ready| *ready).await;
+    }
+
     // Handle the request
     let response = match request {
         Request::QueryTree {
             max_depth,
             max_nodes,
-        } => handle_query_tree(provider, max_depth, max_nodes).await,
-        Request::GetNode { node_id } => handle_get_node(provider, &node_id).await,
-        Request::PerformAction { node_id, action } => {
-            handle_perform_action(provider, &node_id, &action).await
+            fields,
+            order,
+            window_id,
+            deadline_ms,
+        } => project_response(
+            state,
+            handle_query_tree(
+                state,
+                max_depth,
+                max_nodes,
+                order,
+                window_id.as_ref(),
+                deadline_after(state, deadline_ms),
+                read_fields(fields.as_deref()),
+            )
+            .await,
+            fields.as_deref(),
+        ),
+        Request::GetNode {
+            node_id,
+            fields,
+            include_children,
+            describe_actions,
+        } => {
+            let response = handle_get_node(
+                state,
+                &node_id,
+                include_children,
+                read_fields(fields.as_deref()),
+            )
+            .await;
+            if describe_actions {
+                project_with_action_descriptions(state, response, fields.as_deref())
+            } else {
+                project_response(state, response, fields.as_deref())
+            }
+        }
+        Request::PerformAction {
+            node_id,
+            action,
+            idempotency_key: Some(key),
+        } => {
+            let key = IdempotencyKey::new(&target_name, &node_id, &action, &key);
+            state
+                .idempotency
+                .run_once(key, handle_perform_action(state, &node_id, &action))
+                .await
+        }
+        Request::PerformAction {
+            node_id,
+            action,
+            idempotency_key: None,
+        } => handle_perform_action(state, &node_id, &action).await,
+        Request::FindByName {
+            name,
+            match_mode,
+            order,
+            window_id,
+            sort,
+            deadline_ms,
+            include_children,
+        } => {
+            let response = handle_find_by_name(
+                state,
+                &name,
+                match_mode,
+                order,
+                window_id.as_ref(),
+                sort,
+                deadline_after(state, deadline_ms),
+            )
+            .await;
+            omit_children_unless(include_children, response)
+        }
+        Request::WaitFor {
+            role,
+            name_contains,
+            timeout_ms,
+            deadline_ms,
+        } => {
+            handle_wait_for(
+                state,
+                role.as_deref(),
+                name_contains.as_deref(),
+                timeout_ms,
+                deadline_after(state, deadline_ms),
+            )
+            .await
+        }
+        Request::MoveFocus { direction } => handle_move_focus(state, direction).await,
+        Request::GetSelection { node_id } => handle_get_selection(state, &node_id).await,
+        Request::FindByAction {
+            action_type,
+            include_children,
+        } => omit_children_unless(
+            include_children,
+            handle_find_by_action(state, &action_type).await,
+        ),
+        Request::DescribeNode { node_id } => handle_describe_node(state, &node_id).await,
+        Request::GetActionLog { limit } => handle_get_action_log(state, limit).await,
+        Request::IsVisible { node_id } => handle_is_visible(state, &node_id).await,
+        Request::GetWindowBounds { window_id } => {
+            handle_get_window_bounds(state, window_id.as_ref()).await
         }
-        Request::FindByName { name } => handle_find_by_name(provider, &name).await,
+        Request::ListTargets => handle_list_targets(state).await,
+        Request::ValidatePlan { steps } => handle_validate_plan(state, &steps).await,
+        Request::WatchNode {
+            node_id,
+            timeout_ms,
+        } => handle_watch_node(state, &node_id, timeout_ms).await,
+        Request::CountNodes { root, max_depth } => {
+            handle_count_nodes(state, root.as_ref(), max_depth).await
+        }
+        Request::RenderDot { max_depth } => handle_render_dot(state, max_depth).await,
+        Request::FocusWindow { window_id } => handle_focus_window(state, &window_id).await,
+        Request::CompareNodes { a, b } => handle_compare_nodes(state, &a, &b).await,
+        Request::GetSelectedText { node_id } => handle_get_selected_text(state, &node_id).await,
+        Request::GetSiblings { node_id } => handle_get_siblings(state, &node_id).await,
+        Request::ExportTree { path, max_depth } => {
+            handle_export_tree(state, &path, max_depth).await
+        }
+        Request::CaptureSnapshots { count, interval_ms } => {
+            handle_capture_snapshots(state, count, interval_ms).await
+        }
+        Request::QueryInteractive { max_nodes } => handle_query_interactive(state, max_nodes).await,
+        Request::AuditNode { node_id } => handle_audit_node(state, &node_id).await,
+        Request::AuditTree { max_nodes } => handle_audit_tree(state, max_nodes).await,
+        Request::FindInRect { rect } => handle_find_in_rect(state, &rect).await,
+        Request::GetMenuBar { max_depth } => handle_get_menu_bar(state, max_depth).await,
+        Request::GetNativeActions { node_id } => handle_get_native_actions(state, &node_id).await,
+        Request::GetBreadcrumb { node_id } => handle_get_breadcrumb(state, &node_id).await,
+        Request::HoveredNode => handle_hovered_node(state).await,
+        Request::TreeHash { root, max_depth } => {
+            handle_tree_hash(state, root.as_ref(), max_depth).await
+        }
+        Request::PerformOnMatches { find, action } => {
+            handle_perform_on_matches(state, &find, &action).await
+        }
+        Request::ListWindows => handle_list_windows(state).await,
+        Request::GetLocale => handle_get_locale(state).await,
+        Request::GetTextChunk {
+            node_id,
+            offset,
+            length,
+        } => handle_get_text_chunk(state, &node_id, offset, length).await,
+        Request::TreeStats { max_nodes } => handle_tree_stats(state, max_nodes).await,
+        Request::FindRelative {
+            anchor,
+            direction,
+            role,
+        } => handle_find_relative(state, &anchor, direction, role.as_deref()).await,
+        Request::ValidateNode { node_id } => handle_validate_node(state, &node_id).await,
+        Request::ListRoles => Response::Success {
+            result: ResponseData::RoleMap {
+                roles: state.provider.roles(),
+            },
+        },
         Request::Initialize {
             protocol_version,
             capabilities,
@@ -157,310 +663,6418 @@ async fn handle_request(
         Request::ToolsList => handle_tools_list().await,
     };
 
-    Message::response(response)
-}
+    let response = match response {
+        Response::Error { error } if may_mean_app_exit(error.code) && app_terminated(state) => {
+            if let Some(shutdown_tx) = &state.shutdown_on_app_exit {
+                if state.targets.iter().all(|(_, target)| target.has_quit()) {
+                    tracing::info!("Every target application has terminated; shutting down");
+                    let _ = shutdown_tx.send(true);
+                } else {
+                    tracing::info!("Target application {} has terminated", target_name);
+                }
+            }
+            Response::error(
+                ErrorCode::AppTerminated,
+                "the target application has terminated",
+            )
+        }
+        response => response,
+    };
 
-async fn handle_query_tree(
-    provider: &Arc<Box<dyn AccessibilityProvider>>,
-    _max_depth: Option<usize>,
-    _max_nodes: Option<usize>,
-) -> Response {
-    match provider.get_root() {
-        Ok(root) => Response::Success {
-            result: ResponseData::Tree { nodes: vec![root] },
-        },
-        Err(e) => Response::Error {
-            error: crate::protocol::ErrorInfo {
-                code: ErrorCode::Internal,
-                message: format!("Failed to get root: {}", e),
-            },
-        },
-    }
+    #[cfg(feature = "accesskit")]
+    let response = if state.normalize_roles {
+        add_accesskit_roles(response)
+    } else {
+        response
+    };
+
+    let response = with_visual_info(state, response);
+    let response = add_fingerprints(response);
+    Message::response(stamp_epoch(response, state.node_cache.epoch()))
 }
 
-async fn handle_get_node(
-    provider: &Arc<Box<dyn AccessibilityProvider>>,
-    node_id: &crate::protocol::NodeId,
-) -> Response {
-    match provider.get_node(node_id) {
-        Ok(node) => Response::Success {
-            result: ResponseData::Node { node },
-        },
-        Err(e) => Response::Error {
-            error: crate::protocol::ErrorInfo {
-                code: ErrorCode::NotFound,
-                message: format!("Node not found: {}", e),
-            },
-        },
+/// Fill in `akit_role` on every node in a successful response.
+///
+/// Projected nodes only get it when their `role` was kept.
+#[cfg(feature = "accesskit")]
+fn add_accesskit_roles(mut response: Response) -> Response {
+    use crate::roles::accesskit_role_name;
+
+    let project = |node: &mut serde_json::Value| {
+        if let Some(node) = node.as_object_mut() {
+            if let Some(role) = node.get("role").and_then(|r| r.as_str()) {
+                let name = accesskit_role_name(role);
+                node.insert("akit_role".to_string(), name.into());
+            }
+        }
+    };
+
+    if let Response::Success { result } = &mut response {
+        match result {
+            ResponseData::Tree { nodes, .. } | ResponseData::Nodes { nodes, .. } => {
+                for node in nodes.iter_mut() {
+                    node.akit_role = Some(accesskit_role_name(&node.role));
+                }
+            }
+            ResponseData::Node { node } => node.akit_role = Some(accesskit_role_name(&node.role)),
+            ResponseData::ProjectedNodes { nodes, .. } => nodes.iter_mut().for_each(project),
+            ResponseData::ProjectedNode { node } => project(node),
+            ResponseData::RoleMap { roles } => {
+                for entry in roles.iter_mut() {
+                    entry.normalized_role = Some(accesskit_role_name(&entry.role));
+                }
+            }
+            _ => {}
+        }
     }
+    response
 }
 
-async fn handle_perform_action(
-    provider: &Arc<Box<dyn AccessibilityProvider>>,
-    node_id: &crate::protocol::NodeId,
-    action: &crate::protocol::Action,
-) -> Response {
-    match provider.perform_action(node_id, action) {
-        Ok(()) => Response::Success {
-            result: ResponseData::ActionResult { success: true },
-        },
-        Err(e) => Response::Error {
-            error: crate::protocol::ErrorInfo {
-                code: ErrorCode::InvalidAction,
-                message: format!("Failed to perform action: {}", e),
-            },
-        },
+/// Fill in `visual` when `Config::visual` is on.
+///
+/// Projections run this before dropping fields, since it needs the bounds
+/// and actions a projection naming `visual` may leave out.
+fn with_visual_info(state: &AppState, response: Response) -> Response {
+    #[cfg(feature = "visual")]
+    if state.visual {
+        return add_visual_info(response);
     }
+    let _ = state;
+    response
 }
 
-async fn handle_find_by_name(
-    provider: &Arc<Box<dyn AccessibilityProvider>>,
-    name: &str,
-) -> Response {
-    // Get the root node and traverse the tree
-    let root = match provider.get_root() {
-        Ok(r) => r,
-        Err(e) => {
-            return Response::Error {
-                error: crate::protocol::ErrorInfo {
-                    code: ErrorCode::Internal,
-                    message: format!("Failed to get root: {}", e),
-                },
+/// Fill in `visual` on every node with bounds in a successful response
+#[cfg(feature = "visual")]
+fn add_visual_info(mut response: Response) -> Response {
+    use crate::protocol::VisualInfo;
+
+    let add = |node: &mut Node| node.visual = VisualInfo::for_node(node);
+    if let Response::Success { result } = &mut response {
+        match result {
+            ResponseData::Tree { nodes, .. } | ResponseData::Nodes { nodes, .. } => {
+                nodes.iter_mut().for_each(add)
             }
+            ResponseData::Node { node } => add(node),
+            _ => {}
         }
-    };
-
-    // Perform breadth-first search to find matching nodes
-    let mut matches = Vec::new();
-    let mut to_visit = vec![root];
-    let mut visited = std::collections::HashSet::new();
+    }
+    response
+}
 
-    // Limit search to prevent infinite loops
-    const MAX_NODES: usize = 1000;
-    let mut nodes_checked = 0;
+/// Whether an error with `code` may have come from the provider, and so
+/// may be explained by the application having quit. Malformed requests and
+/// refusals made before reaching the provider can't be.
+fn may_mean_app_exit(code: ErrorCode) -> bool {
+    matches!(
+        code,
+        ErrorCode::NotFound | ErrorCode::Transient | ErrorCode::InvalidAction | ErrorCode::Internal
+    )
+}
 
-    while let Some(node) = to_visit.pop() {
-        if nodes_checked >= MAX_NODES {
-            tracing::warn!("find_by_name: hit max nodes limit of {}", MAX_NODES);
-            break;
-        }
-        nodes_checked += 1;
+/// Whether the targeted application has quit, asking its provider unless it
+/// is already known to have.
+///
+/// Checked after a request fails, since whatever went wrong, a dead app is
+/// the explanation an agent needs.
+fn app_terminated(state: &AppState) -> bool {
+    if state.terminated.load(Ordering::SeqCst) {
+        return true;
+    }
+    let terminated = matches!(state.provider.get_root(), Err(e) if is_app_terminated(&e));
+    if terminated {
+        state.terminated.store(true, Ordering::SeqCst);
+    }
+    terminated
+}
 
-        // Skip if already visited (prevent cycles)
-        if !visited.insert(node.id.clone()) {
-            continue;
-        }
+/// The attributes to read for a projection; everything when there is none
+fn read_fields(fields: Option<&[String]>) -> NodeFields {
+    fields.map_or(NodeFields::ALL, NodeFields::for_projection)
+}
 
-        // Check if this node matches (case-insensitive substring match)
-        if let Some(node_name) = &node.name {
-            if node_name.to_lowercase().contains(&name.to_lowercase()) {
-                matches.push(node.clone());
+/// Fill in `fingerprint` on every node in a successful response.
+///
+/// Projected responses are fingerprinted before projection, in
+/// [`project_response`], and keep it only if it was asked for.
+fn add_fingerprints(mut response: Response) -> Response {
+    let add = |node: &mut Node| node.fingerprint = Some(node.content_fingerprint());
+    if let Response::Success { result } = &mut response {
+        match result {
+            ResponseData::Tree { nodes, .. } | ResponseData::Nodes { nodes, .. } => {
+                nodes.iter_mut().for_each(add);
             }
+            ResponseData::Node { node } => add(node),
+            _ => {}
         }
+    }
+    response
+}
 
-        // Add children to the queue
-        for child_id in &node.children {
-            match provider.get_node(child_id) {
-                Ok(child) => to_visit.push(child),
-                Err(e) => {
-                    tracing::debug!("Failed to get child node {:?}: {}", child_id, e);
-                    // Continue with other children
+/// Replace a node's child ids with their count
+fn count_children(node: &mut Node) {
+    node.child_count = Some(node.children.len());
+    node.children = Vec::new();
+}
+
+/// Leave the child ids out of a search's results unless they were asked for
+fn omit_children_unless(include_children: bool, mut response: Response) -> Response {
+    if include_children {
+        return response;
+    }
+    if let Response::Success {
+        result: ResponseData::Nodes { nodes, .. },
+    } = &mut response
+    {
+        nodes.iter_mut().for_each(count_children);
+    }
+    response
+}
+
+/// Mark every node in a successful response with the current epoch
+fn stamp_epoch(mut response: Response, epoch: u64) -> Response {
+    if let Response::Success { result } = &mut response {
+        match result {
+            ResponseData::Tree { nodes, .. } | ResponseData::Nodes { nodes, .. } => {
+                nodes.iter_mut().for_each(|n| n.epoch = epoch);
+            }
+            ResponseData::Node { node } => node.epoch = epoch,
+            ResponseData::ProjectedNodes { nodes, .. } => {
+                for node in nodes.iter_mut().filter_map(|n| n.as_object_mut()) {
+                    node.insert("epoch".to_string(), epoch.into());
+                }
+            }
+            ResponseData::ProjectedNode { node } => {
+                if let Some(node) = node.as_object_mut() {
+                    node.insert("epoch".to_string(), epoch.into());
                 }
             }
+            _ => {}
         }
     }
+    response
+}
 
-    Response::Success {
-        result: ResponseData::Nodes { nodes: matches },
+/// Reduce the nodes in a successful response to the requested fields
+fn project_response(state: &AppState, response: Response, fields: Option<&[String]>) -> Response {
+    let Some(fields) = fields else {
+        return response;
+    };
+
+    match add_fingerprints(with_visual_info(state, response)) {
+        Response::Success {
+            result:
+                ResponseData::Tree { nodes, truncated } | ResponseData::Nodes { nodes, truncated },
+        } => Response::Success {
+            result: ResponseData::ProjectedNodes {
+                nodes: nodes.iter().map(|n| n.project(fields)).collect(),
+                truncated,
+            },
+        },
+        Response::Success {
+            result: ResponseData::Node { node },
+        } => Response::Success {
+            result: ResponseData::ProjectedNode {
+                node: node.project(fields),
+            },
+        },
+        other => other,
     }
 }
 
-async fn handle_initialize(
-    protocol_version: Option<String>,
-    _capabilities: Option<serde_json::Value>,
+/// Like [`project_response`] for a single node, with each of its actions
+/// given a role-aware `description` alongside its `type`
+fn project_with_action_descriptions(
+    state: &AppState,
+    response: Response,
+    fields: Option<&[String]>,
 ) -> Response {
-    // Validate protocol version if provided
-    if let Some(version) = protocol_version {
-        if !version.starts_with("1.") {
-            return Response::Error {
-                error: crate::protocol::ErrorInfo {
-                    code: ErrorCode::Internal,
-                    message: format!("Unsupported protocol version: {}", version),
-                },
+    match add_fingerprints(with_visual_info(state, response)) {
+        Response::Success {
+            result: ResponseData::Node { node },
+        } => {
+            let mut value = match fields {
+                Some(fields) => node.project(fields),
+                None => serde_json::to_value(&node).expect("Node always serializes"),
             };
+            if let Some(actions) = value.get_mut("actions") {
+                *actions = node
+                    .actions
+                    .iter()
+                    .map(|action| {
+                        let mut described =
+                            serde_json::to_value(action).expect("Action always serializes");
+                        described["description"] =
+                            crate::describe::describe_action(&node.role, action).into();
+                        described
+                    })
+                    .collect();
+            }
+            Response::Success {
+                result: ResponseData::ProjectedNode { node: value },
+            }
         }
+        other => other,
+    }
+}
+
+/// The instant a request with a `deadline_ms` must finish by
+fn deadline_after(state: &AppState, deadline_ms: Option<u64>) -> Option<Instant> {
+    deadline_ms.map(|ms| Instant::now() + state.wait_limit(ms))
+}
+
+/// Resolve where a traversal starts: the given window, or the application root
+#[allow(clippy::result_large_err)]
+fn traversal_root(
+    provider: &Arc<Box<dyn AccessibilityProvider>>,
+    window_id: Option<&crate::protocol::NodeId>,
+) -> Result<Node, Response> {
+    match window_id {
+        Some(window_id) => provider
+            .get_node(window_id)
+            .map_err(|e| Response::error(ErrorCode::NotFound, format!("Window not found: {}", e))),
+        None => provider.get_root().map_err(|e| {
+            Response::error(ErrorCode::Internal, format!("Failed to get root: {}", e))
+        }),
+    }
+}
+
+async fn handle_query_tree(
+    state: &AppState,
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    order: TraversalOrder,
+    window_id: Option<&crate::protocol::NodeId>,
+    deadline: Option<Instant>,
+    fields: NodeFields,
+) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, window_id) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    // A full re-read of the tree rebuilds the cache, so ids handed out
+    // before now belong to an older epoch
+    if window_id.is_none() {
+        state.node_cache.rebuild();
     }
 
+    let mut walk = state.walk(order);
+    walk.max_depth = max_depth;
+    walk.max_nodes = max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+    walk.deadline = deadline;
+    walk.fields = fields;
+
+    // Visibility comes from bounds, so there is nothing to work out without them
+    let mut visibility = fields
+        .contains(NodeFields::BOUNDS)
+        .then(|| VisibilityTracker::new(frontmost_window(provider).as_ref()));
+    let mut nodes = Vec::new();
+    let truncated = walk.run(&***provider, root, |node, _| {
+        let mut node = node.clone();
+        node.visible = visibility.as_mut().and_then(|v| v.visit(&node));
+        nodes.push(node);
+    });
+
     Response::Success {
-        result: ResponseData::Initialize {
-            protocol_version: Message::PROTOCOL_VERSION.to_string(),
-            capabilities: crate::protocol::Capabilities {
-                tools: Some(crate::protocol::ToolsCapability {
-                    list_changed: false,
-                }),
-            },
-            server_info: crate::protocol::ServerInfo {
-                name: "accessibility_mcp".to_string(),
-                version: env!("CARGO_PKG_VERSION").to_string(),
-            },
-        },
+        result: ResponseData::Tree { nodes, truncated },
     }
 }
 
-async fn handle_tools_list() -> Response {
-    use crate::protocol::Tool;
+/// Count a subtree, reading nothing but each node's role and children
+async fn handle_count_nodes(
+    state: &AppState,
+    root: Option<&crate::protocol::NodeId>,
+    max_depth: Option<usize>,
+) -> Response {
+    let provider = &state.provider;
+    let root = match root {
+        Some(root) => provider
+            .get_node_fields(root, NodeFields::CHILDREN)
+            .map_err(|e| Response::error(ErrorCode::NotFound, format!("Node not found: {}", e))),
+        None => traversal_root(provider, None),
+    };
+    let root = match root {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
 
-    let tools = vec![
-        Tool {
-            name: "query_tree".to_string(),
-            description: "Query the accessibility tree starting from the root node".to_string(),
-            input_schema: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "max_depth": {
-                        "type": "integer",
-                        "description": "Maximum depth to traverse (optional)"
-                    },
-                    "max_nodes": {
-                        "type": "integer",
-                        "description": "Maximum number of nodes to return (optional)"
-                    }
-                }
-            }),
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_depth = max_depth;
+    walk.fields = NodeFields::NONE;
+
+    let mut count = 0;
+    let truncated = walk.run(&***provider, root, |_, _| count += 1);
+
+    Response::Success {
+        result: ResponseData::Count { count, truncated },
+    }
+}
+
+/// Hash a subtree from each node's fingerprint and depth, reading only the
+/// fields a fingerprint covers
+async fn handle_tree_hash(
+    state: &AppState,
+    root: Option<&crate::protocol::NodeId>,
+    max_depth: Option<usize>,
+) -> Response {
+    let provider = &state.provider;
+    let root = match root {
+        Some(root) => provider
+            .get_node_fields(root, NodeFields::FINGERPRINT)
+            .map_err(|e| Response::error(ErrorCode::NotFound, format!("Node not found: {}", e))),
+        None => traversal_root(provider, None),
+    };
+    let root = match root {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_depth = max_depth;
+    walk.fields = NodeFields::FINGERPRINT;
+
+    // Depth goes in too, so moving a node elsewhere changes the hash
+    let mut bytes = Vec::new();
+    let mut node_count = 0;
+    let truncated = walk.run(&***provider, root, |node, depth| {
+        bytes.extend_from_slice(node.content_fingerprint().as_bytes());
+        bytes.extend_from_slice(&(depth as u64).to_le_bytes());
+        node_count += 1;
+    });
+
+    Response::Success {
+        result: ResponseData::Hash {
+            hash: format!("{:016x}", crate::protocol::fnv1a(&bytes)),
+            node_count,
+            truncated,
         },
-        Tool {
-            name: "get_node".to_string(),
-            description: "Get details for a specific accessibility node by ID".to_string(),
-            input_schema: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "node_id": {
-                        "type": "string",
-                        "description": "The unique identifier of the node"
-                    }
-                },
-                "required": ["node_id"]
-            }),
+    }
+}
+
+/// Measure the tree's size and shape, reading nothing but roles and children
+async fn handle_tree_stats(state: &AppState, max_nodes: Option<usize>) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_nodes = max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+    walk.fields = NodeFields::NONE;
+
+    let mut node_count = 0;
+    let mut max_depth = 0;
+    let mut max_children = 0;
+    let mut role_histogram = std::collections::BTreeMap::new();
+    let truncated = walk.run(&***provider, root, |node, depth| {
+        node_count += 1;
+        max_depth = max_depth.max(depth);
+        max_children = max_children.max(node.children.len());
+        *role_histogram.entry(node.role.clone()).or_insert(0) += 1;
+    });
+
+    Response::Success {
+        result: ResponseData::TreeStats {
+            node_count,
+            max_depth,
+            max_children,
+            role_histogram,
+            truncated,
         },
-        Tool {
-            name: "perform_action".to_string(),
-            description: "Perform an accessibility action on a node".to_string(),
-            input_schema: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "node_id": {
-                        "type": "string",
-                        "description": "The unique identifier of the node"
-                    },
-                    "action": {
-                        "type": "object",
-                        "description": "The action to perform",
-                        "properties": {
-                            "type": {
-                                "type": "string",
-                                "enum": ["focus", "press", "increment", "decrement", "set_value", "scroll", "context_menu", "custom"]
-                            }
-                        },
-                        "required": ["type"]
-                    }
-                },
-                "required": ["node_id", "action"]
-            }),
+    }
+}
+
+/// Walk the tree and render it as GraphViz DOT
+async fn handle_render_dot(state: &AppState, max_depth: Option<usize>) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_depth = max_depth;
+    walk.fields = NodeFields::NAME;
+
+    let mut nodes = Vec::new();
+    let truncated = walk.run(&***provider, root, |node, _| nodes.push(node.clone()));
+
+    Response::Success {
+        result: ResponseData::Text {
+            text: crate::dot::render_dot(&nodes, truncated),
         },
-        Tool {
-            name: "find_by_name".to_string(),
-            description: "Find accessibility nodes by name (substring match)".to_string(),
-            input_schema: serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "name": {
-                        "type": "string",
-                        "description": "The name or partial name to search for"
-                    }
-                },
-                "required": ["name"]
-            }),
+    }
+}
+
+/// Most nodes `export_tree` writes, so a runaway tree can't fill the disk
+const MAX_EXPORTED_NODES: usize = 100_000;
+
+/// Write the tree as a pretty JSON document for attaching to bug reports.
+///
+/// `path` is relative to `Config::export_dir`, and an existing file is never
+/// replaced: any web page can reach the HTTP transport, so a client must not
+/// be able to write anywhere else.
+async fn handle_export_tree(state: &AppState, path: &str, max_depth: Option<usize>) -> Response {
+    let Some(export_dir) = &state.export_dir else {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            "export_tree is disabled; set Config::export_dir to allow it",
+        );
+    };
+    let relative = std::path::Path::new(path);
+    let inside = relative
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)));
+    if path.is_empty() || !inside {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "path must be relative to the export directory, without '..': {}",
+                path
+            ),
+        );
+    }
+    let path = export_dir.join(relative);
+
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+    let app = serde_json::json!({
+        "name": root.name,
+        "role": root.role,
+        "pid": std::process::id(),
+    });
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_depth = max_depth;
+    walk.max_nodes = MAX_EXPORTED_NODES;
+    let mut nodes = Vec::new();
+    let truncated = walk.run(&***provider, root, |node, _| nodes.push(node.clone()));
+    let node_count = nodes.len();
+
+    let exported_at_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let document = serde_json::json!({
+        "protocol_version": Message::PROTOCOL_VERSION,
+        "exported_at_ms": exported_at_ms,
+        "platform": std::env::consts::OS,
+        "app": app,
+        "max_depth": max_depth,
+        "truncated": truncated,
+        "nodes": nodes,
+    });
+    let target = path.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)?;
+        let mut writer = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+        std::io::Write::flush(&mut writer)
+    })
+    .await
+    .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+    match written {
+        Ok(()) => Response::Success {
+            result: ResponseData::Exported {
+                path: path.display().to_string(),
+                node_count,
+                truncated,
+            },
         },
-    ];
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Response::error(
+            ErrorCode::InvalidRequest,
+            format!("{} already exists", path.display()),
+        ),
+        Err(e) => Response::error(
+            ErrorCode::Internal,
+            format!("Failed to write {}: {}", path.display(), e),
+        ),
+    }
+}
+
+/// Default depth of `get_menu_bar`: menu bar items, their menus, and the menu items
+const DEFAULT_MENU_DEPTH: usize = 3;
+
+async fn handle_get_menu_bar(state: &AppState, max_depth: Option<usize>) -> Response {
+    let provider = &state.provider;
+    let menu_bar = match provider.get_menu_bar() {
+        Ok(Some(menu_bar)) => menu_bar,
+        Ok(None) => {
+            return Response::error(ErrorCode::NotFound, "Application has no menu bar");
+        }
+        Err(e) => {
+            return Response::error(
+                ErrorCode::Internal,
+                format!("Failed to read menu bar: {}", e),
+            );
+        }
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_depth = Some(max_depth.unwrap_or(DEFAULT_MENU_DEPTH));
+    let mut nodes = Vec::new();
+    let truncated = walk.run(&***provider, menu_bar, |node, _| nodes.push(node.clone()));
 
     Response::Success {
-        result: ResponseData::Tools { tools },
+        result: ResponseData::Tree { nodes, truncated },
     }
 }
 
-/// Shared state for the HTTP server
-#[derive(Clone)]
-struct AppState {
-    provider: Arc<Box<dyn AccessibilityProvider>>,
-}
+/// Most snapshots one `capture_snapshots` request takes
+const MAX_SNAPSHOTS: usize = 20;
 
-/// HTTP handler for MCP requests
-async fn mcp_handler(
-    State(state): State<AppState>,
-    Json(message): Json<Message>,
-) -> Result<Json<Message>, AppError> {
-    let response = handle_request(&state.provider, message).await;
-    Ok(Json(response))
+/// Read the whole tree `count` times (at most [`MAX_SNAPSHOTS`]), `interval_ms` apart
+async fn handle_capture_snapshots(state: &AppState, count: usize, interval_ms: u64) -> Response {
+    if count == 0 {
+        return Response::error(ErrorCode::InvalidRequest, "count must be at least 1");
+    }
+
+    let provider = &state.provider;
+    let interval = state.wait_limit(interval_ms);
+    let mut snapshots = Vec::new();
+    for i in 0..count.min(MAX_SNAPSHOTS) {
+        if i > 0 {
+            tokio::time::sleep(interval).await;
+        }
+        let root = match traversal_root(provider, None) {
+            Ok(root) => root,
+            Err(response) => return response,
+        };
+        let mut nodes = Vec::new();
+        state
+            .walk(TraversalOrder::DocumentOrder)
+            .run(&***provider, root, |node, _| nodes.push(node.clone()));
+        snapshots.push(nodes);
+    }
+
+    Response::Success {
+        result: ResponseData::Snapshots { snapshots },
+    }
 }
 
-/// Error wrapper for HTTP responses
-struct AppError(String);
+/// Read a node and report whether it is on screen.
+///
+/// Visibility depends on the node's ancestors, so this walks up to the root
+/// and replays the chain top-down, rather than reading the node alone.
+async fn handle_is_visible(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let node = match provider.get_node(node_id) {
+        Ok(node) => node,
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> AxumResponse {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Message::error(ErrorCode::Internal, self.0)),
-        )
-            .into_response()
+    let mut ancestors = Vec::new();
+    let mut current = node.id.clone();
+    // Bounded in case a misbehaving tree loops back on itself
+    while ancestors.len() < DEFAULT_MAX_NODES {
+        match provider.get_parent(&current) {
+            Ok(Some(parent)) => {
+                current = parent.id.clone();
+                ancestors.push(parent);
+            }
+            Ok(None) => break,
+            Err(e) => {
+                return Response::error(
+                    ErrorCode::Internal,
+                    format!("Failed to read ancestors of {}: {}", node_id.as_str(), e),
+                )
+            }
+        }
+    }
+
+    let mut visibility = VisibilityTracker::new(frontmost_window(provider).as_ref());
+    for ancestor in ancestors.iter().rev() {
+        visibility.visit(ancestor);
+    }
+    let visible = visibility.visit(&node);
+
+    Response::Success {
+        result: ResponseData::Node {
+            node: Node { visible, ..node },
+        },
     }
 }
 
-impl<E> From<E> for AppError
-where
-    E: std::error::Error,
-{
-    fn from(err: E) -> Self {
-        AppError(err.to_string())
+async fn handle_get_node(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    include_children: bool,
+    fields: NodeFields,
+) -> Response {
+    // Only complete nodes are cached, so a partial read is only worth it
+    // uncached. Cached nodes lack opt-in attributes, which always read afresh
+    let node = if state.node_cache.is_enabled() && !fields.contains(NodeFields::FOCUSABLE) {
+        state
+            .node_cache
+            .get_or_fetch(node_id, || state.provider.get_node(node_id))
+    } else {
+        state.provider.get_node_fields(node_id, fields)
+    };
+
+    match node {
+        Ok(mut node) => {
+            if !include_children {
+                count_children(&mut node);
+            }
+            Response::Success {
+                result: ResponseData::Node { node },
+            }
+        }
+        Err(e) => Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
     }
 }
 
-/// Run the HTTP-based MCP server
-async fn run_http_server(
-    provider: Arc<Box<dyn AccessibilityProvider>>,
-    shutdown_rx: oneshot::Receiver<()>,
-    port: u16,
-    port_tx: oneshot::Sender<u16>,
-) {
-    let state = AppState { provider };
+/// Perform an action, recording it in the action log if it succeeds
+async fn handle_perform_action(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    action: &crate::protocol::Action,
+) -> Response {
+    use crate::protocol::Action;
 
-    let app = Router::new()
-        .route("/mcp", post(mcp_handler))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+    if let Some(message) = action_parameter_error(action) {
+        return Response::error(ErrorCode::InvalidRequest, message);
+    }
 
-    let addr = format!("127.0.0.1:{}", port);
-    let listener = match tokio::net::TcpListener::bind(&addr).await {
-        Ok(l) => l,
-        Err(e) => {
-            tracing::error!("Failed to bind to {}: {}", addr, e);
-            return;
-        }
+    // Read before mutating, so the log has what's needed to undo the action
+    let changes_value = matches!(
+        action,
+        Action::SetValue { .. }
+            | Action::Increment
+            | Action::Decrement
+            | Action::SetChecked { .. }
+            | Action::Press
+            | Action::PressAt { .. }
+            | Action::TypeText { .. }
+    );
+    let before = if changes_value && state.action_log.is_enabled() {
+        state
+            .provider
+            .get_node_fields(node_id, NodeFields::VALUE)
+            .ok()
+    } else {
+        None
     };
+    // What is typed into a password field is as secret as its value
+    let secure =
+        state.redact_secure_fields && before.as_ref().is_some_and(crate::redact::is_secure_field);
+    let previous_value = before.and_then(|node| node.value);
 
-    // Get the actual bound port (important when port 0 is used)
-    let bound_port = listener.local_addr().unwrap().port();
-    tracing::info!("HTTP server listening on http://127.0.0.1:{}", bound_port);
+    let response = perform_action(state, node_id, action).await;
+    if matches!(response, Response::Success { .. }) {
+        state
+            .action_log
+            .record(node_id, action, previous_value, secure);
+    }
+    response
+}
 
-    // Send the bound port back to the caller
-    let _ = port_tx.send(bound_port);
+/// Act on every node matching `find`, each as its own logged `perform_action`.
+///
+/// Refused without acting when more than `max_batch_actions` nodes match.
+async fn handle_perform_on_matches(
+    state: &AppState,
+    find: &crate::protocol::FindByPredicate,
+    action: &crate::protocol::Action,
+) -> Response {
+    if find.is_empty() {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            "find needs a role or name_contains; acting on every node is refused",
+        );
+    }
 
-    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
-        let _ = shutdown_rx.await;
-        tracing::info!("HTTP server shutting down");
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.fields = NodeFields::NAME | NodeFields::CHILDREN;
+    let mut matches = Vec::new();
+    let truncated = walk.run(&***provider, root, |node, _| {
+        if find.matches(node) {
+            matches.push(node.id.clone());
+        }
     });
+    if matches.len() > state.max_batch_actions {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            format!(
+                "{} nodes match, more than the limit of {}; narrow the query",
+                matches.len(),
+                state.max_batch_actions
+            ),
+        );
+    }
 
-    if let Err(e) = server.await {
-        tracing::error!("Server error: {}", e);
+    let mut failures = Vec::new();
+    for node_id in &matches {
+        if let Response::Error { error } = handle_perform_action(state, node_id, action).await {
+            failures.push((node_id.clone(), error.message));
+        }
+    }
+
+    Response::Success {
+        result: ResponseData::BatchActionResult {
+            total: matches.len(),
+            succeeded: matches.len() - failures.len(),
+            failures,
+            truncated,
+        },
+    }
+}
+
+async fn perform_action(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    action: &crate::protocol::Action,
+) -> Response {
+    let response = dispatch_action(state, node_id, action).await;
+    // Whatever happened, cached nodes may now be stale: not only the target,
+    // but anything the action touched (a radio group, a list's selection),
+    // and any copy a concurrent read cached mid-action
+    state.node_cache.clear();
+    response
+}
+
+async fn dispatch_action(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    action: &crate::protocol::Action,
+) -> Response {
+    let provider = &state.provider;
+
+    // Focus is never refused, so skip the read for it. Anything else is
+    // refused when the checks can't be made: a disabled control must not be
+    // pressed just because its state couldn't be read
+    if !matches!(action, crate::protocol::Action::Focus) {
+        let checked = NodeFields::ENABLED | NodeFields::BOUNDS;
+        let node = match provider.get_node_fields(node_id, checked) {
+            Ok(node) => node,
+            Err(e) => {
+                return Response::error(
+                    ErrorCode::InvalidAction,
+                    format!("Failed to check element before acting: {}", e),
+                )
+            }
+        };
+        if let Some(message) = action_refusal(provider, &node, action) {
+            return Response::error(ErrorCode::InvalidAction, message);
+        }
+    }
+
+    // Synthesized input goes to whichever app is in front, which may not be this one
+    let synthesized = matches!(
+        action,
+        crate::protocol::Action::PressAt { .. } | crate::protocol::Action::TypeText { .. }
+    );
+    if synthesized && state.require_frontmost && !provider.is_frontmost_app().unwrap_or(false) {
+        return Response::error(ErrorCode::Transient, "target app not frontmost");
+    }
+
+    if let crate::protocol::Action::SetChecked {
+        checked: Some(checked),
+    } = action
+    {
+        return handle_set_checked(provider, node_id, *checked).await;
+    }
+
+    // Keystrokes go wherever keyboard focus is, so move it to the node first
+    if let crate::protocol::Action::TypeText { .. } = action {
+        if let Err(e) = provider.set_keyboard_focus(node_id) {
+            return Response::error(
+                ErrorCode::InvalidAction,
+                format!("Element can't be focused: {}", e),
+            );
+        }
+    }
+
+    match provider.perform_action(node_id, action) {
+        Ok(()) => Response::Success {
+            result: ResponseData::ActionResult {
+                success: true,
+                new_value: None,
+            },
+        },
+        Err(e) => Response::error(
+            ErrorCode::InvalidAction,
+            format!("Failed to perform action: {}", e),
+        ),
+    }
+}
+
+/// Largest scroll amount accepted in either direction; anything beyond is
+/// taken for a client bug rather than a real gesture
+const MAX_SCROLL_DELTA: f64 = 100_000.0;
+
+/// Why `action`'s parameters are malformed, if they are, whatever node it's for.
+///
+/// Checked before the provider is consulted at all.
+fn action_parameter_error(action: &crate::protocol::Action) -> Option<String> {
+    match action {
+        crate::protocol::Action::Scroll { x, y } if !x.is_finite() || !y.is_finite() => Some(
+            format!("scroll amounts must be finite numbers, got {}, {}", x, y),
+        ),
+        crate::protocol::Action::SetChecked { checked: None } => {
+            Some("set_checked needs `checked`, true or false".to_string())
+        }
+        // The click point is computed from the bounds, and must land inside them
+        crate::protocol::Action::PressAt { dx, dy }
+            if !(0.0..=1.0).contains(dx) || !(0.0..=1.0).contains(dy) =>
+        {
+            Some(format!(
+                "press_at offsets must be between 0 and 1, got {}, {}",
+                dx, dy
+            ))
+        }
+        crate::protocol::Action::Scroll { x, y }
+            if x.abs() > MAX_SCROLL_DELTA || y.abs() > MAX_SCROLL_DELTA =>
+        {
+            Some(format!(
+                "scroll amounts must be at most {} either way, got {}, {}",
+                MAX_SCROLL_DELTA, x, y
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Why `action` would be refused on `node` before reaching the platform, if it would be.
+///
+/// Needs the node's role, `enabled` state, and bounds; nothing else is consulted.
+fn action_refusal(
+    provider: &Arc<Box<dyn AccessibilityProvider>>,
+    node: &Node,
+    action: &crate::protocol::Action,
+) -> Option<String> {
+    match action {
+        // Focusing a disabled control is harmless
+        crate::protocol::Action::Focus => None,
+        #[cfg(not(feature = "keystrokes"))]
+        crate::protocol::Action::TypeText { .. } => {
+            Some("type_text requires the `keystrokes` feature".to_string())
+        }
+        // Disabled controls ignore input or fail with an opaque platform error
+        _ if node.enabled == Some(false) => Some(if node.role == "AXMenuItem" {
+            "menu item is disabled".to_string()
+        } else {
+            "element is disabled".to_string()
+        }),
+        // Refuse names the element doesn't advertise rather than handing them to the platform
+        crate::protocol::Action::Custom { name } => match provider.native_action_names(&node.id) {
+            Ok(Some(available)) if !available.contains(name) => Some(format!(
+                "unsupported custom action '{}'; available: [{}]",
+                name,
+                available.join(", ")
+            )),
+            Ok(_) => None,
+            // Without the native names, only names the role's actions stand for are accepted
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to read native actions of {}, checking against its role: {}",
+                    node.id.as_str(),
+                    e
+                );
+                let offered =
+                    crate::platform::action_for_native_name(name).is_some_and(|action_type| {
+                        node.actions.iter().any(|a| a.action_type() == action_type)
+                    });
+                (!offered).then(|| {
+                    let types: Vec<_> = node.actions.iter().map(|a| a.action_type()).collect();
+                    format!(
+                        "unsupported custom action '{}'; native actions unavailable, role offers: [{}]",
+                        name,
+                        types.join(", ")
+                    )
+                })
+            }
+        },
+        crate::protocol::Action::PressAt { .. } if node.bounds.is_none() => Some(format!(
+            "Node {} has no bounds to press within",
+            node.id.as_str()
+        )),
+        _ => None,
+    }
+}
+
+async fn handle_get_native_actions(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+) -> Response {
+    let names = match state.provider.native_action_names(node_id) {
+        Ok(Some(names)) => names,
+        Ok(None) => {
+            return Response::error(
+                ErrorCode::InvalidRequest,
+                "this platform doesn't list native actions",
+            )
+        }
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+
+    let actions = names
+        .into_iter()
+        .map(|name| crate::protocol::NativeAction {
+            maps_to: crate::platform::action_for_native_name(&name).map(str::to_string),
+            name,
+        })
+        .collect();
+    Response::Success {
+        result: ResponseData::NativeActions { actions },
+    }
+}
+
+/// Dry-run a sequence of actions: check each step without performing it
+async fn handle_validate_plan(state: &AppState, steps: &[crate::protocol::ActionStep]) -> Response {
+    use crate::protocol::{Action, StepValidation};
+
+    let provider = &state.provider;
+    let steps: Vec<_> = steps
+        .iter()
+        .map(|step| {
+            let reason = action_parameter_error(&step.action).or_else(|| {
+                match provider.get_node(&step.node_id) {
+                    Err(e) => Some(format!("Node not found: {}", e)),
+                    Ok(node) => match &step.action {
+                        Action::SetChecked { checked } => checked
+                            .and_then(|checked| {
+                                crate::platform::set_checked_refusal(&node, checked)
+                            })
+                            .or_else(|| action_refusal(provider, &node, &step.action)),
+                        // Custom actions, positional presses, and typing aren't listed among the node's actions
+                        Action::Custom { .. }
+                        | Action::PressAt { .. }
+                        | Action::TypeText { .. } => action_refusal(provider, &node, &step.action),
+                        action => {
+                            let action_type = action.action_type();
+                            if node.actions.iter().any(|a| a.action_type() == action_type) {
+                                action_refusal(provider, &node, action)
+                            } else {
+                                Some(format!(
+                                    "{} {} does not support '{}'",
+                                    node.role,
+                                    node.id.as_str(),
+                                    action_type
+                                ))
+                            }
+                        }
+                    },
+                }
+            });
+            StepValidation {
+                ok: reason.is_none(),
+                reason,
+            }
+        })
+        .collect();
+
+    Response::Success {
+        result: ResponseData::PlanValidation {
+            valid: steps.iter().all(|step| step.ok),
+            steps,
+        },
+    }
+}
+
+/// Bring a toggle to the requested state, pressing it only if it differs
+async fn handle_set_checked(
+    provider: &Arc<Box<dyn AccessibilityProvider>>,
+    node_id: &crate::protocol::NodeId,
+    checked: bool,
+) -> Response {
+    let node = match provider.get_node(node_id) {
+        Ok(node) => node,
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+
+    let new_value = match crate::platform::set_checked(&***provider, &node, checked) {
+        Ok(false) => node.value,
+        Ok(true) => provider.refresh(node_id).ok().and_then(|n| n.value),
+        Err(e) => return Response::error(ErrorCode::InvalidAction, e.to_string()),
+    };
+
+    Response::Success {
+        result: ResponseData::ActionResult {
+            success: true,
+            new_value,
+        },
+    }
+}
+
+async fn handle_find_by_name(
+    state: &AppState,
+    name: &str,
+    match_mode: MatchMode,
+    order: TraversalOrder,
+    window_id: Option<&crate::protocol::NodeId>,
+    sort: SortOrder,
+    deadline: Option<Instant>,
+) -> Response {
+    // Get the starting node and traverse the tree
+    let provider = &state.provider;
+    let root = match traversal_root(provider, window_id) {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    let matcher = match NameMatcher::new(name, match_mode) {
+        Ok(matcher) => matcher,
+        Err(message) => {
+            return Response::error(ErrorCode::InvalidRequest, message);
+        }
+    };
+    let mut matches = Vec::new();
+
+    let mut walk = state.walk(order);
+    walk.deadline = deadline;
+    let truncated = walk.run(&***provider, root, |node, _| {
+        if node.name.as_deref().is_some_and(|n| matcher.is_match(n)) {
+            matches.push(node.clone());
+        }
+    });
+
+    if truncated {
+        tracing::warn!("find_by_name: stopped early at the node limit or deadline");
+    }
+
+    if sort == SortOrder::ReadingOrder {
+        sort_by_reading_order(&mut matches);
+    }
+
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes: matches,
+            truncated,
+        },
+    }
+}
+
+/// A `find_by_name` pattern, compiled for its match mode
+enum NameMatcher {
+    Substring(String),
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl NameMatcher {
+    /// Compile `pattern`, describing what is wrong with it if it's invalid
+    fn new(pattern: &str, mode: MatchMode) -> Result<Self, String> {
+        match mode {
+            MatchMode::Substring => Ok(Self::Substring(pattern.to_lowercase())),
+            MatchMode::Glob => glob::Pattern::new(pattern)
+                .map(Self::Glob)
+                .map_err(|e| format!("invalid glob '{}': {}", pattern, e)),
+            MatchMode::Regex => regex::RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(Self::Regex)
+                .map_err(|e| format!("invalid regex '{}': {}", pattern, e)),
+        }
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            Self::Substring(needle) => name.to_lowercase().contains(needle),
+            Self::Glob(pattern) => pattern.matches_with(
+                name,
+                glob::MatchOptions {
+                    case_sensitive: false,
+                    ..Default::default()
+                },
+            ),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+async fn handle_find_by_action(state: &AppState, action_type: &str) -> Response {
+    if !crate::protocol::Action::TYPES.contains(&action_type) {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            format!("Unknown action type: {}", action_type),
+        );
+    }
+
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    // Actions follow from the role, so the search itself reads nothing else
+    let mut matches = Vec::new();
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.fields = NodeFields::NONE;
+    let truncated = walk.run(&***provider, root, |node, _| {
+        if node.actions.iter().any(|a| a.action_type() == action_type) {
+            matches.push(read_remaining_fields(provider, node));
+        }
+    });
+
+    if truncated {
+        tracing::warn!("find_by_action: hit max nodes limit of {}", walk.max_nodes);
+    }
+
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes: matches,
+            truncated,
+        },
+    }
+}
+
+/// Every node offering an action besides focus, with child ids replaced by a count
+async fn handle_query_interactive(state: &AppState, max_nodes: Option<usize>) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+
+    // As in find_by_action, the actions come with the role
+    let mut interactive = Vec::new();
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.fields = NodeFields::NONE;
+    walk.max_nodes = max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+    let truncated = walk.run(&***provider, root, |node, _| {
+        if node
+            .actions
+            .iter()
+            .any(|a| !matches!(a, crate::protocol::Action::Focus))
+        {
+            let mut node = read_remaining_fields(provider, node);
+            count_children(&mut node);
+            interactive.push(node);
+        }
+    });
+
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes: interactive,
+            truncated,
+        },
+    }
+}
+
+/// Re-read a node visited with only some of its attributes.
+///
+/// What the traversal worked out (promoted children, collapsed wrappers) is
+/// kept; if the node can't be read again it is returned as it was.
+fn read_remaining_fields(provider: &Arc<Box<dyn AccessibilityProvider>>, node: &Node) -> Node {
+    match provider.get_node(&node.id) {
+        Ok(full) => Node {
+            children: node.children.clone(),
+            collapsed_from: node.collapsed_from.clone(),
+            ..full
+        },
+        Err(_) => node.clone(),
+    }
+}
+
+/// Stable-sort nodes top to bottom, then left to right, with boundless nodes last
+fn sort_by_reading_order(nodes: &mut [Node]) {
+    nodes.sort_by(|a, b| match (a.bounds, b.bounds) {
+        (Some(a), Some(b)) => a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// How often `wait_for` re-reads the tree
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+async fn handle_wait_for(
+    state: &AppState,
+    role: Option<&str>,
+    name_contains: Option<&str>,
+    timeout_ms: u64,
+    deadline: Option<Instant>,
+) -> Response {
+    let predicate = crate::protocol::FindByPredicate {
+        role: role.map(str::to_string),
+        name_contains: name_contains.map(str::to_string),
+    };
+    // The root would match at once, hiding a misspelled field
+    if predicate.is_empty() {
+        return Response::error(
+            ErrorCode::InvalidRequest,
+            "wait_for needs a role or name_contains",
+        );
+    }
+
+    let provider = &state.provider;
+    let timeout = Instant::now() + state.wait_limit(timeout_ms);
+    let give_up = deadline.map_or(timeout, |deadline| deadline.min(timeout));
+
+    loop {
+        // A missing root may be transient while the app is starting up
+        if let Ok(root) = provider.get_root() {
+            let mut found = None;
+            let mut walk = state.walk(TraversalOrder::DocumentOrder);
+            walk.deadline = deadline;
+            walk.run(&***provider, root, |node, _| {
+                if found.is_none() && predicate.matches(node) {
+                    found = Some(node.clone());
+                }
+            });
+
+            if let Some(node) = found {
+                return Response::Success {
+                    result: ResponseData::Node { node },
+                };
+            }
+        }
+
+        let now = Instant::now();
+        if now >= give_up {
+            let message = if deadline.is_some_and(|deadline| now >= deadline) {
+                "deadline exceeded"
+            } else {
+                "timeout waiting for condition"
+            };
+            return Response::error(ErrorCode::Transient, message.to_string());
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL.min(give_up - now)).await;
+    }
+}
+
+/// Wait for one node's value to change.
+///
+/// Uses the platform's change notification where it has one, which also
+/// catches a change undone before the node is read again. Otherwise polls
+/// every [`WAIT_POLL_INTERVAL`] until the value differs from when the watch
+/// started.
+async fn handle_watch_node(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    timeout_ms: u64,
+) -> Response {
+    let provider = &state.provider;
+    let not_found =
+        |e: anyhow::Error| Response::error(ErrorCode::NotFound, format!("Node not found: {}", e));
+
+    let initial = match provider.refresh(node_id) {
+        Ok(node) => node.value,
+        Err(e) => return not_found(e),
+    };
+    let limit = state.wait_limit(timeout_ms);
+    let timeout = Instant::now() + limit;
+    let timed_out = || Response::error(ErrorCode::Transient, "timeout waiting for value change");
+
+    // Handlers run on the blocking pool, so waiting here stalls no other request
+    match provider.wait_for_value_change(node_id, limit) {
+        Ok(Some(true)) => {
+            state.node_cache.invalidate(node_id);
+            return match provider.refresh(node_id) {
+                Ok(node) => Response::Success {
+                    result: ResponseData::Node { node },
+                },
+                Err(e) => not_found(e),
+            };
+        }
+        Ok(Some(false)) => return timed_out(),
+        Ok(None) => {}
+        Err(e) => tracing::debug!(
+            "Failed to observe {}, polling instead: {}",
+            node_id.as_str(),
+            e
+        ),
+    }
+
+    loop {
+        let now = Instant::now();
+        if now >= timeout {
+            return timed_out();
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL.min(timeout - now)).await;
+
+        match provider.refresh(node_id) {
+            Ok(node) if node.value != initial => {
+                state.node_cache.invalidate(node_id);
+                return Response::Success {
+                    result: ResponseData::Node { node },
+                };
+            }
+            Ok(_) => {}
+            Err(e) => return not_found(e),
+        }
+    }
+}
+
+async fn handle_get_selection(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let container = match provider.get_node(node_id) {
+        Ok(node) => node,
+        Err(e) => {
+            return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e));
+        }
+    };
+
+    // Containers without a selection concept simply have no selected children
+    let nodes = container
+        .selected_children
+        .iter()
+        .filter_map(|child_id| {
+            provider
+                .get_node(child_id)
+                .inspect_err(|e| {
+                    tracing::debug!("Failed to get selected node {:?}: {}", child_id, e)
+                })
+                .ok()
+        })
+        .collect();
+
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes,
+            truncated: false,
+        },
+    }
+}
+
+async fn handle_audit_node(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let role = match provider.get_node_fields(node_id, NodeFields::NONE) {
+        Ok(node) => node.role,
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+
+    match provider.name_sources(node_id) {
+        Ok(sources) => Response::Success {
+            result: ResponseData::NameAudit {
+                audit: crate::audit::audit_name(&role, sources),
+            },
+        },
+        Err(e) => Response::error(
+            ErrorCode::Internal,
+            format!("Failed to read naming attributes: {}", e),
+        ),
+    }
+}
+
+async fn handle_audit_tree(state: &AppState, max_nodes: Option<usize>) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut audit = crate::audit::TreeAudit::new();
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.max_nodes = max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+    let truncated = walk.run(&***provider, root, |node, _| {
+        audit.visit(&***provider, node)
+    });
+
+    Response::Success {
+        result: ResponseData::AuditReport {
+            issues: audit.finish(),
+            truncated,
+        },
+    }
+}
+
+async fn handle_find_in_rect(state: &AppState, rect: &crate::protocol::Rect) -> Response {
+    let provider = &state.provider;
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    // Nodes without bounds can't be placed, so never match
+    let mut inside = Vec::new();
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.fields = NodeFields::BOUNDS;
+    let truncated = walk.run(&***provider, root, |node, _| {
+        if node.bounds.is_some_and(|bounds| rect.contains(&bounds)) {
+            let mut node = read_remaining_fields(provider, node);
+            count_children(&mut node);
+            inside.push(node);
+        }
+    });
+
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes: inside,
+            truncated,
+        },
+    }
+}
+
+async fn handle_describe_node(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let node = match provider.get_node(node_id) {
+        Ok(node) => node,
+        Err(e) => {
+            return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e));
+        }
+    };
+
+    let window = provider
+        .get_window(node_id)
+        .ok()
+        .flatten()
+        .and_then(|window| window.bounds);
+    Response::Success {
+        result: ResponseData::Text {
+            text: crate::describe::describe(&node, window.as_ref()),
+        },
+    }
+}
+
+/// The window holding keyboard focus, or the frontmost window if nothing is focused
+fn focused_window(provider: &Arc<Box<dyn AccessibilityProvider>>) -> Option<Node> {
+    match provider.get_focused_window() {
+        Ok(Some(window)) => Some(window),
+        _ => top_level_windows(provider).into_iter().next(),
+    }
+}
+
+async fn handle_get_window_bounds(
+    state: &AppState,
+    window_id: Option<&crate::protocol::NodeId>,
+) -> Response {
+    let provider = &state.provider;
+    let window = match window_id {
+        Some(window_id) => provider
+            .get_node(window_id)
+            .ok()
+            .filter(|node| node.role == "AXWindow"),
+        None => focused_window(provider),
+    };
+
+    let not_found = |message: String| Response::error(ErrorCode::NotFound, message);
+    let Some(window) = window else {
+        return not_found(match window_id {
+            Some(window_id) => format!("Window not found: {}", window_id.as_str()),
+            None => "No focused window".to_string(),
+        });
+    };
+
+    match window.bounds {
+        Some(bounds) => Response::Success {
+            result: ResponseData::Bounds { bounds },
+        },
+        None => not_found(format!("Window has no bounds: {}", window.id.as_str())),
+    }
+}
+
+/// The application's windows, front to back by `window_level`; windows
+/// without a level keep the platform's order, after the rest
+fn top_level_windows(provider: &Arc<Box<dyn AccessibilityProvider>>) -> Vec<Node> {
+    let Ok(root) = provider.get_root() else {
+        return Vec::new();
+    };
+
+    let mut windows: Vec<_> = root
+        .children
+        .iter()
+        .filter_map(|id| {
+            provider
+                .get_node_fields(id, NodeFields::ALL | NodeFields::WINDOW_LEVEL)
+                .ok()
+        })
+        .filter(|window| window.role == "AXWindow")
+        .collect();
+    windows.sort_by_key(|window| std::cmp::Reverse(window.window_level));
+    windows
+}
+
+/// The window treated as unoccluded when judging visibility: the one the
+/// platform reports as frontmost, else the first window
+fn frontmost_window(provider: &Arc<Box<dyn AccessibilityProvider>>) -> Option<Node> {
+    let mut windows = top_level_windows(provider);
+    let front = windows
+        .iter()
+        .position(|window| window.is_frontmost)
+        .unwrap_or(0);
+    (front < windows.len()).then(|| windows.swap_remove(front))
+}
+
+async fn handle_get_selected_text(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    match provider.get_node_fields(node_id, NodeFields::NONE) {
+        Ok(node) if crate::platform::TEXT_ROLES.contains(&node.role.as_str()) => {}
+        Ok(node) => {
+            return Response::error(
+                ErrorCode::InvalidAction,
+                format!("{} {} has no text to select", node.role, node_id.as_str()),
+            )
+        }
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    }
+
+    match provider.selected_text(node_id) {
+        Ok(text) => Response::Success {
+            result: ResponseData::Text {
+                text: text.unwrap_or_default(),
+            },
+        },
+        Err(e) => Response::error(
+            ErrorCode::Internal,
+            format!("Failed to read selected text: {}", e),
+        ),
+    }
+}
+
+async fn handle_get_siblings(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    if let Err(e) = provider.get_node_fields(node_id, NodeFields::NONE) {
+        return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e));
+    }
+
+    let parent = match provider.get_parent(node_id) {
+        Ok(Some(parent)) => parent,
+        // The root has no siblings
+        Ok(None) => {
+            return Response::Success {
+                result: ResponseData::Nodes {
+                    nodes: Vec::new(),
+                    truncated: false,
+                },
+            }
+        }
+        Err(e) => {
+            return Response::error(ErrorCode::Internal, format!("Failed to get parent: {}", e))
+        }
+    };
+
+    match provider.get_children(&parent.id) {
+        Ok(children) => Response::Success {
+            result: ResponseData::Nodes {
+                nodes: children
+                    .into_iter()
+                    .filter(|child| child.id != *node_id)
+                    .collect(),
+                truncated: false,
+            },
+        },
+        Err(e) => Response::error(
+            ErrorCode::Internal,
+            format!("Failed to get children: {}", e),
+        ),
+    }
+}
+
+async fn handle_hovered_node(state: &AppState) -> Response {
+    let provider = &state.provider;
+    let (x, y) = match provider.cursor_position() {
+        Ok(Some(position)) => position,
+        Ok(None) => {
+            return Response::error(
+                ErrorCode::InvalidRequest,
+                "this platform doesn't report the cursor position",
+            )
+        }
+        Err(e) => {
+            return Response::error(
+                ErrorCode::Internal,
+                format!("Failed to read cursor position: {}", e),
+            )
+        }
+    };
+
+    match provider.node_at_point(x, y) {
+        Ok(Some(node)) => Response::Success {
+            result: ResponseData::Node { node },
+        },
+        Ok(None) => Response::error(
+            ErrorCode::NotFound,
+            format!("Nothing under the cursor at ({}, {})", x, y),
+        ),
+        Err(e) => Response::error(ErrorCode::Internal, format!("Failed to hit-test: {}", e)),
+    }
+}
+
+async fn handle_find_relative(
+    state: &AppState,
+    anchor: &crate::protocol::NodeId,
+    direction: Direction,
+    role: Option<&str>,
+) -> Response {
+    let provider = &state.provider;
+    let anchor_bounds = match provider.get_node_fields(anchor, NodeFields::BOUNDS) {
+        Ok(Node {
+            bounds: Some(bounds),
+            ..
+        }) => bounds,
+        Ok(_) => {
+            return Response::error(
+                ErrorCode::InvalidRequest,
+                format!("Node {} has no bounds", anchor.as_str()),
+            )
+        }
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+    let root = match traversal_root(provider, None) {
+        Ok(root) => root,
+        Err(response) => return response,
+    };
+
+    let mut walk = state.walk(TraversalOrder::DocumentOrder);
+    walk.fields = NodeFields::BOUNDS;
+    let mut nearest: Option<(f64, crate::protocol::NodeId)> = None;
+    walk.run(&***provider, root, |node, _| {
+        if node.id == *anchor || role.is_some_and(|role| node.role != role) {
+            return;
+        }
+        let Some(distance) = node
+            .bounds
+            .and_then(|bounds| relative_distance(&anchor_bounds, &bounds, direction))
+        else {
+            return;
+        };
+        if nearest.as_ref().is_none_or(|(best, _)| distance < *best) {
+            nearest = Some((distance, node.id.clone()));
+        }
+    });
+
+    let Some((_, nearest)) = nearest else {
+        return Response::error(
+            ErrorCode::NotFound,
+            format!(
+                "No node {} {}{}",
+                match direction {
+                    Direction::Left => "left of",
+                    Direction::Right => "right of",
+                    Direction::Above => "above",
+                    Direction::Below => "below",
+                },
+                anchor.as_str(),
+                role.map(|role| format!(" with role {}", role))
+                    .unwrap_or_default()
+            ),
+        );
+    };
+    match provider.get_node(&nearest) {
+        Ok(node) => Response::Success {
+            result: ResponseData::Node { node },
+        },
+        Err(e) => Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    }
+}
+
+/// Distance between the centers of `anchor` and `other` if `other` lies
+/// entirely on the `direction` side of `anchor`, so containers and
+/// overlapping nodes never count as neighbors
+fn relative_distance(
+    anchor: &crate::protocol::Rect,
+    other: &crate::protocol::Rect,
+    direction: Direction,
+) -> Option<f64> {
+    let beside = match direction {
+        Direction::Left => other.x + other.width <= anchor.x,
+        Direction::Right => other.x >= anchor.x + anchor.width,
+        Direction::Above => other.y + other.height <= anchor.y,
+        Direction::Below => other.y >= anchor.y + anchor.height,
+    };
+    let (ax, ay) = anchor.point_at(0.5, 0.5);
+    let (ox, oy) = other.point_at(0.5, 0.5);
+    beside.then(|| (ox - ax).hypot(oy - ay))
+}
+
+/// Whether `node_id` still refers to a live element, asking the provider
+/// rather than reading the node. A stale id is dropped from the node cache,
+/// so a later `get_node` can't serve it either.
+async fn handle_validate_node(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let (valid, reason) = match state.provider.is_alive(node_id) {
+        Ok(true) => (true, None),
+        Ok(false) => (false, Some("the element no longer exists".to_string())),
+        Err(e) if is_app_terminated(&e) => {
+            return Response::error(ErrorCode::Internal, e.to_string())
+        }
+        Err(e) => (false, Some(e.to_string())),
+    };
+    if !valid {
+        state.node_cache.invalidate(node_id);
+    }
+    Response::Success {
+        result: ResponseData::Validity { valid, reason },
+    }
+}
+
+/// Most ancestors a breadcrumb follows, in case a platform's parents loop
+const MAX_BREADCRUMB_DEPTH: usize = 64;
+
+async fn handle_get_breadcrumb(state: &AppState, node_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let node = match provider.get_node_fields(node_id, NodeFields::NAME) {
+        Ok(node) => node,
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+
+    let mut path = vec![node];
+    while path.len() < MAX_BREADCRUMB_DEPTH {
+        let last = &path[path.len() - 1].id;
+        match provider.get_parent(last) {
+            Ok(Some(parent)) => path.push(parent),
+            Ok(None) => break,
+            Err(e) => {
+                return Response::error(ErrorCode::Internal, format!("Failed to get parent: {}", e))
+            }
+        }
+    }
+    path.reverse();
+
+    Response::Success {
+        result: ResponseData::Text {
+            text: crate::describe::breadcrumb(&path),
+        },
+    }
+}
+
+async fn handle_compare_nodes(
+    state: &AppState,
+    a: &crate::protocol::NodeId,
+    b: &crate::protocol::NodeId,
+) -> Response {
+    let provider = &state.provider;
+    let (a, b) = match (provider.get_node(a), provider.get_node(b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(e), _) | (_, Err(e)) => {
+            return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e));
+        }
+    };
+
+    Response::Success {
+        result: ResponseData::NodeDiff {
+            changed_fields: a.changed_fields(&b),
+        },
+    }
+}
+
+/// The application's top-level windows, front to back
+async fn handle_list_windows(state: &AppState) -> Response {
+    Response::Success {
+        result: ResponseData::Nodes {
+            nodes: top_level_windows(&state.provider),
+            truncated: false,
+        },
+    }
+}
+
+async fn handle_get_locale(state: &AppState) -> Response {
+    match state.provider.locale() {
+        Ok((language, region)) => Response::Success {
+            result: ResponseData::Locale { language, region },
+        },
+        Err(e) => Response::error(ErrorCode::Internal, format!("Failed to read locale: {}", e)),
+    }
+}
+
+/// Read part of a text value, through the platform's range reads where it
+/// has them and otherwise by slicing the whole value
+async fn handle_get_text_chunk(
+    state: &AppState,
+    node_id: &crate::protocol::NodeId,
+    offset: usize,
+    length: usize,
+) -> Response {
+    let provider = &state.provider;
+
+    let (text, total_length) = match provider.text_range(node_id, offset, length) {
+        Ok(Some(range)) => range,
+        Ok(None) => match provider.get_node_fields(node_id, NodeFields::VALUE) {
+            Ok(Node {
+                value: Some(value), ..
+            }) => {
+                let units: Vec<u16> = value.encode_utf16().collect();
+                (utf16_chunk(&units, offset, length), units.len())
+            }
+            Ok(_) => {
+                return Response::error(
+                    ErrorCode::InvalidRequest,
+                    format!("Node {} has no text value", node_id.as_str()),
+                )
+            }
+            Err(e) => {
+                return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e))
+            }
+        },
+        Err(e) => return Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    };
+
+    let has_more = offset
+        .min(total_length)
+        .saturating_add(text.encode_utf16().count())
+        < total_length;
+    Response::Success {
+        result: ResponseData::TextChunk {
+            text,
+            total_length,
+            has_more,
+        },
+    }
+}
+
+/// Up to `length` UTF-16 code units of `units` from `offset`, stopping before
+/// a surrogate pair the range would split, as [`AccessibilityProvider::text_range`] does
+fn utf16_chunk(units: &[u16], offset: usize, length: usize) -> String {
+    let start = offset.min(units.len());
+    let mut end = start.saturating_add(length).min(units.len());
+    if end - start > 1 && (0xD800..0xDC00).contains(&units[end - 1]) {
+        end -= 1;
+    }
+    String::from_utf16_lossy(&units[start..end])
+}
+
+/// Bring a window to the front, returning it with its new frontmost state
+async fn handle_focus_window(state: &AppState, window_id: &crate::protocol::NodeId) -> Response {
+    let provider = &state.provider;
+    let not_found = |message: String| Response::error(ErrorCode::NotFound, message);
+    match provider.get_node(window_id) {
+        Ok(window) if window.role == "AXWindow" => {}
+        Ok(node) => {
+            return not_found(format!(
+                "Not a window: {} is {}",
+                window_id.as_str(),
+                node.role
+            ))
+        }
+        Err(e) => return not_found(format!("Window not found: {}", e)),
+    }
+
+    if let Err(e) = provider.focus_window(window_id) {
+        return Response::error(
+            ErrorCode::InvalidAction,
+            format!("Failed to focus window: {}", e),
+        );
+    }
+
+    // Every window's frontmost flag may have changed
+    for window in top_level_windows(provider) {
+        state.node_cache.invalidate(&window.id);
+    }
+
+    match provider.get_node_fields(window_id, NodeFields::ALL | NodeFields::WINDOW_LEVEL) {
+        Ok(node) => Response::Success {
+            result: ResponseData::Node { node },
+        },
+        Err(e) => not_found(format!("Window not found: {}", e)),
+    }
+}
+
+async fn handle_list_targets(state: &AppState) -> Response {
+    let targets = state
+        .targets
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| crate::protocol::TargetInfo {
+            name: name.clone(),
+            primary: i == 0,
+        })
+        .collect();
+
+    Response::Success {
+        result: ResponseData::Targets { targets },
+    }
+}
+
+async fn handle_get_action_log(state: &AppState, limit: Option<usize>) -> Response {
+    Response::Success {
+        result: ResponseData::ActionLog {
+            entries: state.action_log.recent(limit),
+        },
+    }
+}
+
+/// Roles that take keyboard focus when tabbing through a window
+const FOCUSABLE_ROLES: &[&str] = &[
+    "AXButton",
+    "AXCheckBox",
+    "AXRadioButton",
+    "AXTextField",
+    "AXTextArea",
+    "AXSearchField",
+    "AXComboBox",
+    "AXPopUpButton",
+    "AXSlider",
+    "AXLink",
+];
+
+async fn handle_move_focus(state: &AppState, direction: FocusDirection) -> Response {
+    let provider = &state.provider;
+    let root = match provider.get_root() {
+        Ok(r) => r,
+        Err(e) => {
+            return Response::error(ErrorCode::Internal, format!("Failed to get root: {}", e));
+        }
+    };
+
+    let mut focusable = Vec::new();
+    state
+        .walk(TraversalOrder::DocumentOrder)
+        .run(&***provider, root, |node, _| {
+            if FOCUSABLE_ROLES.contains(&node.role.as_str()) && node.enabled != Some(false) {
+                focusable.push(node.id.clone());
+            }
+        });
+
+    let current = provider
+        .get_focused()
+        .inspect_err(|e| tracing::debug!("Failed to get focused node: {}", e))
+        .ok()
+        .flatten()
+        .and_then(|focused| focusable.iter().position(|id| *id == focused.id));
+
+    let Some(target) = focus_target(focusable.len(), current, direction) else {
+        return Response::error(ErrorCode::NotFound, "No focusable element");
+    };
+    let target = &focusable[target];
+
+    if let Err(e) = provider.perform_action(target, &crate::protocol::Action::Focus) {
+        return Response::error(
+            ErrorCode::InvalidAction,
+            format!("Failed to focus {}: {}", target.as_str(), e),
+        );
+    }
+
+    match provider.refresh(target) {
+        Ok(node) => Response::Success {
+            result: ResponseData::Node { node },
+        },
+        Err(e) => Response::error(ErrorCode::NotFound, format!("Node not found: {}", e)),
+    }
+}
+
+/// Index of the control to focus among `count` focusable controls, given
+/// the index of the currently focused one. Next and previous wrap around;
+/// with nothing focused they start from the first and last control.
+fn focus_target(count: usize, current: Option<usize>, direction: FocusDirection) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+
+    Some(match (direction, current) {
+        (FocusDirection::First, _) | (FocusDirection::Next, None) => 0,
+        (FocusDirection::Last, _) | (FocusDirection::Previous, None) => count - 1,
+        (FocusDirection::Next, Some(i)) => (i + 1) % count,
+        (FocusDirection::Previous, Some(i)) => (i + count - 1) % count,
+    })
+}
+
+async fn handle_initialize(
+    protocol_version: Option<String>,
+    _capabilities: Option<serde_json::Value>,
+) -> Response {
+    // Validate protocol version if provided
+    if let Some(version) = protocol_version {
+        if !version.starts_with("1.") {
+            return Response::Error {
+                error: crate::protocol::ErrorInfo {
+                    code: ErrorCode::InvalidRequest,
+                    message: format!("Unsupported protocol version: {}", version),
+                    supported_versions: Some(crate::protocol::supported_versions()),
+                },
+            };
+        }
+    }
+
+    Response::Success {
+        result: ResponseData::Initialize {
+            protocol_version: Message::PROTOCOL_VERSION.to_string(),
+            supported_versions: crate::protocol::supported_versions(),
+            capabilities: crate::protocol::Capabilities {
+                tools: Some(crate::protocol::ToolsCapability {
+                    list_changed: false,
+                }),
+            },
+            server_info: crate::protocol::ServerInfo {
+                name: "accessibility_mcp".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        },
+    }
+}
+
+async fn handle_tools_list() -> Response {
+    use crate::protocol::Tool;
+
+    let order_schema = serde_json::json!({
+        "type": "string",
+        "enum": ["document_order", "depth_first", "breadth_first"],
+        "description": "Traversal order (optional, defaults to document_order)"
+    });
+    let window_schema = serde_json::json!({
+        "type": "string",
+        "description": "Restrict the traversal to this window's subtree (optional)"
+    });
+    let deadline_schema = serde_json::json!({
+        "type": "integer",
+        "description": "Stop after this many milliseconds (at most the server's max_wait_ms, 60000 by default), returning partial results marked truncated (optional)"
+    });
+
+    let tools = vec![
+        Tool {
+            name: "query_tree".to_string(),
+            description: "Query the accessibility tree starting from the root node".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum depth to traverse (optional)"
+                    },
+                    "max_nodes": {
+                        "type": "integer",
+                        "description": "Maximum number of nodes to return (optional)"
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Node fields to include; id is always included (optional). Opt-in fields such as focusable are only read when listed"
+                    },
+                    "order": order_schema.clone(),
+                    "window_id": window_schema.clone(),
+                    "deadline_ms": deadline_schema.clone()
+                }
+            }),
+        },
+        Tool {
+            name: "get_node".to_string(),
+            description: "Get details for a specific accessibility node by ID".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The unique identifier of the node"
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Node fields to include; id is always included (optional). Opt-in fields such as focusable are only read when listed"
+                    },
+                    "include_children": {
+                        "type": "boolean",
+                        "description": "Return child ids (default true); when false only child_count is returned"
+                    },
+                    "describe_actions": {
+                        "type": "boolean",
+                        "description": "Give each action a description of what it does for this node (default false)"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "perform_action".to_string(),
+            description: "Perform an accessibility action on a node".to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The unique identifier of the node"
+                    },
+                    "action": {
+                        "type": "object",
+                        "description": "The action to perform",
+                        "properties": {
+                            "type": {
+                                "type": "string",
+                                "enum": crate::protocol::Action::TYPES
+                            }
+                        },
+                        "required": ["type"]
+                    },
+                    "idempotency_key": {
+                        "type": "string",
+                        "description": "Unique key for this action; retries with the same key \
+                                        within a minute return the first result instead of acting again"
+                    }
+                },
+                "required": ["node_id", "action"]
+            }),
+        },
+        Tool {
+            name: "find_by_name".to_string(),
+            description: "Find accessibility nodes by name (substring, glob, or regex match)"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "The name or partial name to search for, or a pattern"
+                    },
+                    "match_mode": {
+                        "type": "string",
+                        "enum": ["substring", "glob", "regex"],
+                        "description": "How to compare: substring (default), a glob over the whole name (e.g. \"*Button\"), or a regex search (e.g. \"^(OK|Cancel)$\"); all ignore case"
+                    },
+                    "order": order_schema,
+                    "window_id": window_schema,
+                    "sort": {
+                        "type": "string",
+                        "enum": ["traversal", "reading_order"],
+                        "description": "Result order: traversal order (default) or top-to-bottom, left-to-right by bounds"
+                    },
+                    "deadline_ms": deadline_schema,
+                    "include_children": {
+                        "type": "boolean",
+                        "description": "Return child ids (default false); otherwise only child_count is returned"
+                    }
+                },
+                "required": ["name"]
+            }),
+        },
+        Tool {
+            name: "find_by_action".to_string(),
+            description:
+                "Find accessibility nodes that offer a given action (e.g. everything pressable)"
+                    .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "action_type": {
+                        "type": "string",
+                        "enum": crate::protocol::Action::TYPES,
+                        "description": "The action type to look for"
+                    },
+                    "include_children": {
+                        "type": "boolean",
+                        "description": "Return child ids (default false); otherwise only child_count is returned"
+                    }
+                },
+                "required": ["action_type"]
+            }),
+        },
+        Tool {
+            name: "wait_for".to_string(),
+            description: "Wait until a node matching the given role and/or name appears; \
+                          at least one of them is required"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "role": {
+                        "type": "string",
+                        "description": "Exact role to match (optional)"
+                    },
+                    "name_contains": {
+                        "type": "string",
+                        "description": "Case-insensitive substring of the node name (optional)"
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "How long to wait before giving up, in milliseconds (at most the server's max_wait_ms, 60000 by default)"
+                    },
+                    "deadline_ms": {
+                        "type": "integer",
+                        "description": "Hard limit in milliseconds (at most the server's max_wait_ms), also ending a tree scan in progress (optional)"
+                    }
+                },
+                "required": ["timeout_ms"]
+            }),
+        },
+        Tool {
+            name: "watch_node".to_string(),
+            description: "Wait until one node's value changes, e.g. after acting on it, and \
+                          return the node with its new value"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node to watch"
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "description": "How long to wait before giving up, in milliseconds (at most the server's max_wait_ms, 60000 by default)"
+                    }
+                },
+                "required": ["node_id", "timeout_ms"]
+            }),
+        },
+        Tool {
+            name: "count_nodes".to_string(),
+            description: "Count the nodes in the tree (or a subtree) without returning them, \
+                          e.g. to choose a max_depth before query_tree"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Node to count from; defaults to the application root"
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum depth to count (optional)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "render_dot".to_string(),
+            description: "Render the accessibility tree as a GraphViz DOT digraph, \
+                          each node labeled with its role and name. A tree cut short by the \
+                          node limit is labeled as truncated"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum depth to render (optional)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "focus_window".to_string(),
+            description: "Bring a window to the front and make it the application's main window; \
+                          returns the window with is_frontmost set"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "window_id": {
+                        "type": "string",
+                        "description": "The window to focus"
+                    }
+                },
+                "required": ["window_id"]
+            }),
+        },
+        Tool {
+            name: "compare_nodes".to_string(),
+            description: "List the fields (role, name, value, bounds, states, ...) that differ \
+                          between two nodes"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "type": "string",
+                        "description": "First node ID"
+                    },
+                    "b": {
+                        "type": "string",
+                        "description": "Second node ID"
+                    }
+                },
+                "required": ["a", "b"]
+            }),
+        },
+        Tool {
+            name: "get_selected_text".to_string(),
+            description: "Get the text selected in a text field or text area \
+                          (empty if nothing is selected)"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The text element"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "get_siblings".to_string(),
+            description: "Get the other children of a node's parent, such as the other \
+                          options in a radio group or tab bar"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node whose siblings to list"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "export_tree".to_string(),
+            description: "Save the accessibility tree, with app and platform details, as \
+                          a JSON file in the server's export directory, if one is configured"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "File to create, relative to the export directory; \
+                                        an existing file is never replaced"
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum depth to export (default: unlimited)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        Tool {
+            name: "capture_snapshots".to_string(),
+            description: "Read the whole tree several times in a row, to see how a flickering \
+                          or animating UI changes"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "count": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_SNAPSHOTS,
+                        "description": "Number of snapshots to take"
+                    },
+                    "interval_ms": {
+                        "type": "integer",
+                        "description": "Milliseconds between snapshots (at most the server's max_wait_ms)"
+                    }
+                },
+                "required": ["count", "interval_ms"]
+            }),
+        },
+        Tool {
+            name: "query_interactive".to_string(),
+            description: "List the nodes that can be acted on (buttons, fields, sliders, \
+                          checkboxes, links, menu items), skipping static and structural ones"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_nodes": {
+                        "type": "integer",
+                        "description": "Maximum number of nodes to visit (default: 1000)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "list_roles".to_string(),
+            description: "List the roles this platform reports, with each one's normalized \
+                          role (when enabled) and default actions"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "audit_node".to_string(),
+            description: "Report which attributes could name a node (AXTitle, AXTitleUIElement, \
+                          AXDescription, AXValue, AXHelp) and whether it has an accessible name"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node to audit"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "audit_tree".to_string(),
+            description: "Check the whole tree for accessibility problems: unnamed controls, \
+                          unlabelled images and buttons, zero-size controls, and elements \
+                          listed in more than one place"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_nodes": {
+                        "type": "integer",
+                        "description": "Maximum number of nodes to visit (default: 1000)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "find_in_rect".to_string(),
+            description: "Find every node whose bounds lie entirely within a screen rectangle, \
+                          such as a selection box"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "rect": {
+                        "type": "object",
+                        "description": "Region in screen coordinates, origin top-left",
+                        "properties": {
+                            "x": { "type": "number" },
+                            "y": { "type": "number" },
+                            "width": { "type": "number" },
+                            "height": { "type": "number" }
+                        },
+                        "required": ["x", "y", "width", "height"]
+                    }
+                },
+                "required": ["rect"]
+            }),
+        },
+        Tool {
+            name: "get_menu_bar".to_string(),
+            description: "Get the application's menu bar with its menus and menu items, \
+                          which live outside the window tree. Menu items can then be pressed \
+                          with perform_action"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Levels below the menu bar to include (default: 3)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "get_native_actions".to_string(),
+            description: "List the platform's own names for a node's actions (e.g. AXPress, \
+                          AXPick), each with the action type that performs it, or null if it \
+                          can only be performed as a custom action"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node ID to list actions for"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "get_breadcrumb".to_string(),
+            description: "Describe where a node is as the names of its ancestors from the root, \
+                          e.g. 'App > Main > Login > Submit'; unnamed ones show their role"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node ID to locate"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "hovered_node".to_string(),
+            description: "Get the node under the mouse cursor without moving it, e.g. to \
+                          inspect a tooltip or hover state"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "tree_hash".to_string(),
+            description: "Get a single hash of the tree (or a subtree) that stays the same \
+                          while nothing in it changes, to check a cached tree cheaply"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Node to hash from; defaults to the application root"
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "Maximum depth to hash (optional)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "perform_on_matches".to_string(),
+            description: "Perform one action on every node matching a role and/or name, \
+                          e.g. check all the checkboxes; reports which nodes failed, and \
+                          whether the search stopped at the node limit"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "find": {
+                        "type": "object",
+                        "description": "Criteria every affected node must meet; at least one is required",
+                        "properties": {
+                            "role": {
+                                "type": "string",
+                                "description": "Exact role to match (optional)"
+                            },
+                            "name_contains": {
+                                "type": "string",
+                                "description": "Case-insensitive substring of the name (optional)"
+                            }
+                        }
+                    },
+                    "action": {
+                        "type": "object",
+                        "description": "The action to perform on each match, as for perform_action"
+                    }
+                },
+                "required": ["find", "action"]
+            }),
+        },
+        Tool {
+            name: "list_windows".to_string(),
+            description: "List the application's windows front to back, each with a \
+                          window_level (higher is nearer the front), to tell which of \
+                          overlapping windows is on top"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "get_locale".to_string(),
+            description: "Get the language and region codes of the application's locale \
+                          (e.g. \"en\", \"US\"), to know what language names and values \
+                          are in; null where it can't be determined"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "get_text_chunk".to_string(),
+            description: "Read part of a text element's value, to page through text too \
+                          large to fetch whole; reports the total length and whether more \
+                          follows"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The text element to read"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "UTF-16 code unit to start from, 0 for the beginning"
+                    },
+                    "length": {
+                        "type": "integer",
+                        "description": "Most UTF-16 code units to return"
+                    }
+                },
+                "required": ["node_id", "offset", "length"]
+            }),
+        },
+        Tool {
+            name: "tree_stats".to_string(),
+            description: "Summarize the tree's shape: node count, depth, most children of \
+                          any node, and how many nodes have each role, e.g. to choose \
+                          max_depth and max_nodes before query_tree"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "max_nodes": {
+                        "type": "integer",
+                        "description": "Stop after this many nodes (default 1000)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "find_relative".to_string(),
+            description: "Find the nearest node to the left of, right of, above, or below \
+                          another, e.g. the text field to the right of a label"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "anchor": {
+                        "type": "string",
+                        "description": "The node to search beside"
+                    },
+                    "direction": {
+                        "type": "string",
+                        "enum": ["left", "right", "above", "below"],
+                        "description": "Which side of the anchor to search"
+                    },
+                    "role": {
+                        "type": "string",
+                        "description": "Only consider nodes with this role, e.g. AXTextField"
+                    }
+                },
+                "required": ["anchor", "direction"]
+            }),
+        },
+        Tool {
+            name: "validate_node".to_string(),
+            description: "Check whether a node id still refers to a live element, more \
+                          cheaply than get_node, e.g. before acting on an id from an \
+                          earlier query"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node to check"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "get_selection".to_string(),
+            description: "Get the selected children of a list, tab group, or radio group"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The container whose selection to return"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "describe_node".to_string(),
+            description: "Summarize a node in one sentence: role, label, state, value, and \
+                          rough position in its window"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node to describe"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "get_action_log".to_string(),
+            description: "List recently performed actions, oldest first, with each node's value \
+                          before the action where it changed. Empty unless the server enables \
+                          the action log"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "description": "Return only the latest N entries (optional)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "is_visible".to_string(),
+            description:
+                "Get a node with `visible` set: whether it is inside its scroll areas and \
+                          window, and that window is frontmost"
+                    .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "node_id": {
+                        "type": "string",
+                        "description": "The node to check"
+                    }
+                },
+                "required": ["node_id"]
+            }),
+        },
+        Tool {
+            name: "get_window_bounds".to_string(),
+            description: "Get a window's frame in screen coordinates (origin top-left), e.g. \
+                          to map a screenshot onto the screen"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "window_id": {
+                        "type": "string",
+                        "description": "The window to measure; defaults to the focused window"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "list_targets".to_string(),
+            description: "List the applications this server can inspect. Pass a name as a \
+                          message's `target` to address that application instead of the \
+                          primary one"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        Tool {
+            name: "validate_plan".to_string(),
+            description: "Dry-run a sequence of actions: check that each step's node exists \
+                          and supports its action, without performing anything"
+                .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "steps": {
+                        "type": "array",
+                        "description": "The actions to check, in order",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "node_id": { "type": "string" },
+                                "action": {
+                                    "type": "object",
+                                    "description": "An action as taken by perform_action"
+                                }
+                            },
+                            "required": ["node_id", "action"]
+                        }
+                    }
+                },
+                "required": ["steps"]
+            }),
+        },
+        Tool {
+            name: "move_focus".to_string(),
+            description:
+                "Move keyboard focus to the next, previous, first, or last focusable control"
+                    .to_string(),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "direction": {
+                        "type": "string",
+                        "enum": ["next", "previous", "first", "last"],
+                        "description": "Where to move focus; next and previous wrap around"
+                    }
+                },
+                "required": ["direction"]
+            }),
+        },
+    ];
+
+    Response::Success {
+        result: ResponseData::Tools { tools },
+    }
+}
+
+/// The name the sole provider is registered under: this process's PID
+fn default_target_name() -> String {
+    std::process::id().to_string()
+}
+
+/// A registered application's provider, with the state kept per provider
+#[derive(Clone)]
+pub(crate) struct Target {
+    provider: Arc<Box<dyn AccessibilityProvider>>,
+    node_cache: Arc<NodeCache>,
+    action_log: Arc<ActionLog>,
+    /// Becomes true once this application's tree is populated
+    ready: Option<watch::Receiver<bool>>,
+    /// Set once this application is found to have quit; it never comes back
+    terminated: Arc<AtomicBool>,
+}
+
+impl Target {
+    fn has_quit(&self) -> bool {
+        self.terminated.load(Ordering::SeqCst)
+    }
+}
+
+/// Shared state for every transport
+///
+/// `provider`, `node_cache`, `action_log`, `ready`, and `terminated` belong
+/// to the application a request targets; see [`for_target`](Self::for_target).
+#[derive(Clone)]
+pub(crate) struct AppState {
+    pub provider: Arc<Box<dyn AccessibilityProvider>>,
+    pub node_cache: Arc<NodeCache>,
+    pub action_log: Arc<ActionLog>,
+    /// Every registered application by name, the primary one first
+    pub targets: Arc<Vec<(String, Target)>>,
+    pub descend_into_web: bool,
+    pub collapse_single_child_groups: bool,
+    /// Fill in `akit_role` on returned nodes
+    #[cfg(feature = "accesskit")]
+    pub normalize_roles: bool,
+    /// Fill in `visual` on returned nodes
+    #[cfg(feature = "visual")]
+    pub visual: bool,
+    /// Permits for open socket connections, when `Config::max_connections` is set
+    pub connection_limit: Option<Arc<Semaphore>>,
+    /// Signals shutdown once every target app is found to have quit, when
+    /// `Config::shutdown_on_app_exit` is set
+    pub shutdown_on_app_exit: Option<Arc<watch::Sender<bool>>>,
+    /// Set once the targeted app is found to have quit
+    pub terminated: Arc<AtomicBool>,
+    /// Results of recent actions by idempotency key, shared by all targets
+    pub idempotency: Arc<IdempotencyCache>,
+    /// Pretty-print responses where the framing allows it: HTTP and
+    /// `Content-Length` stdio, never newline-delimited streams
+    pub pretty_json: bool,
+    /// Only synthesize input while the app is frontmost
+    pub require_frontmost: bool,
+    /// Becomes true once the targeted app's tree is populated, when
+    /// `Config::init_timeout_ms` is set
+    pub ready: Option<watch::Receiver<bool>>,
+    /// Most nodes one `perform_on_matches` request may act on
+    pub max_batch_actions: usize,
+    /// Longest a request may wait, from `Config::max_wait_ms`
+    pub max_wait: Duration,
+    /// Mask what actions type into secure text fields in the action log
+    pub redact_secure_fields: bool,
+    /// Where `export_tree` may write, when `Config::export_dir` is set
+    pub export_dir: Option<std::path::PathBuf>,
+}
+
+impl AppState {
+    pub fn new(provider: Box<dyn AccessibilityProvider>, config: &Config) -> Self {
+        Self::with_targets(vec![(default_target_name(), provider)], config)
+    }
+
+    /// State serving several named providers; the first is the primary one.
+    ///
+    /// Panics if `providers` is empty.
+    pub fn with_targets(
+        providers: Vec<(String, Box<dyn AccessibilityProvider>)>,
+        config: &Config,
+    ) -> Self {
+        let targets: Vec<_> = providers
+            .into_iter()
+            .map(|(name, provider)| {
+                let target = Target {
+                    provider: Arc::new(RedactingProvider::wrap(
+                        SynthesizingProvider::wrap(provider, config),
+                        config,
+                    )),
+                    node_cache: Arc::new(NodeCache::new(Duration::from_millis(
+                        config.node_cache_ttl_ms,
+                    ))),
+                    action_log: Arc::new(ActionLog::new(config.action_log_size)),
+                    ready: None,
+                    terminated: Arc::new(AtomicBool::new(false)),
+                };
+                (name, target)
+            })
+            .collect();
+        let primary = targets.first().expect("at least one provider").1.clone();
+
+        Self {
+            provider: primary.provider,
+            node_cache: primary.node_cache,
+            action_log: primary.action_log,
+            targets: Arc::new(targets),
+            descend_into_web: config.descend_into_web,
+            collapse_single_child_groups: config.collapse_single_child_groups,
+            #[cfg(feature = "accesskit")]
+            normalize_roles: config.normalize_roles,
+            #[cfg(feature = "visual")]
+            visual: config.visual,
+            connection_limit: config
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            shutdown_on_app_exit: None,
+            terminated: primary.terminated,
+            idempotency: Arc::new(IdempotencyCache::new(IDEMPOTENCY_TTL)),
+            pretty_json: config.pretty_json,
+            require_frontmost: config.require_frontmost,
+            ready: None,
+            max_batch_actions: config.max_batch_actions,
+            max_wait: Duration::from_millis(config.max_wait_ms),
+            redact_secure_fields: config.redact_secure_fields,
+            export_dir: config.export_dir.clone(),
+        }
+    }
+
+    /// Hold each target's requests until its root has a window, or
+    /// `timeout` has passed, polling in the background. Targets are polled
+    /// separately, so one that is slow to start doesn't hold up the others.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn wait_for_window(&mut self, timeout: Duration) {
+        const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+        for (name, target) in Arc::make_mut(&mut self.targets).iter_mut() {
+            let (ready_tx, ready_rx) = watch::channel(false);
+            let provider = target.provider.clone();
+            let name = name.clone();
+            tokio::task::spawn_blocking(move || {
+                let deadline = Instant::now() + timeout;
+                while top_level_windows(&provider).is_empty() {
+                    if Instant::now() >= deadline {
+                        tracing::warn!(
+                            "No window appeared in {} within {:?}; serving anyway",
+                            name,
+                            timeout
+                        );
+                        break;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                let _ = ready_tx.send(true);
+            });
+            target.ready = Some(ready_rx);
+        }
+        self.ready = self.targets[0].1.ready.clone();
+    }
+
+    /// A requested wait of `ms` milliseconds, clamped to `max_wait`
+    pub fn wait_limit(&self, ms: u64) -> Duration {
+        Duration::from_millis(ms).min(self.max_wait)
+    }
+
+    /// This state, serving the provider registered as `name` instead
+    pub fn for_target(&self, name: &str) -> Option<Self> {
+        let (_, target) = self.targets.iter().find(|(n, _)| n == name)?;
+        Some(Self {
+            provider: target.provider.clone(),
+            node_cache: target.node_cache.clone(),
+            action_log: target.action_log.clone(),
+            ready: target.ready.clone(),
+            terminated: target.terminated.clone(),
+            ..self.clone()
+        })
+    }
+
+    /// A tree walk in the given order that honours the configured traversal options
+    pub fn walk(&self, order: TraversalOrder) -> Walk {
+        let mut walk = Walk::new(order);
+        walk.descend_into_web = self.descend_into_web;
+        walk.collapse_single_child_groups = self.collapse_single_child_groups;
+        walk
+    }
+}
+
+/// HTTP handler for MCP requests
+async fn mcp_handler(
+    State(state): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<AxumResponse, AppError> {
+    let message = Message::from_json(&body).map_err(AppError)?;
+    let response = handle_request_blocking(&state, message).await;
+    let status = http_status(&response);
+    if state.pretty_json {
+        let body = match serde_json::to_string_pretty(&response) {
+            Ok(body) => body,
+            // The request was fine; the server failed to encode its reply
+            Err(e) => {
+                let error = Message::error(
+                    ErrorCode::Internal,
+                    format!("Failed to encode response: {}", e),
+                );
+                return Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(error)).into_response());
+            }
+        };
+        let content_type = [(axum::http::header::CONTENT_TYPE, "application/json")];
+        return Ok((status, content_type, body).into_response());
+    }
+    Ok((status, Json(response)).into_response())
+}
+
+/// A self-contained page that fetches the tree from `/mcp` and shows it as an outline
+async fn inspector_page() -> Html<String> {
+    Html(include_str!("inspector.html").replace("{{PROTOCOL_VERSION}}", Message::PROTOCOL_VERSION))
+}
+
+/// HTTP status for a response message; error envelopes map their code to a matching status
+fn http_status(message: &Message) -> StatusCode {
+    let MessageContent::Response(Response::Error { error }) = &message.content else {
+        return StatusCode::OK;
+    };
+
+    match error.code {
+        ErrorCode::NotFound => StatusCode::NOT_FOUND,
+        ErrorCode::PermissionDenied => StatusCode::FORBIDDEN,
+        ErrorCode::InvalidAction | ErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+        ErrorCode::Transient => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorCode::AppTerminated => StatusCode::GONE,
+    }
+}
+
+/// Error wrapper for HTTP responses
+struct AppError(String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> AxumResponse {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(Message::error(ErrorCode::InvalidRequest, self.0)),
+        )
+            .into_response()
+    }
+}
+
+impl<E> From<E> for AppError
+where
+    E: std::error::Error,
+{
+    fn from(err: E) -> Self {
+        AppError(err.to_string())
+    }
+}
+
+/// Run the HTTP-based MCP server
+async fn run_http_server(
+    state: AppState,
+    mut shutdown_rx: watch::Receiver<bool>,
+    addr: SocketAddr,
+    port_tx: oneshot::Sender<u16>,
+) {
+    let app = Router::new()
+        .route("/", get(inspector_page))
+        .route("/mcp", post(mcp_handler))
+        .layer(CorsLayer::permissive())
+        .with_state(state);
+
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!("Failed to bind to {}: {}", addr, e);
+            return;
+        }
+    };
+
+    // Get the actual bound port (important when port 0 is used)
+    let bound_port = listener.local_addr().unwrap().port();
+    tracing::info!(
+        "HTTP server listening on http://{}",
+        SocketAddr::new(addr.ip(), bound_port)
+    );
+
+    // Send the bound port back to the caller
+    let _ = port_tx.send(bound_port);
+
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        let _ = shutdown_rx.changed().await;
+        tracing::info!("HTTP server shutting down");
+    });
+
+    if let Err(e) = server.await {
+        tracing::error!("Server error: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, sample_tree, MockProvider};
+    use crate::protocol::{Action, NodeId};
+
+    fn state(mock: &MockProvider) -> AppState {
+        state_with(mock, &Config::default())
+    }
+
+    fn state_with(mock: &MockProvider, config: &Config) -> AppState {
+        AppState::new(Box::new(mock.clone()), config)
+    }
+
+    fn checkbox(id: &str, value: &str) -> crate::protocol::Node {
+        let mut n = node(id, "AXCheckBox");
+        n.value = Some(value.to_string());
+        n
+    }
+
+    #[tokio::test]
+    async fn set_checked_skips_press_when_already_in_target_state() {
+        let mock = MockProvider::new(vec![checkbox("cb", "1")]);
+        let response = handle_perform_action(
+            &state(&mock),
+            &NodeId::from("cb"),
+            &Action::SetChecked {
+                checked: Some(true),
+            },
+        )
+        .await;
+
+        match response {
+            Response::Success {
+                result: ResponseData::ActionResult { success, new_value },
+            } => {
+                assert!(success);
+                assert_eq!(new_value.as_deref(), Some("1"));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(mock.performed().is_empty(), "no toggle should occur");
+    }
+
+    #[tokio::test]
+    async fn set_checked_presses_when_state_differs() {
+        let mock = MockProvider::new(vec![checkbox("cb", "0")]);
+        let response = handle_perform_action(
+            &state(&mock),
+            &NodeId::from("cb"),
+            &Action::SetChecked {
+                checked: Some(true),
+            },
+        )
+        .await;
+
+        match response {
+            Response::Success {
+                result: ResponseData::ActionResult { new_value, .. },
+            } => assert_eq!(new_value.as_deref(), Some("1")),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert_eq!(mock.performed(), vec![(NodeId::from("cb"), Action::Press)]);
+    }
+
+    #[tokio::test]
+    async fn set_checked_refuses_to_uncheck_a_radio_button() {
+        let mock = MockProvider::new(vec![Node {
+            value: Some("1".to_string()),
+            ..node("small", "AXRadioButton")
+        }]);
+        let uncheck = Action::SetChecked {
+            checked: Some(false),
+        };
+        match handle_perform_action(&state(&mock), &NodeId::from("small"), &uncheck).await {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::InvalidAction);
+                assert!(error.message.contains("radio button"), "{}", error.message);
+            }
+            other => panic!("expected an error, got {:?}", other),
+        }
+        assert!(mock.performed().is_empty());
+
+        // Nodes advertise it without a value, which can't be performed as is
+        let advertised = Action::SetChecked { checked: None };
+        assert_eq!(
+            serde_json::to_value(&advertised).unwrap(),
+            serde_json::json!({ "type": "set_checked" })
+        );
+        match handle_perform_action(&state(&mock), &NodeId::from("small"), &advertised).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn value_description_serialized_only_when_present() {
+        let mut slider = node("s1", "AXSlider");
+        slider.value = Some("0.73".to_string());
+        slider.value_description = Some("73%".to_string());
+        let mock = MockProvider::new(vec![slider, node("b1", "AXButton")]);
+        let state = state(&mock);
+
+        let json = serde_json::to_value(
+            handle_get_node(&state, &NodeId::from("s1"), true, NodeFields::ALL).await,
+        )
+        .unwrap();
+        assert_eq!(json["result"]["node"]["value"], "0.73");
+        assert_eq!(json["result"]["node"]["value_description"], "73%");
+
+        let json = serde_json::to_value(
+            handle_get_node(&state, &NodeId::from("b1"), true, NodeFields::ALL).await,
+        )
+        .unwrap();
+        assert!(json["result"]["node"].get("value_description").is_none());
+    }
+
+    #[tokio::test]
+    async fn find_by_name_defaults_to_document_order() {
+        let mock = crate::platform::mock::sample_tree();
+        let ids = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => nodes
+                .into_iter()
+                .map(|n| n.id.as_str().to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let found = handle_find_by_name(
+            &state(&mock),
+            "a",
+            MatchMode::Substring,
+            TraversalOrder::default(),
+            None,
+            SortOrder::default(),
+            None,
+        )
+        .await;
+        assert_eq!(ids(found), ["a", "a1", "a2"]);
+
+        let found = handle_find_by_name(
+            &state(&mock),
+            "",
+            MatchMode::Substring,
+            TraversalOrder::BreadthFirst,
+            None,
+            SortOrder::default(),
+            None,
+        )
+        .await;
+        assert_eq!(ids(found), ["root", "a", "b", "a1", "a2", "b1"]);
+    }
+
+    #[tokio::test]
+    async fn find_by_name_scoped_to_window() {
+        let parent = |id: &str, role: &str, children: &[&str]| crate::protocol::Node {
+            name: Some(id.to_string()),
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            parent("app", "AXApplication", &["main", "prefs"]),
+            parent("main", "AXWindow", &["main-ok"]),
+            parent("prefs", "AXWindow", &["prefs-ok"]),
+            parent("main-ok", "AXButton", &[]),
+            parent("prefs-ok", "AXButton", &[]),
+        ]);
+        let state = state(&mock);
+
+        let window = NodeId::from("prefs");
+        match handle_find_by_name(
+            &state,
+            "ok",
+            MatchMode::Substring,
+            TraversalOrder::default(),
+            Some(&window),
+            SortOrder::default(),
+            None,
+        )
+        .await
+        {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                assert_eq!(ids, ["prefs-ok"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let missing = NodeId::from("gone");
+        match handle_find_by_name(
+            &state,
+            "ok",
+            MatchMode::Substring,
+            TraversalOrder::default(),
+            Some(&missing),
+            SortOrder::default(),
+            None,
+        )
+        .await
+        {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_for_returns_node_once_it_appears() {
+        let mock = MockProvider::new(vec![node("app", "AXApplication")]);
+        let later = mock.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let mut dialog = node("dialog", "AXSheet");
+            dialog.name = Some("Save Changes".to_string());
+            later.set_node(dialog);
+            let mut app = node("app", "AXApplication");
+            app.children = vec![NodeId::from("dialog")];
+            later.set_node(app);
+        });
+
+        let response =
+            handle_wait_for(&state(&mock), Some("AXSheet"), Some("save"), 2_000, None).await;
+        match response {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => assert_eq!(node.id, NodeId::from("dialog")),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_for_needs_a_predicate() {
+        let mock = MockProvider::new(vec![node("app", "AXApplication")]);
+        match handle_wait_for(&state(&mock), None, None, 2_000, None).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_for_times_out_as_transient() {
+        let mock = MockProvider::new(vec![node("app", "AXApplication")]);
+        let response = handle_wait_for(&state(&mock), Some("AXSheet"), None, 50, None).await;
+        match response {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::Transient);
+                assert_eq!(error.message, "timeout waiting for condition");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_node_returns_the_changed_value() {
+        let field = |value: &str| crate::protocol::Node {
+            value: Some(value.to_string()),
+            ..node("status", "AXStaticText")
+        };
+        let mock = MockProvider::new(vec![field("Saving…")]);
+        let later = mock.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            later.set_node(field("Saved"));
+        });
+
+        let state = state(&mock);
+        let response = handle_watch_node(&state, &NodeId::from("status"), 2_000).await;
+        match response {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => assert_eq!(node.value.as_deref(), Some("Saved")),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // No further change
+        match handle_watch_node(&state, &NodeId::from("status"), 50).await {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::Transient);
+                assert_eq!(error.message, "timeout waiting for value change");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_node_is_notified_of_changes_polling_would_miss() {
+        let field = |value: &str| crate::protocol::Node {
+            value: Some(value.to_string()),
+            ..node("status", "AXStaticText")
+        };
+        let mock = MockProvider::new(vec![field("Idle")]);
+        mock.observe_value_changes();
+        let later = mock.clone();
+        // The wait blocks this thread, as it would a blocking-pool thread
+        let changer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            later.set_node(field("Busy"));
+            later.set_node(field("Idle"));
+        });
+
+        let state = state(&mock);
+        let response = handle_watch_node(&state, &NodeId::from("status"), 2_000).await;
+        changer.join().unwrap();
+        match response {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => assert_eq!(node.value.as_deref(), Some("Idle")),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        // The initial read and the one after the change; no polling
+        assert_eq!(mock.get_node_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn waits_are_capped_at_max_wait() {
+        let mock = sample_tree();
+        let config = Config {
+            max_wait_ms: 50,
+            ..Default::default()
+        };
+        let state = state_with(&mock, &config);
+        let forever = |request: Request| {
+            let state = state.clone();
+            async move {
+                let message = Message::request(request);
+                tokio::time::timeout(Duration::from_secs(5), handle_request(&state, message))
+                    .await
+                    .expect("wait was capped")
+            }
+        };
+
+        let watch = forever(Request::WatchNode {
+            node_id: NodeId::from("a"),
+            timeout_ms: u64::MAX,
+        });
+        let json = serde_json::to_value(watch.await).unwrap();
+        assert_eq!(json["error"]["message"], "timeout waiting for value change");
+
+        let wait = forever(Request::WaitFor {
+            role: Some("AXNothing".to_string()),
+            name_contains: None,
+            timeout_ms: u64::MAX,
+            deadline_ms: Some(u64::MAX),
+        });
+        let json = serde_json::to_value(wait.await).unwrap();
+        assert_eq!(json["error"]["code"], "transient");
+
+        let snapshots = forever(Request::CaptureSnapshots {
+            count: 2,
+            interval_ms: u64::MAX,
+        });
+        let json = serde_json::to_value(snapshots.await).unwrap();
+        assert_eq!(json["result"]["snapshots"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn count_nodes_matches_the_tree_size() {
+        let mock = sample_tree();
+        let state = state(&mock);
+        let count = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Count { count, truncated },
+            } => (count, truncated),
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        assert_eq!(
+            count(handle_count_nodes(&state, None, None).await),
+            (6, false)
+        );
+        assert_eq!(
+            count(handle_count_nodes(&state, None, Some(1)).await),
+            (3, false)
+        );
+        let a = NodeId::from("a");
+        assert_eq!(
+            count(handle_count_nodes(&state, Some(&a), None).await),
+            (3, false)
+        );
+
+        let missing = NodeId::from("missing");
+        assert!(matches!(
+            handle_count_nodes(&state, Some(&missing), None).await,
+            Response::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn disabled_controls_only_accept_focus() {
+        let button = |id: &str, enabled: bool| crate::protocol::Node {
+            enabled: Some(enabled),
+            ..node(id, "AXButton")
+        };
+        let mock = MockProvider::new(vec![button("save", true), button("delete", false)]);
+        let state = state(&mock);
+        let delete = NodeId::from("delete");
+
+        let set_value = Action::SetValue {
+            value: "x".to_string(),
+        };
+        for action in [Action::Press, set_value] {
+            match handle_perform_action(&state, &delete, &action).await {
+                Response::Error { error } => {
+                    assert_eq!(error.code, ErrorCode::InvalidAction);
+                    assert_eq!(error.message, "element is disabled");
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        let response = handle_perform_action(&state, &delete, &Action::Focus).await;
+        assert!(matches!(response, Response::Success { .. }));
+        let response = handle_perform_action(&state, &NodeId::from("save"), &Action::Press).await;
+        assert!(matches!(response, Response::Success { .. }));
+
+        assert_eq!(
+            mock.performed(),
+            vec![
+                (delete, Action::Focus),
+                (NodeId::from("save"), Action::Press),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pressing_disabled_menu_item_is_rejected() {
+        let menu_item = |id: &str, enabled: bool| crate::protocol::Node {
+            enabled: Some(enabled),
+            checked: Some(false),
+            ..node(id, "AXMenuItem")
+        };
+        let mock = MockProvider::new(vec![menu_item("copy", true), menu_item("paste", false)]);
+        let state = state(&mock);
+
+        let response = handle_perform_action(&state, &NodeId::from("paste"), &Action::Press).await;
+        match response {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::InvalidAction);
+                assert_eq!(error.message, "menu item is disabled");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response = handle_perform_action(&state, &NodeId::from("copy"), &Action::Press).await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(
+            mock.performed(),
+            vec![(NodeId::from("copy"), Action::Press)]
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_actions_are_checked_against_native_names() {
+        let mock = MockProvider::new(vec![node("cell", "AXCell"), node("plain", "AXGroup")]);
+        mock.set_native_actions("cell", &["AXPress", "AXShowMenu"]);
+        let state = state(&mock);
+        let custom = |name: &str| Action::Custom {
+            name: name.to_string(),
+        };
+
+        let response =
+            handle_perform_action(&state, &NodeId::from("cell"), &custom("AXPick")).await;
+        match response {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::InvalidAction);
+                assert_eq!(
+                    error.message,
+                    "unsupported custom action 'AXPick'; available: [AXPress, AXShowMenu]"
+                );
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response =
+            handle_perform_action(&state, &NodeId::from("cell"), &custom("AXShowMenu")).await;
+        assert!(matches!(response, Response::Success { .. }));
+
+        // Without a known action list the name is passed through
+        let response =
+            handle_perform_action(&state, &NodeId::from("plain"), &custom("AXPick")).await;
+        assert!(matches!(response, Response::Success { .. }));
+
+        assert_eq!(
+            mock.performed(),
+            vec![
+                (NodeId::from("cell"), custom("AXShowMenu")),
+                (NodeId::from("plain"), custom("AXPick")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_actions_fall_back_to_the_role_when_native_names_fail() {
+        let mock = MockProvider::new(vec![Node {
+            actions: vec![Action::Press, Action::Focus],
+            ..node("button", "AXButton")
+        }]);
+        mock.fail_native_actions("button");
+        let state = state(&mock);
+        let custom = |name: &str| Action::Custom {
+            name: name.to_string(),
+        };
+
+        let response =
+            handle_perform_action(&state, &NodeId::from("button"), &custom("AXPick")).await;
+        match response {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::InvalidAction);
+                assert_eq!(
+                    error.message,
+                    "unsupported custom action 'AXPick'; native actions unavailable, role offers: [press, focus]"
+                );
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let response =
+            handle_perform_action(&state, &NodeId::from("button"), &custom("AXPress")).await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(
+            mock.performed(),
+            vec![(NodeId::from("button"), custom("AXPress"))]
+        );
+    }
+
+    #[tokio::test]
+    async fn press_at_needs_bounds_and_offsets_inside_them() {
+        let bar = Node {
+            bounds: Some(crate::protocol::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 300.0,
+                height: 20.0,
+            }),
+            ..node("bar", "AXGroup")
+        };
+        let mock = MockProvider::new(vec![bar, node("label", "AXStaticText")]);
+        let state = state(&mock);
+        let press_at = |dx, dy| Action::PressAt { dx, dy };
+
+        for (id, action, code) in [
+            ("label", press_at(0.5, 0.5), ErrorCode::InvalidAction),
+            ("bar", press_at(1.5, 0.5), ErrorCode::InvalidRequest),
+            ("bar", press_at(f64::NAN, 0.5), ErrorCode::InvalidRequest),
+        ] {
+            match handle_perform_action(&state, &NodeId::from(id), &action).await {
+                Response::Error { error } => assert_eq!(error.code, code),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+
+        let response =
+            handle_perform_action(&state, &NodeId::from("bar"), &press_at(0.75, 0.5)).await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(
+            mock.performed(),
+            vec![(NodeId::from("bar"), press_at(0.75, 0.5))]
+        );
+    }
+
+    #[tokio::test]
+    async fn actions_are_refused_when_the_element_cant_be_checked() {
+        let button = Node {
+            enabled: Some(false),
+            actions: vec![Action::Focus, Action::Press],
+            ..node("button", "AXButton")
+        };
+        let mock = MockProvider::new(vec![button]);
+        mock.fail_node_fields("button");
+        let state = state(&mock);
+
+        match handle_perform_action(&state, &NodeId::from("button"), &Action::Press).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidAction),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(mock.performed().is_empty());
+
+        // Focus skips the checks, so it doesn't need the read
+        let response = handle_perform_action(&state, &NodeId::from("button"), &Action::Focus).await;
+        assert!(matches!(response, Response::Success { .. }));
+    }
+
+    #[tokio::test]
+    async fn press_at_is_refused_when_the_bounds_cant_be_read() {
+        let bar = Node {
+            bounds: Some(crate::protocol::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 300.0,
+                height: 20.0,
+            }),
+            ..node("bar", "AXGroup")
+        };
+        let mock = MockProvider::new(vec![bar]);
+        mock.fail_node_fields("bar");
+        let state = state(&mock);
+
+        for (dx, dy) in [(5.0, 0.5), (f64::NAN, 0.5), (0.5, 0.5)] {
+            let action = Action::PressAt { dx, dy };
+            let response = handle_perform_action(&state, &NodeId::from("bar"), &action).await;
+            assert!(matches!(response, Response::Error { .. }), "{:?}", response);
+        }
+        assert!(mock.performed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_node_projects_requested_fields() {
+        let mut button = node("b1", "AXButton");
+        button.name = Some("OK".to_string());
+        let mock = MockProvider::new(vec![button]);
+
+        let request = Message::request(Request::GetNode {
+            node_id: NodeId::from("b1"),
+            fields: Some(vec!["id".into(), "role".into(), "name".into()]),
+            include_children: true,
+            describe_actions: false,
+        });
+        let response = handle_request(&state(&mock), request).await;
+        let json = serde_json::to_value(&response).unwrap();
+
+        let node = json["result"]["node"].as_object().expect("node object");
+        let mut keys: Vec<_> = node.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["epoch", "id", "name", "role"]);
+        assert_eq!(node["name"], "OK");
+    }
+
+    #[tokio::test]
+    async fn cached_get_node_hits_provider_once_within_ttl() {
+        let mock = MockProvider::new(vec![node("b1", "AXButton")]);
+        let config = Config {
+            node_cache_ttl_ms: 60_000,
+            ..Default::default()
+        };
+        let state = state_with(&mock, &config);
+
+        for _ in 0..2 {
+            let response =
+                handle_get_node(&state, &NodeId::from("b1"), true, NodeFields::ALL).await;
+            assert!(matches!(response, Response::Success { .. }));
+        }
+        assert_eq!(mock.get_node_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn perform_action_invalidates_cached_node() {
+        let mock = MockProvider::new(vec![checkbox("cb", "0"), checkbox("other", "1")]);
+        let config = Config {
+            node_cache_ttl_ms: 60_000,
+            ..Default::default()
+        };
+        let state = state_with(&mock, &config);
+        let value = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => node.value,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        handle_get_node(&state, &NodeId::from("cb"), true, NodeFields::ALL).await;
+        handle_get_node(&state, &NodeId::from("other"), true, NodeFields::ALL).await;
+        // A side effect of the press, like a radio group deselecting its other buttons
+        mock.set_node(checkbox("other", "0"));
+        handle_perform_action(&state, &NodeId::from("cb"), &Action::Press).await;
+
+        let cb = handle_get_node(&state, &NodeId::from("cb"), true, NodeFields::ALL).await;
+        assert_eq!(value(cb).as_deref(), Some("1"));
+        let other = handle_get_node(&state, &NodeId::from("other"), true, NodeFields::ALL).await;
+        assert_eq!(value(other).as_deref(), Some("0"));
+    }
+
+    fn focus_row() -> MockProvider {
+        let mut root = node("win", "AXWindow");
+        root.children = ["t1", "label", "b1", "b2"].map(NodeId::from).to_vec();
+        MockProvider::new(vec![
+            root,
+            node("t1", "AXTextField"),
+            node("label", "AXStaticText"),
+            node("b1", "AXButton"),
+            node("b2", "AXButton"),
+        ])
+    }
+
+    async fn move_focus(state: &AppState, direction: FocusDirection) -> String {
+        match handle_move_focus(state, direction).await {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => node.id.as_str().to_string(),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn move_focus_next_wraps_around() {
+        let mock = focus_row();
+        let state = state(&mock);
+
+        // Nothing focused yet, so Next starts at the first control
+        assert_eq!(move_focus(&state, FocusDirection::Next).await, "t1");
+        // The static text is skipped
+        assert_eq!(move_focus(&state, FocusDirection::Next).await, "b1");
+        assert_eq!(move_focus(&state, FocusDirection::Next).await, "b2");
+        assert_eq!(move_focus(&state, FocusDirection::Next).await, "t1");
+    }
+
+    #[tokio::test]
+    async fn move_focus_previous_wraps_around() {
+        let mock = focus_row();
+        mock.set_focused(Some("b1"));
+        let state = state(&mock);
+
+        assert_eq!(move_focus(&state, FocusDirection::Previous).await, "t1");
+        assert_eq!(move_focus(&state, FocusDirection::Previous).await, "b2");
+        assert_eq!(move_focus(&state, FocusDirection::First).await, "t1");
+        assert_eq!(move_focus(&state, FocusDirection::Last).await, "b2");
+    }
+
+    #[tokio::test]
+    async fn move_focus_without_focusable_controls_is_not_found() {
+        let mock = MockProvider::new(vec![node("win", "AXWindow")]);
+        match handle_move_focus(&state(&mock), FocusDirection::Next).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    fn page_with_web_view() -> MockProvider {
+        let parent = |id: &str, role: &str, children: &[&str]| crate::protocol::Node {
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        MockProvider::new(vec![
+            parent("win", "AXWindow", &["toolbar", "web"]),
+            parent("toolbar", "AXToolbar", &[]),
+            parent("web", "AXWebArea", &["heading"]),
+            parent("heading", "AXHeading", &[]),
+        ])
+    }
+
+    async fn tree_ids(state: &AppState) -> Vec<(String, bool)> {
+        let response = handle_query_tree(
+            state,
+            None,
+            None,
+            TraversalOrder::default(),
+            None,
+            None,
+            NodeFields::ALL,
+        )
+        .await;
+        match response {
+            Response::Success {
+                result: ResponseData::Tree { nodes, .. },
+            } => nodes
+                .iter()
+                .map(|n| (n.id.as_str().to_string(), n.is_web_content))
+                .collect(),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn web_areas_are_flagged_and_optionally_pruned() {
+        let mock = page_with_web_view();
+
+        let ids = tree_ids(&state(&mock)).await;
+        let expected = [
+            ("win", false),
+            ("toolbar", false),
+            ("web", true),
+            ("heading", false),
+        ];
+        assert_eq!(ids, expected.map(|(id, web)| (id.to_string(), web)));
+
+        let config = Config {
+            descend_into_web: false,
+            ..Default::default()
+        };
+        let ids = tree_ids(&state_with(&mock, &config)).await;
+        let expected = [("win", false), ("toolbar", false), ("web", true)];
+        assert_eq!(ids, expected.map(|(id, web)| (id.to_string(), web)));
+    }
+
+    #[tokio::test]
+    async fn find_by_name_sorts_by_reading_order() {
+        let field = |id: &str, bounds: Option<(f64, f64)>| crate::protocol::Node {
+            name: Some(format!("field {id}")),
+            bounds: bounds.map(|(x, y)| crate::protocol::Rect {
+                x,
+                y,
+                width: 80.0,
+                height: 20.0,
+            }),
+            ..node(id, "AXTextField")
+        };
+        let mut form = node("form", "AXGroup");
+        form.children = ["unplaced", "bottom", "top-right", "top-left"]
+            .map(NodeId::from)
+            .to_vec();
+        let mock = MockProvider::new(vec![
+            form,
+            field("unplaced", None),
+            field("bottom", Some((0.0, 200.0))),
+            field("top-right", Some((300.0, 10.0))),
+            field("top-left", Some((10.0, 10.0))),
+        ]);
+
+        let response = handle_find_by_name(
+            &state(&mock),
+            "field",
+            MatchMode::Substring,
+            TraversalOrder::default(),
+            None,
+            SortOrder::ReadingOrder,
+            None,
+        )
+        .await;
+        match response {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                assert_eq!(ids, ["top-left", "top-right", "bottom", "unplaced"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_node_can_replace_children_with_count() {
+        let mock = sample_tree();
+        let state = state(&mock);
+
+        let json = serde_json::to_value(
+            handle_get_node(&state, &NodeId::from("a"), false, NodeFields::ALL).await,
+        )
+        .unwrap();
+        assert_eq!(json["result"]["node"]["children"], serde_json::json!([]));
+        assert_eq!(json["result"]["node"]["child_count"], 2);
+
+        let json = serde_json::to_value(
+            handle_get_node(&state, &NodeId::from("a"), true, NodeFields::ALL).await,
+        )
+        .unwrap();
+        assert_eq!(
+            json["result"]["node"]["children"],
+            serde_json::json!(["a1", "a2"])
+        );
+        assert!(json["result"]["node"].get("child_count").is_none());
+    }
+
+    #[tokio::test]
+    async fn describe_node_places_node_within_its_window() {
+        let rect = |x, y, width, height| {
+            Some(crate::protocol::Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        };
+        let mock = MockProvider::new(vec![
+            crate::protocol::Node {
+                children: vec![NodeId::from("win")],
+                ..node("app", "AXApplication")
+            },
+            crate::protocol::Node {
+                bounds: rect(1000.0, 0.0, 300.0, 300.0),
+                children: vec![NodeId::from("ok")],
+                ..node("win", "AXWindow")
+            },
+            crate::protocol::Node {
+                name: Some("OK".to_string()),
+                bounds: rect(1010.0, 250.0, 40.0, 20.0),
+                ..node("ok", "AXButton")
+            },
+        ]);
+
+        let request = Message::request(Request::DescribeNode {
+            node_id: NodeId::from("ok"),
+        });
+        let json = serde_json::to_value(handle_request(&state(&mock), request).await).unwrap();
+        assert_eq!(
+            json["result"]["text"],
+            "Button labeled 'OK', located bottom-left."
+        );
+    }
+
+    fn logging_state(mock: &MockProvider) -> AppState {
+        let config = Config {
+            action_log_size: 100,
+            ..Default::default()
+        };
+        state_with(mock, &config)
+    }
+
+    #[tokio::test]
+    async fn action_log_is_off_by_default() {
+        let press = |state: AppState, mock: MockProvider| async move {
+            handle_perform_action(&state, &NodeId::from("cb"), &Action::Press).await;
+            assert_eq!(mock.performed(), vec![(NodeId::from("cb"), Action::Press)]);
+            (state.action_log.recent(None).len(), mock.attribute_reads())
+        };
+
+        let mock = MockProvider::new(vec![checkbox("cb", "0")]);
+        let (logged, reads_without_log) = press(state(&mock), mock).await;
+        assert_eq!(logged, 0);
+
+        // Logging reads the value ahead of the press
+        let mock = MockProvider::new(vec![checkbox("cb", "0")]);
+        let (logged, reads_with_log) = press(logging_state(&mock), mock).await;
+        assert_eq!(logged, 1);
+        assert!(reads_without_log < reads_with_log);
+    }
+
+    #[tokio::test]
+    async fn action_log_records_prior_values() {
+        let field = |id: &str| crate::protocol::Node {
+            value: Some("draft".to_string()),
+            ..node(id, "AXTextField")
+        };
+        let mock = MockProvider::new(vec![field("title")]);
+        let state = logging_state(&mock);
+        let set_value = |value: &str| Action::SetValue {
+            value: value.to_string(),
+        };
+
+        for value in ["first", "second"] {
+            let response =
+                handle_perform_action(&state, &NodeId::from("title"), &set_value(value)).await;
+            assert!(matches!(response, Response::Success { .. }));
+        }
+        // Failed actions are not logged
+        handle_perform_action(&state, &NodeId::from("missing"), &set_value("x")).await;
+
+        let request = Message::request(Request::GetActionLog { limit: None });
+        let MessageContent::Response(Response::Success {
+            result: ResponseData::ActionLog { entries },
+        }) = handle_request(&state, request).await.content
+        else {
+            panic!("expected an action log");
+        };
+        let logged: Vec<_> = entries
+            .iter()
+            .map(|e| (e.node_id.as_str(), &e.action, e.previous_value.as_deref()))
+            .collect();
+        assert_eq!(
+            logged,
+            [
+                ("title", &set_value("first"), Some("draft")),
+                ("title", &set_value("second"), Some("first")),
+            ]
+        );
+        assert!(entries[0].timestamp_ms <= entries[1].timestamp_ms);
+
+        let latest = state.action_log.recent(Some(1));
+        assert_eq!(latest, entries[1..]);
+    }
+
+    #[tokio::test]
+    async fn action_log_masks_what_is_typed_into_secure_fields() {
+        let mock = MockProvider::new(vec![crate::protocol::Node {
+            subrole: Some("AXSecureTextField".to_string()),
+            value: Some("old secret".to_string()),
+            ..node("password", "AXTextField")
+        }]);
+        let state = logging_state(&mock);
+        let set_value = Action::SetValue {
+            value: "new secret".to_string(),
+        };
+        handle_perform_action(&state, &NodeId::from("password"), &set_value).await;
+
+        let entries = state.action_log.recent(None);
+        let logged = serde_json::to_string(&entries).unwrap();
+        assert!(!logged.contains("secret"), "{}", logged);
+        assert_eq!(
+            entries[0].action,
+            Action::SetValue {
+                value: crate::redact::SECURE_VALUE_MASK.to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn action_log_drops_oldest_entries_when_full() {
+        let mock = MockProvider::new(vec![node("b", "AXButton")]);
+        let config = Config {
+            action_log_size: 2,
+            ..Default::default()
+        };
+        let state = state_with(&mock, &config);
+
+        for action in [Action::Focus, Action::Press, Action::ContextMenu] {
+            handle_perform_action(&state, &NodeId::from("b"), &action).await;
+        }
+
+        let actions: Vec<_> = state
+            .action_log
+            .recent(None)
+            .into_iter()
+            .map(|e| e.action)
+            .collect();
+        assert_eq!(actions, [Action::Press, Action::ContextMenu]);
+    }
+
+    #[tokio::test]
+    async fn nodes_scrolled_out_of_their_container_are_not_visible() {
+        let rect = |x, y, width, height| {
+            Some(crate::protocol::Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        };
+        let with = |id: &str, role: &str, bounds, children: &[&str]| crate::protocol::Node {
+            bounds,
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            with("app", "AXApplication", None, &["front", "back"]),
+            with(
+                "front",
+                "AXWindow",
+                rect(0.0, 0.0, 800.0, 600.0),
+                &["scroll"],
+            ),
+            with(
+                "scroll",
+                "AXScrollArea",
+                rect(0.0, 100.0, 800.0, 200.0),
+                &["list"],
+            ),
+            // The list is taller than its scroll area; only the first row fits
+            with(
+                "list",
+                "AXList",
+                rect(0.0, 100.0, 800.0, 1000.0),
+                &["row1", "row9"],
+            ),
+            with("row1", "AXRow", rect(0.0, 120.0, 800.0, 40.0), &[]),
+            with("row9", "AXRow", rect(0.0, 900.0, 800.0, 40.0), &[]),
+            // Sticks out to the right of the frontmost window
+            with(
+                "back",
+                "AXWindow",
+                rect(400.0, 0.0, 800.0, 600.0),
+                &["hidden", "peeking"],
+            ),
+            with("hidden", "AXButton", rect(410.0, 10.0, 50.0, 20.0), &[]),
+            with("peeking", "AXButton", rect(900.0, 10.0, 50.0, 20.0), &[]),
+        ]);
+        let state = state(&mock);
+
+        let visible = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => node.visible,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert_eq!(
+            visible(handle_is_visible(&state, &NodeId::from("row1")).await),
+            Some(true)
+        );
+        assert_eq!(
+            visible(handle_is_visible(&state, &NodeId::from("row9")).await),
+            Some(false)
+        );
+        // Behind the frontmost window, and beside it
+        assert_eq!(
+            visible(handle_is_visible(&state, &NodeId::from("hidden")).await),
+            Some(false)
+        );
+        assert_eq!(
+            visible(handle_is_visible(&state, &NodeId::from("peeking")).await),
+            Some(true)
+        );
+        // No bounds, so no verdict
+        assert_eq!(
+            visible(handle_is_visible(&state, &NodeId::from("app")).await),
+            None
+        );
+
+        // query_tree reports the same
+        let response = handle_query_tree(
+            &state,
+            None,
+            None,
+            TraversalOrder::DocumentOrder,
+            None,
+            None,
+            NodeFields::ALL,
+        )
+        .await;
+        let Response::Success {
+            result: ResponseData::Tree { nodes, .. },
+        } = response
+        else {
+            panic!("unexpected response: {:?}", response);
+        };
+        let row9 = nodes.iter().find(|n| n.id.as_str() == "row9").unwrap();
+        assert_eq!(row9.visible, Some(false));
+
+        assert!(matches!(
+            handle_is_visible(&state, &NodeId::from("missing")).await,
+            Response::Error { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn deadline_returns_partial_results() {
+        let mock = sample_tree();
+        mock.set_delay(Duration::from_millis(20));
+        let state = state(&mock);
+
+        let started = Instant::now();
+        let deadline = deadline_after(&state, Some(50));
+        let response = handle_query_tree(
+            &state,
+            None,
+            None,
+            TraversalOrder::default(),
+            None,
+            deadline,
+            NodeFields::ALL,
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_millis(500));
+        match response {
+            Response::Success {
+                result: ResponseData::Tree { nodes, truncated },
+            } => {
+                assert!(truncated);
+                assert!(
+                    !nodes.is_empty() && nodes.len() < 6,
+                    "got {} nodes",
+                    nodes.len()
+                );
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let deadline = deadline_after(&state, Some(50));
+        let response = handle_wait_for(&state, Some("AXSheet"), None, 5_000, deadline).await;
+        assert!(started.elapsed() < Duration::from_millis(1_000));
+        match response {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::Transient);
+                assert_eq!(error.message, "deadline exceeded");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // Without a deadline the whole tree comes back
+        let response = handle_query_tree(
+            &state,
+            None,
+            None,
+            TraversalOrder::default(),
+            None,
+            None,
+            NodeFields::ALL,
+        )
+        .await;
+        assert!(matches!(
+            response,
+            Response::Success {
+                result: ResponseData::Tree { ref nodes, truncated: false },
+            } if nodes.len() == 6
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_selection_returns_selected_tab() {
+        let mut tabs = node("tabs", "AXTabGroup");
+        tabs.children = ["general", "advanced"].map(NodeId::from).to_vec();
+        tabs.selected_children = vec![NodeId::from("advanced")];
+        let mock = MockProvider::new(vec![
+            tabs,
+            node("general", "AXRadioButton"),
+            node("advanced", "AXRadioButton"),
+        ]);
+        let state = state(&mock);
+
+        let selected = |response| match response {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => nodes
+                .iter()
+                .map(|n: &crate::protocol::Node| n.id.as_str().to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let tab_ids = selected(handle_get_selection(&state, &NodeId::from("tabs")).await);
+        assert_eq!(tab_ids, ["advanced"]);
+
+        // A plain control has no selection
+        let none = selected(handle_get_selection(&state, &NodeId::from("general")).await);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn error_codes_map_to_http_status() {
+        let cases = [
+            (ErrorCode::NotFound, StatusCode::NOT_FOUND),
+            (ErrorCode::PermissionDenied, StatusCode::FORBIDDEN),
+            (ErrorCode::InvalidAction, StatusCode::BAD_REQUEST),
+            (ErrorCode::InvalidRequest, StatusCode::BAD_REQUEST),
+            (ErrorCode::Transient, StatusCode::SERVICE_UNAVAILABLE),
+            (ErrorCode::Internal, StatusCode::INTERNAL_SERVER_ERROR),
+        ];
+        for (code, status) in cases {
+            assert_eq!(
+                http_status(&Message::error(code, "boom")),
+                status,
+                "{code:?}"
+            );
+        }
+
+        let ok = Message::success(ResponseData::Nodes {
+            nodes: Vec::new(),
+            truncated: false,
+        });
+        assert_eq!(http_status(&ok), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn find_by_action_lists_pressable_nodes() {
+        let pressable = |id: &str, role: &str| crate::protocol::Node {
+            actions: vec![Action::Press, Action::Focus],
+            ..node(id, role)
+        };
+        let mut root = node("win", "AXWindow");
+        root.children = ["title", "ok", "remember", "cancel"]
+            .map(NodeId::from)
+            .to_vec();
+        let mock = MockProvider::new(vec![
+            root,
+            node("title", "AXStaticText"),
+            pressable("ok", "AXButton"),
+            pressable("remember", "AXCheckBox"),
+            pressable("cancel", "AXButton"),
+        ]);
+        let state = state(&mock);
+
+        match handle_find_by_action(&state, "press").await {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                assert_eq!(ids, ["ok", "remember", "cancel"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        match handle_find_by_action(&state, "click").await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn epoch_advances_when_tree_is_reread() {
+        let mock = sample_tree();
+        let state = state(&mock);
+        let epochs = |message: Message| -> Vec<u64> {
+            let json = serde_json::to_value(&message).unwrap();
+            match &json["result"] {
+                r if r.get("nodes").is_some() => r["nodes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|n| n["epoch"].as_u64().unwrap())
+                    .collect(),
+                r => vec![r["node"]["epoch"].as_u64().unwrap()],
+            }
+        };
+        let get_a = || {
+            Message::request(Request::GetNode {
+                node_id: NodeId::from("a"),
+                fields: None,
+                include_children: true,
+                describe_actions: false,
+            })
+        };
+        let query = || {
+            Message::request(Request::QueryTree {
+                max_depth: None,
+                max_nodes: None,
+                fields: None,
+                order: TraversalOrder::default(),
+                window_id: None,
+                deadline_ms: None,
+            })
+        };
+
+        // Stable between rebuilds
+        let before = epochs(handle_request(&state, get_a()).await);
+        assert_eq!(epochs(handle_request(&state, get_a()).await), before);
+
+        // Every node from one tree read shares the new, larger epoch
+        let tree = epochs(handle_request(&state, query()).await);
+        assert_eq!(tree.len(), 6);
+        assert!(tree.iter().all(|&e| e == tree[0]));
+        assert!(tree[0] > before[0]);
+
+        assert_eq!(epochs(handle_request(&state, get_a()).await), [tree[0]]);
+        let again = epochs(handle_request(&state, query()).await);
+        assert_eq!(again[0], tree[0] + 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "accesskit")]
+    async fn normalized_roles_are_reported_when_enabled() {
+        let mock = MockProvider::new(vec![node("box", "AXCheckBox")]);
+        let get_box = |fields: Option<Vec<String>>| {
+            Message::request(Request::GetNode {
+                node_id: NodeId::from("box"),
+                fields,
+                include_children: true,
+                describe_actions: false,
+            })
+        };
+        let akit_role = |message: Message| {
+            serde_json::to_value(&message).unwrap()["result"]["node"]["akit_role"].clone()
+        };
+
+        let state = state(&mock);
+        assert!(akit_role(handle_request(&state, get_box(None)).await).is_null());
+
+        let config = Config {
+            normalize_roles: true,
+            ..Config::default()
+        };
+        let state = state_with(&mock, &config);
+        assert_eq!(
+            akit_role(handle_request(&state, get_box(None)).await),
+            "CheckBox"
+        );
+
+        // Projections carry it along with the role they were derived from
+        let fields = Some(vec!["role".to_string()]);
+        assert_eq!(
+            akit_role(handle_request(&state, get_box(fields)).await),
+            "CheckBox"
+        );
+        let fields = Some(vec!["name".to_string()]);
+        assert!(akit_role(handle_request(&state, get_box(fields)).await).is_null());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "visual")]
+    async fn small_targets_are_flagged_when_visual_info_is_enabled() {
+        let button = |id: &str, size: f64| crate::protocol::Node {
+            bounds: Some(crate::protocol::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: size,
+                height: size,
+            }),
+            ..node(id, "AXButton")
+        };
+        let mock = MockProvider::new(vec![
+            button("tiny", 20.0),
+            button("roomy", 44.0),
+            crate::protocol::Node {
+                actions: Vec::new(),
+                ..button("label", 20.0)
+            },
+        ]);
+        let get_fields = |id: &str, fields: Option<Vec<String>>| {
+            Message::request(Request::GetNode {
+                node_id: NodeId::from(id),
+                fields,
+                include_children: false,
+                describe_actions: false,
+            })
+        };
+        let get = |id: &str| get_fields(id, None);
+        let visual = |message: Message| {
+            serde_json::to_value(&message).unwrap()["result"]["node"]["visual"].clone()
+        };
+
+        let state = state(&mock);
+        assert!(visual(handle_request(&state, get("tiny")).await).is_null());
+
+        let config = Config {
+            visual: true,
+            ..Config::default()
+        };
+        let state = state_with(&mock, &config);
+        assert_eq!(
+            visual(handle_request(&state, get("tiny")).await),
+            serde_json::json!({ "min_target_dimension": 20.0, "below_min_target": true })
+        );
+        assert_eq!(
+            visual(handle_request(&state, get("roomy")).await)["below_min_target"],
+            false
+        );
+        // Nothing to hit, so nothing to flag
+        assert_eq!(
+            visual(handle_request(&state, get("label")).await)["below_min_target"],
+            false
+        );
+
+        // Projections get it without keeping the bounds it comes from
+        let projected = handle_request(&state, get_fields("tiny", Some(vec!["visual".into()])));
+        let projected = serde_json::to_value(projected.await).unwrap();
+        assert_eq!(
+            projected["result"]["node"]["visual"]["below_min_target"],
+            true
+        );
+        assert!(projected["result"]["node"].get("bounds").is_none());
+    }
+
+    #[tokio::test]
+    async fn window_bounds_default_to_the_focused_window() {
+        let rect = |x, y, width, height| crate::protocol::Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let mock = MockProvider::new(vec![
+            crate::protocol::Node {
+                children: vec![NodeId::from("main"), NodeId::from("palette")],
+                ..node("app", "AXApplication")
+            },
+            crate::protocol::Node {
+                bounds: Some(rect(0.0, 25.0, 800.0, 600.0)),
+                children: vec![NodeId::from("ok"), NodeId::from("popover")],
+                ..node("main", "AXWindow")
+            },
+            crate::protocol::Node {
+                bounds: Some(rect(900.0, 100.0, 200.0, 300.0)),
+                children: vec![NodeId::from("swatch")],
+                ..node("palette", "AXWindow")
+            },
+            crate::protocol::Node {
+                bounds: Some(rect(10.0, 500.0, 40.0, 20.0)),
+                ..node("ok", "AXButton")
+            },
+            crate::protocol::Node {
+                bounds: Some(rect(950.0, 150.0, 20.0, 20.0)),
+                ..node("swatch", "AXButton")
+            },
+            // Drawn over the palette, but part of the main window
+            crate::protocol::Node {
+                bounds: Some(rect(920.0, 200.0, 100.0, 40.0)),
+                ..node("popover", "AXPopover")
+            },
+        ]);
+        let state = state(&mock);
+        let bounds = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Bounds { bounds },
+            } => bounds,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let palette = handle_get_window_bounds(&state, Some(&NodeId::from("palette"))).await;
+        assert_eq!(bounds(palette), rect(900.0, 100.0, 200.0, 300.0));
+
+        // Nothing focused: the frontmost window
+        let front = handle_get_window_bounds(&state, None).await;
+        assert_eq!(bounds(front), rect(0.0, 25.0, 800.0, 600.0));
+
+        mock.set_focused(Some("swatch"));
+        let focused = handle_get_window_bounds(&state, None).await;
+        assert_eq!(bounds(focused), rect(900.0, 100.0, 200.0, 300.0));
+
+        mock.set_focused(Some("popover"));
+        let focused = handle_get_window_bounds(&state, None).await;
+        assert_eq!(bounds(focused), rect(0.0, 25.0, 800.0, 600.0));
+
+        for window_id in ["missing", "ok"] {
+            match handle_get_window_bounds(&state, Some(&NodeId::from(window_id))).await {
+                Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn projections_read_fewer_attributes() {
+        let mock = sample_tree();
+        let state = state(&mock);
+        let query = |fields: Option<&[&str]>| {
+            Message::request(Request::QueryTree {
+                max_depth: None,
+                max_nodes: None,
+                fields: fields.map(|f| f.iter().map(|s| s.to_string()).collect()),
+                order: TraversalOrder::default(),
+                window_id: None,
+                deadline_ms: None,
+            })
+        };
+        let reads = |before: usize| mock.attribute_reads() - before;
+
+        let before = mock.attribute_reads();
+        handle_request(&state, query(None)).await;
+        let full = reads(before);
+
+        let before = mock.attribute_reads();
+        let response = handle_request(&state, query(Some(&["role"]))).await;
+        let projected = reads(before);
+        assert!(
+            projected < full / 2,
+            "{} reads projected vs {} full",
+            projected,
+            full
+        );
+
+        // Still the whole tree, just with fewer attributes per node
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["result"]["nodes"].as_array().unwrap().len(), 6);
+
+        // A role-only search re-reads just its matches in full
+        mock.set_node(crate::protocol::Node {
+            actions: vec![Action::Press],
+            name: Some("b1".to_string()),
+            ..node("b1", "AXButton")
+        });
+        let response = handle_find_by_action(&state, "press").await;
+        let Response::Success {
+            result: ResponseData::Nodes { nodes, .. },
+        } = response
+        else {
+            panic!("unexpected response: {:?}", response);
+        };
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name.as_deref(), Some("b1"));
+    }
+
+    #[tokio::test]
+    async fn requests_are_routed_to_their_target() {
+        let editor = MockProvider::new(vec![node("editor", "AXApplication")]);
+        let viewer = MockProvider::new(vec![node("viewer", "AXApplication")]);
+        let state = AppState::with_targets(
+            vec![
+                ("editor".to_string(), Box::new(editor.clone())),
+                ("viewer".to_string(), Box::new(viewer.clone())),
+            ],
+            &Config::default(),
+        );
+        let root_id = |message: Message| {
+            let json = serde_json::to_value(&message).unwrap();
+            json["result"]["nodes"][0]["id"].clone()
+        };
+        let query = || {
+            Message::request(Request::QueryTree {
+                max_depth: None,
+                max_nodes: None,
+                fields: None,
+                order: TraversalOrder::default(),
+                window_id: None,
+                deadline_ms: None,
+            })
+        };
+
+        assert_eq!(root_id(handle_request(&state, query()).await), "editor");
+        let to_viewer = query().with_target("viewer");
+        assert_eq!(root_id(handle_request(&state, to_viewer).await), "viewer");
+        let to_editor = query().with_target("editor");
+        assert_eq!(root_id(handle_request(&state, to_editor).await), "editor");
+
+        let unknown = handle_request(&state, query().with_target("nope")).await;
+        let json = serde_json::to_value(&unknown).unwrap();
+        assert_eq!(json["error"]["code"], "not_found");
+
+        // Actions reach only the targeted provider
+        let press = Message::request(Request::PerformAction {
+            node_id: NodeId::from("viewer"),
+            action: Action::Press,
+            idempotency_key: None,
+        })
+        .with_target("viewer");
+        handle_request(&state, press).await;
+        assert_eq!(viewer.performed().len(), 1);
+        assert!(editor.performed().is_empty());
+
+        let listed = handle_request(&state, Message::request(Request::ListTargets)).await;
+        let json = serde_json::to_value(&listed).unwrap();
+        assert_eq!(
+            json["result"]["targets"],
+            serde_json::json!([{ "name": "editor", "primary": true }, { "name": "viewer" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_plan_checks_each_step_without_acting() {
+        let button = crate::protocol::Node {
+            actions: vec![Action::Press, Action::Focus],
+            ..node("save", "AXButton")
+        };
+        let menu_item = crate::protocol::Node {
+            actions: vec![Action::Press],
+            enabled: Some(false),
+            ..node("paste", "AXMenuItem")
+        };
+        let mock = MockProvider::new(vec![
+            crate::protocol::Node {
+                children: vec![
+                    NodeId::from("save"),
+                    NodeId::from("paste"),
+                    NodeId::from("remember"),
+                ],
+                ..node("app", "AXApplication")
+            },
+            button,
+            menu_item,
+            checkbox("remember", "0"),
+        ]);
+        let step = |node_id: &str, action: Action| crate::protocol::ActionStep {
+            node_id: NodeId::from(node_id),
+            action,
+        };
+        let plan = vec![
+            step("save", Action::Press),
+            step(
+                "save",
+                Action::SetValue {
+                    value: "x".to_string(),
+                },
+            ),
+            step("missing", Action::Focus),
+            step("paste", Action::Press),
+            step(
+                "remember",
+                Action::SetChecked {
+                    checked: Some(true),
+                },
+            ),
+            step(
+                "save",
+                Action::SetChecked {
+                    checked: Some(true),
+                },
+            ),
+        ];
+
+        let response = handle_validate_plan(&state(&mock), &plan).await;
+        let Response::Success {
+            result: ResponseData::PlanValidation { valid, steps },
+        } = response
+        else {
+            panic!("unexpected response: {:?}", response);
+        };
+
+        assert!(!valid);
+        let ok: Vec<_> = steps.iter().map(|s| s.ok).collect();
+        assert_eq!(ok, [true, false, false, false, true, false]);
+        assert_eq!(steps[0].reason, None);
+        assert_eq!(
+            steps[1].reason.as_deref(),
+            Some("AXButton save does not support 'set_value'")
+        );
+        assert!(steps[2]
+            .reason
+            .as_deref()
+            .unwrap()
+            .starts_with("Node not found"));
+        assert_eq!(steps[3].reason.as_deref(), Some("menu item is disabled"));
+        assert_eq!(
+            steps[5].reason.as_deref(),
+            Some("Node save has no checked state")
+        );
+
+        // Nothing was performed
+        assert!(mock.performed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn requests_after_the_app_quits_report_app_terminated() {
+        let mock = sample_tree();
+        let mut state = state(&mock);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        state.shutdown_on_app_exit = Some(Arc::new(shutdown_tx));
+        let get_a = || {
+            Message::request(Request::GetNode {
+                node_id: NodeId::from("a"),
+                fields: None,
+                include_children: true,
+                describe_actions: false,
+            })
+        };
+
+        let response = handle_request(&state, get_a()).await;
+        assert_eq!(http_status(&response), StatusCode::OK);
+        assert!(!*shutdown_rx.borrow());
+
+        mock.terminate();
+        let response = handle_request(&state, get_a()).await;
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["error"]["code"], "app_terminated");
+        assert_eq!(
+            json["error"]["message"],
+            "the target application has terminated"
+        );
+        assert_eq!(http_status(&response), StatusCode::GONE);
+
+        let press = Message::request(Request::PerformAction {
+            node_id: NodeId::from("a"),
+            action: Action::Press,
+            idempotency_key: None,
+        });
+        let json = serde_json::to_value(handle_request(&state, press).await).unwrap();
+        assert_eq!(json["error"]["code"], "app_terminated");
+
+        assert!(*shutdown_rx.borrow(), "server should shut itself down");
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_every_target_to_quit() {
+        let editor = MockProvider::new(vec![node("editor", "AXApplication")]);
+        let viewer = MockProvider::new(vec![node("viewer", "AXApplication")]);
+        let mut state = AppState::with_targets(
+            vec![
+                ("editor".to_string(), Box::new(editor.clone())),
+                ("viewer".to_string(), Box::new(viewer.clone())),
+            ],
+            &Config::default(),
+        );
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        state.shutdown_on_app_exit = Some(Arc::new(shutdown_tx));
+        let get_root = |target: &str| {
+            Message::request(Request::GetNode {
+                node_id: NodeId::from(target),
+                fields: None,
+                include_children: true,
+                describe_actions: false,
+            })
+            .with_target(target)
+        };
+        let code =
+            |message: Message| serde_json::to_value(&message).unwrap()["error"]["code"].clone();
+
+        editor.terminate();
+        assert_eq!(
+            code(handle_request(&state, get_root("editor")).await),
+            "app_terminated"
+        );
+        assert!(!*shutdown_rx.borrow(), "the viewer is still running");
+        let response = handle_request(&state, get_root("viewer")).await;
+        assert_eq!(http_status(&response), StatusCode::OK);
+
+        // A malformed request fails the same way whether or not the app is running
+        let bad_scroll = Message::request(Request::PerformAction {
+            node_id: NodeId::from("editor"),
+            action: Action::Scroll {
+                x: f64::NAN,
+                y: 0.0,
+            },
+            idempotency_key: None,
+        })
+        .with_target("editor");
+        assert_eq!(
+            code(handle_request(&state, bad_scroll).await),
+            "invalid_request"
+        );
+
+        viewer.terminate();
+        assert_eq!(
+            code(handle_request(&state, get_root("viewer")).await),
+            "app_terminated"
+        );
+        assert!(*shutdown_rx.borrow(), "server should shut itself down");
+    }
+
+    #[tokio::test]
+    async fn focus_window_moves_the_frontmost_flag() {
+        let window = |id: &str, frontmost: bool| Node {
+            is_frontmost: frontmost,
+            ..node(id, "AXWindow")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("w1"), NodeId::from("w2")],
+                ..node("app", "AXApplication")
+            },
+            window("w1", true),
+            window("w2", false),
+            node("button", "AXButton"),
+        ]);
+        let state = state(&mock);
+        let frontmost = |id: &str| mock.get_node(&NodeId::from(id)).unwrap().is_frontmost;
+
+        match handle_focus_window(&state, &NodeId::from("w2")).await {
+            Response::Success {
+                result: ResponseData::Node { node },
+            } => assert!(node.is_frontmost),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(!frontmost("w1"));
+        assert!(frontmost("w2"));
+        assert_eq!(
+            frontmost_window(&state.provider).map(|window| window.id),
+            Some(NodeId::from("w2"))
+        );
+
+        for id in ["missing", "button"] {
+            match handle_focus_window(&state, &NodeId::from(id)).await {
+                Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn compare_nodes_lists_differing_fields() {
+        let slider = |id: &str, value: &str| Node {
+            name: Some("Volume".to_string()),
+            value: Some(value.to_string()),
+            ..node(id, "AXSlider")
+        };
+        let mock = MockProvider::new(vec![slider("before", "0.3"), slider("after", "0.7")]);
+        let state = state(&mock);
+        let diff = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::NodeDiff { changed_fields },
+            } => changed_fields,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let (before, after) = (NodeId::from("before"), NodeId::from("after"));
+
+        assert_eq!(
+            diff(handle_compare_nodes(&state, &before, &after).await),
+            ["value"]
+        );
+        assert!(diff(handle_compare_nodes(&state, &before, &before).await).is_empty());
+
+        let missing = handle_compare_nodes(&state, &before, &NodeId::from("gone")).await;
+        assert!(matches!(missing, Response::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn selected_text_is_read_from_text_elements() {
+        let mock = MockProvider::new(vec![
+            node("editor", "AXTextArea"),
+            node("empty", "AXTextField"),
+            node("button", "AXButton"),
+        ]);
+        mock.set_selected_text("editor", "quick brown");
+        let state = state(&mock);
+        let text = |response: Response| match response {
+            Response::Success {
+                result: ResponseData::Text { text },
+            } => text,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let editor = handle_get_selected_text(&state, &NodeId::from("editor")).await;
+        assert_eq!(text(editor), "quick brown");
+        let empty = handle_get_selected_text(&state, &NodeId::from("empty")).await;
+        assert_eq!(text(empty), "");
+
+        match handle_get_selected_text(&state, &NodeId::from("button")).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidAction),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "keystrokes")]
+    #[tokio::test]
+    async fn type_text_focuses_before_typing() {
+        let mock = MockProvider::new(vec![Node {
+            value: Some("cat".to_string()),
+            ..node("search", "AXTextField")
+        }]);
+        let state = state(&mock);
+        let search = NodeId::from("search");
+        let type_text = Action::TypeText {
+            text: "alog".to_string(),
+        };
+
+        let response = handle_perform_action(&state, &search, &type_text).await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(
+            mock.performed(),
+            vec![(search.clone(), Action::Focus), (search.clone(), type_text)]
+        );
+        let value = mock.get_node(&search).unwrap().value;
+        assert_eq!(value.as_deref(), Some("catalog"));
+    }
+
+    #[cfg(not(feature = "keystrokes"))]
+    #[tokio::test]
+    async fn type_text_needs_the_keystrokes_feature() {
+        let mock = MockProvider::new(vec![node("search", "AXTextField")]);
+        let state = state(&mock);
+        let type_text = Action::TypeText {
+            text: "alog".to_string(),
+        };
+
+        match handle_perform_action(&state, &NodeId::from("search"), &type_text).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidAction),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(mock.performed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn siblings_exclude_the_node_itself() {
+        let radio = |id: &str| Node {
+            name: Some(id.to_string()),
+            ..node(id, "AXRadioButton")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["small", "medium", "large", "huge"]
+                    .map(NodeId::from)
+                    .to_vec(),
+                ..node("size", "AXRadioGroup")
+            },
+            radio("small"),
+            radio("medium"),
+            radio("large"),
+            radio("huge"),
+        ]);
+        let state = state(&mock);
+        let ids = |response| match response {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            } => nodes
+                .into_iter()
+                .map(|node: Node| node.id.as_str().to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let medium = handle_get_siblings(&state, &NodeId::from("medium")).await;
+        assert_eq!(ids(medium), ["small", "large", "huge"]);
+        let root = handle_get_siblings(&state, &NodeId::from("size")).await;
+        assert!(ids(root).is_empty());
+        assert!(matches!(
+            handle_get_siblings(&state, &NodeId::from("missing")).await,
+            Response::Error { error } if error.code == ErrorCode::NotFound
+        ));
+    }
+
+    #[tokio::test]
+    async fn synthesized_input_waits_for_the_app_to_be_frontmost() {
+        let mock = MockProvider::new(vec![Node {
+            bounds: Some(crate::protocol::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 80.0,
+                height: 20.0,
+            }),
+            actions: vec![Action::Press],
+            ..node("ok", "AXButton")
+        }]);
+        mock.set_frontmost(false);
+        let config = Config {
+            require_frontmost: true,
+            ..Config::default()
+        };
+        let state = AppState::new(Box::new(mock.clone()), &config);
+        let ok = NodeId::from("ok");
+
+        let press_at = Action::PressAt { dx: 0.5, dy: 0.5 };
+        match handle_perform_action(&state, &ok, &press_at).await {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::Transient);
+                assert_eq!(error.message, "target app not frontmost");
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        // Pressing through the accessibility API reaches the app wherever it is
+        let response = handle_perform_action(&state, &ok, &Action::Press).await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(mock.performed(), vec![(ok.clone(), Action::Press)]);
+
+        mock.set_frontmost(true);
+        let response = handle_perform_action(&state, &ok, &press_at).await;
+        assert!(matches!(response, Response::Success { .. }));
+    }
+
+    #[tokio::test]
+    async fn export_tree_writes_a_readable_document() {
+        let mock = sample_tree();
+        let dir = std::env::temp_dir().join(format!("a11y_mcp_export_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config {
+            export_dir: Some(dir.clone()),
+            ..Config::default()
+        };
+        let state = state_with(&mock, &config);
+        let export = |path: &'static str| handle_export_tree(&state, path, None);
+        let error_code = |response| match response {
+            Response::Error { error } => error.code,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let path = dir.join("tree.json");
+        match export("tree.json").await {
+            Response::Success {
+                result:
+                    ResponseData::Exported {
+                        path: written,
+                        node_count,
+                        truncated,
+                    },
+            } => {
+                assert_eq!(written, path.display().to_string());
+                assert_eq!((node_count, truncated), (6, false));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains('\n'), "exports are pretty-printed");
+        let document: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(document["platform"], std::env::consts::OS);
+        assert_eq!(document["app"]["name"], "root");
+        assert!(document["exported_at_ms"].as_u64().unwrap() > 0);
+        let nodes: Vec<Node> = serde_json::from_value(document["nodes"].clone()).unwrap();
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(nodes[0].id, NodeId::from("root"));
+
+        // Existing files are left alone
+        assert_eq!(
+            error_code(export("tree.json").await),
+            ErrorCode::InvalidRequest
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Nothing outside the export directory
+        for escaping in ["../tree.json", "/tmp/tree.json", ""] {
+            assert_eq!(
+                error_code(export(escaping).await),
+                ErrorCode::InvalidRequest,
+                "{}",
+                escaping
+            );
+        }
+        assert_eq!(
+            error_code(export("missing-dir/tree.json").await),
+            ErrorCode::Internal
+        );
+
+        // Off unless configured
+        let state = self::state(&mock);
+        assert_eq!(
+            error_code(handle_export_tree(&state, "tree.json", None).await),
+            ErrorCode::InvalidRequest
+        );
+    }
+
+    #[tokio::test]
+    async fn search_results_count_children_unless_asked_for_them() {
+        let mock = sample_tree();
+        let find = |include_children| {
+            Message::request(Request::FindByName {
+                name: "a".to_string(),
+                match_mode: MatchMode::default(),
+                order: TraversalOrder::default(),
+                window_id: None,
+                sort: SortOrder::default(),
+                deadline_ms: None,
+                include_children,
+            })
+        };
+        let nodes = |message: Message| match message.content {
+            MessageContent::Response(Response::Success {
+                result: ResponseData::Nodes { nodes, .. },
+            }) => nodes,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let lean = nodes(handle_request(&state(&mock), find(false)).await);
+        assert_eq!(lean[0].id, NodeId::from("a"));
+        assert!(lean[0].children.is_empty());
+        assert_eq!(lean[0].child_count, Some(2));
+        assert_eq!(lean[1].child_count, Some(0));
+
+        let full = nodes(handle_request(&state(&mock), find(true)).await);
+        assert_eq!(full[0].children.len(), 2);
+        assert_eq!(full[0].child_count, None);
+    }
+
+    #[tokio::test]
+    async fn snapshots_show_the_tree_changing() {
+        let status = |value: &str| crate::protocol::Node {
+            value: Some(value.to_string()),
+            ..node("status", "AXStaticText")
+        };
+        let mock = MockProvider::new(vec![status("Loading…")]);
+        let later = mock.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            later.set_node(status("Done"));
+        });
+
+        let state = state(&mock);
+        let snapshots = match handle_capture_snapshots(&state, 2, 250).await {
+            Response::Success {
+                result: ResponseData::Snapshots { snapshots },
+            } => snapshots,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let values: Vec<_> = snapshots
+            .iter()
+            .map(|nodes| nodes[0].value.as_deref().unwrap())
+            .collect();
+        assert_eq!(values, ["Loading…", "Done"]);
+
+        match handle_capture_snapshots(&state, 0, 0).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match handle_capture_snapshots(&state, 1_000, 0).await {
+            Response::Success {
+                result: ResponseData::Snapshots { snapshots },
+            } => assert_eq!(snapshots.len(), MAX_SNAPSHOTS),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn query_interactive_skips_static_nodes() {
+        let with_actions = |id: &str, role: &str, actions: Vec<Action>| Node {
+            actions,
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["title", "group", "save", "name", "volume", "terms"]
+                    .map(NodeId::from)
+                    .to_vec(),
+                ..node("win", "AXWindow")
+            },
+            with_actions("title", "AXStaticText", vec![]),
+            node("group", "AXGroup"),
+            with_actions("save", "AXButton", vec![Action::Focus, Action::Press]),
+            with_actions(
+                "name",
+                "AXTextField",
+                vec![
+                    Action::Focus,
+                    Action::SetValue {
+                        value: String::new(),
+                    },
+                ],
+            ),
+            with_actions(
+                "volume",
+                "AXSlider",
+                vec![Action::Increment, Action::Decrement],
+            ),
+            with_actions("terms", "AXCheckBox", vec![Action::Press]),
+        ]);
+
+        match handle_query_interactive(&state(&mock), None).await {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, truncated },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                assert_eq!(ids, ["save", "name", "volume", "terms"]);
+                assert!(!truncated);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_idempotency_key_performs_once() {
+        let mock = MockProvider::new(vec![Node {
+            actions: vec![Action::Press],
+            ..node("ok", "AXButton")
+        }]);
+        let state = state(&mock);
+        let press = |key: &str| {
+            Message::request(Request::PerformAction {
+                node_id: NodeId::from("ok"),
+                action: Action::Press,
+                idempotency_key: Some(key.to_string()),
+            })
+        };
+
+        let first = serde_json::to_value(handle_request(&state, press("order-1")).await).unwrap();
+        let retry = serde_json::to_value(handle_request(&state, press("order-1")).await).unwrap();
+        assert_eq!(first["status"], "success");
+        assert_eq!(retry, first);
+        assert_eq!(mock.performed().len(), 1);
+
+        handle_request(&state, press("order-2")).await;
+        assert_eq!(mock.performed().len(), 2);
+
+        // The same key on a different action is a different request
+        let focus = Message::request(Request::PerformAction {
+            node_id: NodeId::from("ok"),
+            action: Action::Focus,
+            idempotency_key: Some("order-1".to_string()),
+        });
+        handle_request(&state, focus).await;
+        assert_eq!(mock.performed().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn list_roles_reports_the_providers_roles() {
+        let mock = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("ok")],
+                ..node("win", "AXWindow")
+            },
+            Node {
+                actions: vec![Action::Press, Action::Focus],
+                ..node("ok", "AXButton")
+            },
+        ]);
+        let request = Message::request(Request::ListRoles);
+        let roles = match handle_request(&state(&mock), request).await.content {
+            MessageContent::Response(Response::Success {
+                result: ResponseData::RoleMap { roles },
+            }) => roles,
+            other => panic!("unexpected response: {:?}", other),
+        };
+
+        let names: Vec<_> = roles.iter().map(|entry| entry.role.as_str()).collect();
+        assert_eq!(names, ["AXButton", "AXWindow"]);
+        assert_eq!(roles[0].actions, ["press", "focus"]);
+        assert_eq!(roles[0].normalized_role, None);
+    }
+
+    /// An async backend over a mock tree, yielding to the runtime on every call
+    struct AsyncTree(MockProvider);
+
+    #[crate::platform::async_trait]
+    impl AsyncAccessibilityProvider for AsyncTree {
+        async fn get_root(&self) -> anyhow::Result<Node> {
+            tokio::task::yield_now().await;
+            self.0.get_root()
+        }
+
+        async fn get_children(&self, node_id: &NodeId) -> anyhow::Result<Vec<Node>> {
+            tokio::task::yield_now().await;
+            self.0.get_children(node_id)
+        }
+
+        async fn get_node(&self, node_id: &NodeId) -> anyhow::Result<Node> {
+            tokio::task::yield_now().await;
+            self.0.get_node(node_id)
+        }
+
+        async fn perform_action(&self, node_id: &NodeId, action: &Action) -> anyhow::Result<()> {
+            tokio::task::yield_now().await;
+            self.0.perform_action(node_id, action)
+        }
+    }
+
+    #[tokio::test]
+    async fn async_providers_are_served() {
+        let mock = sample_tree();
+        let provider = SyncAdapter::new(Box::new(AsyncTree(mock.clone())));
+        let state = AppState::new(Box::new(provider), &Config::default());
+
+        let query = Message::request(Request::QueryTree {
+            max_depth: None,
+            max_nodes: None,
+            order: TraversalOrder::default(),
+            window_id: None,
+            deadline_ms: None,
+            fields: None,
+        });
+        match handle_request(&state, query).await.content {
+            MessageContent::Response(Response::Success {
+                result: ResponseData::Tree { nodes, .. },
+            }) => assert_eq!(nodes.len(), 6),
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let press = Message::request(Request::PerformAction {
+            node_id: NodeId::from("b1"),
+            action: Action::Press,
+            idempotency_key: None,
+        });
+        handle_request(&state, press).await;
+        assert_eq!(mock.performed(), vec![(NodeId::from("b1"), Action::Press)]);
+    }
+
+    #[tokio::test]
+    async fn audit_explains_a_description_only_name() {
+        let mock = MockProvider::new(vec![Node {
+            description: Some("Close".to_string()),
+            ..node("close", "AXButton")
+        }]);
+
+        match handle_audit_node(&state(&mock), &NodeId::from("close")).await {
+            Response::Success {
+                result: ResponseData::NameAudit { audit },
+            } => {
+                assert!(audit.has_accessible_name);
+                assert_eq!(audit.name_source.as_deref(), Some("AXDescription"));
+                assert!(audit.explanation.contains("description"));
+                assert_eq!(audit.sources.title, None);
+                assert_eq!(audit.sources.description.as_deref(), Some("Close"));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_tree_reports_each_rule() {
+        use crate::audit::rules;
+
+        let rect = |width, height| {
+            Some(crate::protocol::Rect {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height,
+            })
+        };
+        let control = |id: &str, role: &str, name: Option<&str>| Node {
+            name: name.map(str::to_string),
+            actions: vec![Action::Focus, Action::Press],
+            bounds: rect(80.0, 20.0),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["ok", "unnamed", "logo", "hidden", "toolbar", "icon"]
+                    .map(NodeId::from)
+                    .to_vec(),
+                ..node("win", "AXWindow")
+            },
+            // Named by a label elsewhere, which only its name sources show
+            node("icon", "AXImage"),
+            control("ok", "AXButton", Some("OK")),
+            control("unnamed", "AXCheckBox", None),
+            Node {
+                name: Some(" ".to_string()),
+                ..node("logo", "AXImage")
+            },
+            Node {
+                bounds: rect(0.0, 20.0),
+                ..control("hidden", "AXLink", Some("Help"))
+            },
+            Node {
+                children: vec![NodeId::from("ok")],
+                ..node("toolbar", "AXToolbar")
+            },
+        ]);
+        mock.set_title_element("icon", "ok");
+
+        let issues = match handle_audit_tree(&state(&mock), None).await {
+            Response::Success {
+                result: ResponseData::AuditReport { issues, truncated },
+            } => {
+                assert!(!truncated);
+                issues
+            }
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let found: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.node_id.as_str(), issue.rule.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("unnamed", rules::MISSING_NAME),
+                ("logo", rules::EMPTY_LABEL),
+                ("hidden", rules::ZERO_SIZE),
+                ("ok", rules::DUPLICATE_ID),
+            ]
+        );
+        assert_eq!(
+            issues[3].message,
+            "Listed as a child 2 times, under win, toolbar"
+        );
+    }
+
+    #[tokio::test]
+    async fn text_nodes_report_character_and_line_counts() {
+        let mock = MockProvider::new(vec![
+            Node {
+                value: Some("first line\nsecond\n😀".to_string()),
+                ..node("notes", "AXTextArea")
+            },
+            Node {
+                value: Some("1".to_string()),
+                ..node("toggle", "AXCheckBox")
+            },
+        ]);
+        let state = state(&mock);
+
+        let notes =
+            match handle_get_node(&state, &NodeId::from("notes"), true, NodeFields::ALL).await {
+                Response::Success {
+                    result: ResponseData::Node { node },
+                } => node,
+                other => panic!("unexpected response: {:?}", other),
+            };
+        // Counted in UTF-16 units: the emoji is a surrogate pair
+        assert_eq!(notes.char_count, Some(20));
+        assert_eq!(notes.line_count, Some(3));
+
+        // Not a text role
+        let toggle = mock.get_node(&NodeId::from("toggle")).unwrap();
+        assert_eq!((toggle.char_count, toggle.line_count), (None, None));
+
+        // Only counted when asked for, and lines only along with the value
+        let lean = mock
+            .get_node_fields(&NodeId::from("notes"), NodeFields::VALUE)
+            .unwrap();
+        assert_eq!(lean.char_count, None);
+        let length_only = mock
+            .get_node_fields(&NodeId::from("notes"), NodeFields::TEXT_STATS)
+            .unwrap();
+        assert_eq!(
+            (length_only.char_count, length_only.line_count),
+            (Some(20), None)
+        );
+    }
+
+    #[test]
+    fn quiet_suppresses_the_listening_banner() {
+        let mut stderr = Vec::new();
+        announce_to(&mut stderr, true, "http://127.0.0.1:3000");
+        assert!(stderr.is_empty());
+
+        announce_to(&mut stderr, false, "http://127.0.0.1:3000");
+        assert_eq!(
+            String::from_utf8(stderr).unwrap(),
+            "[MCP] listening on http://127.0.0.1:3000\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn find_in_rect_returns_fully_contained_nodes() {
+        let placed = |id: &str, x, y, width, height| Node {
+            bounds: Some(crate::protocol::Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            ..node(id, "AXButton")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["inside", "edge", "straddling", "outside", "unplaced"]
+                    .map(NodeId::from)
+                    .to_vec(),
+                ..placed("win", 0.0, 0.0, 800.0, 600.0)
+            },
+            placed("inside", 120.0, 120.0, 50.0, 20.0),
+            placed("edge", 100.0, 100.0, 200.0, 100.0),
+            placed("straddling", 250.0, 150.0, 100.0, 20.0),
+            placed("outside", 500.0, 500.0, 10.0, 10.0),
+            node("unplaced", "AXGroup"),
+        ]);
+        let rect = crate::protocol::Rect {
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 100.0,
+        };
+
+        match handle_find_in_rect(&state(&mock), &rect).await {
+            Response::Success {
+                result: ResponseData::Nodes { nodes, truncated },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                assert_eq!(ids, ["inside", "edge"]);
+                assert!(!truncated);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn focusable_is_reported_only_when_asked_for() {
+        let mock = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("label"), NodeId::from("field")],
+                ..node("win", "AXWindow")
+            },
+            node("label", "AXStaticText"),
+            node("field", "AXTextField"),
+        ]);
+        let state = state(&mock);
+        let get = |id: &str, fields: Option<Vec<String>>| {
+            handle_request(
+                &state,
+                Message::request(Request::GetNode {
+                    node_id: NodeId::from(id),
+                    fields,
+                    include_children: true,
+                    describe_actions: false,
+                }),
+            )
+        };
+
+        let fields = Some(vec!["role".to_string(), "focusable".to_string()]);
+        let json = serde_json::to_value(get("label", fields.clone()).await).unwrap();
+        assert_eq!(json["result"]["node"]["focusable"], false);
+        let json = serde_json::to_value(get("field", fields).await).unwrap();
+        assert_eq!(json["result"]["node"]["focusable"], true);
+
+        // Not read by default
+        let json = serde_json::to_value(get("field", None).await).unwrap();
+        assert!(json["result"]["node"].get("focusable").is_none());
+    }
+
+    #[tokio::test]
+    async fn get_menu_bar_returns_menus_and_items() {
+        let with_children = |id: &str, role: &str, children: &[&str]| Node {
+            name: Some(id.to_string()),
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            with_children("app", "AXApplication", &["win", "menubar"]),
+            with_children("win", "AXWindow", &[]),
+            with_children("menubar", "AXMenuBar", &["File", "Edit"]),
+            with_children("File", "AXMenuBarItem", &["file menu"]),
+            with_children("file menu", "AXMenu", &["Open"]),
+            with_children("Open", "AXMenuItem", &["recent"]),
+            with_children("recent", "AXMenu", &[]),
+            with_children("Edit", "AXMenuBarItem", &[]),
+        ]);
+
+        match handle_get_menu_bar(&state(&mock), None).await {
+            Response::Success {
+                result: ResponseData::Tree { nodes, .. },
+            } => {
+                let ids: Vec<_> = nodes.iter().map(|n| n.id.as_str()).collect();
+                // Bounded at the menu items
+                assert_eq!(ids, ["menubar", "File", "file menu", "Open", "Edit"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        let no_menu_bar = sample_tree();
+        match handle_get_menu_bar(&state(&no_menu_bar), None).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_native_actions_classifies_unmapped_ones_as_custom() {
+        let mock = MockProvider::new(vec![node("popup", "AXPopUpButton")]);
+        mock.set_native_actions("popup", &["AXPress", "AXPick"]);
+
+        match handle_get_native_actions(&state(&mock), &NodeId::from("popup")).await {
+            Response::Success {
+                result: ResponseData::NativeActions { actions },
+            } => {
+                let classified: Vec<_> = actions
+                    .iter()
+                    .map(|a| (a.name.as_str(), a.maps_to.as_deref()))
+                    .collect();
+                assert_eq!(classified, [("AXPress", Some("press")), ("AXPick", None)]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn breadcrumb_names_each_ancestor_from_the_root() {
+        let with_children = |id: &str, role: &str, name: Option<&str>, children: &[&str]| Node {
+            name: name.map(str::to_string),
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            with_children("app", "AXApplication", Some("Mail"), &["win"]),
+            with_children("win", "AXWindow", Some("Main"), &["form"]),
+            with_children("form", "AXGroup", Some("Login"), &["wrapper"]),
+            with_children("wrapper", "AXGroup", None, &["submit"]),
+            with_children("submit", "AXButton", Some("Submit"), &[]),
+        ]);
+        let state = state(&mock);
+
+        match handle_get_breadcrumb(&state, &NodeId::from("submit")).await {
+            Response::Success {
+                result: ResponseData::Text { text },
+            } => assert_eq!(text, "Mail > Main > Login > AXGroup > Submit"),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match handle_get_breadcrumb(&state, &NodeId::from("gone")).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::NotFound),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_node_can_describe_actions() {
+        let mock = MockProvider::new(vec![Node {
+            actions: vec![Action::Focus, Action::Increment, Action::Decrement],
+            ..node("volume", "AXSlider")
+        }]);
+        let request = Message::request(Request::GetNode {
+            node_id: NodeId::from("volume"),
+            fields: None,
+            include_children: true,
+            describe_actions: true,
+        });
+        let json = serde_json::to_value(handle_request(&state(&mock), request).await).unwrap();
+
+        let actions = &json["result"]["node"]["actions"];
+        assert_eq!(actions[1]["type"], "increment");
+        assert_eq!(
+            actions[1]["description"],
+            "Move the slider up, increasing its value"
+        );
+        assert_eq!(actions[2]["type"], "decrement");
+        assert_eq!(json["result"]["node"]["role"], "AXSlider");
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_the_first_window() {
+        let mock = MockProvider::new(vec![node("app", "AXApplication")]);
+        let mut state = state(&mock);
+        state.wait_for_window(Duration::from_secs(5));
+
+        // The tree is built a little after the server starts
+        let populate = mock.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(150));
+            populate.set_node(node("win", "AXWindow"));
+            populate.set_node(Node {
+                children: vec![NodeId::from("win")],
+                ..node("app", "AXApplication")
+            });
+        });
+
+        let request = Message::request(Request::QueryTree {
+            max_depth: None,
+            max_nodes: None,
+            fields: None,
+            order: TraversalOrder::default(),
+            window_id: None,
+            deadline_ms: None,
+        });
+        match handle_request(&state, request).await.content {
+            MessageContent::Response(Response::Success {
+                result: ResponseData::Tree { nodes, .. },
+            }) => {
+                let roles: Vec<_> = nodes.iter().map(|n| n.role.as_str()).collect();
+                assert_eq!(roles, ["AXApplication", "AXWindow"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_requests_for_an_unready_target_wait() {
+        let starting = MockProvider::new(vec![node("starting", "AXApplication")]);
+        let running = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("win")],
+                ..node("running", "AXApplication")
+            },
+            node("win", "AXWindow"),
+        ]);
+        let mut state = AppState::with_targets(
+            vec![
+                ("starting".to_string(), Box::new(starting)),
+                ("running".to_string(), Box::new(running)),
+            ],
+            &Config::default(),
+        );
+        state.wait_for_window(Duration::from_secs(1));
+
+        let prompt = |message: Message| {
+            let state = state.clone();
+            async move {
+                tokio::time::timeout(Duration::from_millis(500), handle_request(&state, message))
+                    .await
+                    .expect("request waited for the unready target")
+            }
+        };
+        prompt(Message::request(Request::ToolsList)).await;
+        prompt(Message::request(Request::ListTargets)).await;
+        let root = prompt(Message::request(Request::ListWindows).with_target("running")).await;
+        let json = serde_json::to_value(&root).unwrap();
+        assert_eq!(json["result"]["nodes"][0]["id"], "win");
+
+        let held = tokio::time::timeout(
+            Duration::from_millis(200),
+            handle_request(&state, Message::request(Request::ListWindows)),
+        );
+        assert!(held.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn hovered_node_hit_tests_at_the_cursor() {
+        let rect = |x, y, width, height| {
+            Some(crate::protocol::Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                bounds: rect(0.0, 0.0, 800.0, 600.0),
+                children: vec![NodeId::from("toolbar"), NodeId::from("content")],
+                ..node("win", "AXWindow")
+            },
+            Node {
+                bounds: rect(0.0, 0.0, 800.0, 40.0),
+                children: vec![NodeId::from("save")],
+                ..node("toolbar", "AXToolbar")
+            },
+            Node {
+                bounds: rect(10.0, 5.0, 60.0, 30.0),
+                ..node("save", "AXButton")
+            },
+            Node {
+                bounds: rect(0.0, 40.0, 800.0, 560.0),
+                ..node("content", "AXGroup")
+            },
+        ]);
+        let state = state(&mock);
+        let hovered = || async {
+            match handle_hovered_node(&state).await {
+                Response::Success {
+                    result: ResponseData::Node { node },
+                } => Ok(node.id),
+                Response::Error { error } => Err(error.code),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        // No cursor reported yet
+        assert_eq!(hovered().await, Err(ErrorCode::InvalidRequest));
+
+        mock.set_cursor(30.0, 20.0);
+        assert_eq!(hovered().await, Ok(NodeId::from("save")));
+        mock.set_cursor(300.0, 20.0);
+        assert_eq!(hovered().await, Ok(NodeId::from("toolbar")));
+        mock.set_cursor(300.0, 300.0);
+        assert_eq!(hovered().await, Ok(NodeId::from("content")));
+
+        mock.set_cursor(900.0, 300.0);
+        assert_eq!(hovered().await, Err(ErrorCode::NotFound));
+    }
+
+    #[tokio::test]
+    async fn initialize_lists_supported_versions() {
+        match handle_initialize(Some("2.0".to_string()), None).await {
+            Response::Error { error } => {
+                assert_eq!(error.code, ErrorCode::InvalidRequest);
+                assert_eq!(error.supported_versions, Some(vec!["1.0".to_string()]));
+            }
+            other => panic!("expected an error, got {:?}", other),
+        }
+
+        match handle_initialize(Some("1.0".to_string()), None).await {
+            Response::Success {
+                result:
+                    ResponseData::Initialize {
+                        supported_versions, ..
+                    },
+            } => assert_eq!(supported_versions, vec!["1.0".to_string()]),
+            other => panic!("expected initialize data, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn tree_hash_changes_only_with_the_tree() {
+        let mock = sample_tree();
+        let state = state(&mock);
+        let hash = || async {
+            match handle_tree_hash(&state, None, None).await {
+                Response::Success {
+                    result:
+                        ResponseData::Hash {
+                            hash, node_count, ..
+                        },
+                } => (hash, node_count),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        let (first, node_count) = hash().await;
+        assert_eq!(node_count, 6);
+        assert_eq!(hash().await, (first.clone(), 6));
+
+        mock.set_node(Node {
+            name: Some("renamed".to_string()),
+            ..node("a2", "AXGroup")
+        });
+        let (renamed, _) = hash().await;
+        assert_ne!(renamed, first);
+    }
+
+    #[tokio::test]
+    async fn perform_on_matches_acts_on_each_match() {
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["a", "b", "locked", "ok"].map(NodeId::from).to_vec(),
+                ..node("root", "AXWindow")
+            },
+            checkbox("a", "0"),
+            checkbox("b", "0"),
+            Node {
+                enabled: Some(false),
+                ..checkbox("locked", "0")
+            },
+            node("ok", "AXButton"),
+        ]);
+        let find = crate::protocol::FindByPredicate {
+            role: Some("AXCheckBox".to_string()),
+            ..Default::default()
+        };
+
+        let response = handle_perform_on_matches(&state(&mock), &find, &Action::Press).await;
+        match response {
+            Response::Success {
+                result:
+                    ResponseData::BatchActionResult {
+                        total,
+                        succeeded,
+                        failures,
+                        truncated,
+                    },
+            } => {
+                assert_eq!((total, succeeded), (3, 2));
+                assert!(!truncated);
+                assert_eq!(
+                    failures,
+                    vec![(NodeId::from("locked"), "element is disabled".to_string())]
+                );
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert_eq!(
+            mock.performed(),
+            vec![
+                (NodeId::from("a"), Action::Press),
+                (NodeId::from("b"), Action::Press)
+            ]
+        );
+
+        // Over the cap nothing is touched
+        let config = Config {
+            max_batch_actions: 2,
+            ..Config::default()
+        };
+        let response =
+            handle_perform_on_matches(&state_with(&mock, &config), &find, &Action::Press).await;
+        match response {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        assert_eq!(mock.performed().len(), 2);
+
+        // Without criteria everything would match
+        let everything = crate::protocol::FindByPredicate::default();
+        let response = handle_perform_on_matches(&state(&mock), &everything, &Action::Press).await;
+        match response {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        assert_eq!(mock.performed().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn find_by_name_supports_each_match_mode() {
+        let named = |id: &str, name: &str| Node {
+            name: Some(name.to_string()),
+            ..node(id, "AXButton")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["ok", "cancel", "okay", "help"].map(NodeId::from).to_vec(),
+                ..node("root", "AXWindow")
+            },
+            named("ok", "OK"),
+            named("cancel", "Cancel"),
+            named("okay", "Okay Button"),
+            named("help", "Help Button"),
+        ]);
+        let state = state(&mock);
+        let find = |pattern: &'static str, mode| {
+            let state = &state;
+            async move {
+                match handle_find_by_name(
+                    state,
+                    pattern,
+                    mode,
+                    TraversalOrder::default(),
+                    None,
+                    SortOrder::default(),
+                    None,
+                )
+                .await
+                {
+                    Response::Success {
+                        result: ResponseData::Nodes { nodes, .. },
+                    } => Ok(nodes
+                        .iter()
+                        .map(|n| n.id.as_str().to_string())
+                        .collect::<Vec<_>>()),
+                    Response::Error { error } => Err(error),
+                    other => panic!("unexpected response: {:?}", other),
+                }
+            }
+        };
+
+        assert_eq!(
+            find("ok", MatchMode::Substring).await.unwrap(),
+            ["ok", "okay"]
+        );
+        assert_eq!(
+            find("*button", MatchMode::Glob).await.unwrap(),
+            ["okay", "help"]
+        );
+        assert_eq!(
+            find("^(ok|cancel)$", MatchMode::Regex).await.unwrap(),
+            ["ok", "cancel"]
+        );
+
+        let error = find("(unclosed", MatchMode::Regex).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidRequest);
+        assert!(
+            error.message.starts_with("invalid regex"),
+            "{}",
+            error.message
+        );
+        let error = find("[", MatchMode::Glob).await.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidRequest);
+    }
+
+    #[tokio::test]
+    async fn list_windows_sorts_front_to_back() {
+        let window = |id: &str, level| Node {
+            window_level: Some(level),
+            ..node(id, "AXWindow")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("back"), NodeId::from("front")],
+                ..node("app", "AXApplication")
+            },
+            window("back", 0),
+            window("front", 1),
+        ]);
+        let state = state(&mock);
+        let windows = || async {
+            match handle_list_windows(&state).await {
+                Response::Success {
+                    result: ResponseData::Nodes { nodes, .. },
+                } => nodes
+                    .into_iter()
+                    .map(|n| (n.id.as_str().to_string(), n.window_level.unwrap()))
+                    .collect::<Vec<_>>(),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        assert_eq!(
+            windows().await,
+            [("front".to_string(), 1), ("back".to_string(), 0)]
+        );
+
+        // Raising the back window puts it above the other
+        handle_focus_window(&state, &NodeId::from("back")).await;
+        assert_eq!(
+            windows().await,
+            [("back".to_string(), 2), ("front".to_string(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_locale_reports_nulls_when_unknown() {
+        let mock = sample_tree();
+        let response = handle_request(&state(&mock), Message::request(Request::GetLocale)).await;
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["status"], "success");
+        assert_eq!(
+            value["result"],
+            serde_json::json!({"language": null, "region": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn radio_buttons_link_to_their_group() {
+        let radio = |id: &str, value: &str| Node {
+            name: Some(id.to_string()),
+            value: Some(value.to_string()),
+            ..node(id, "AXRadioButton")
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: vec![NodeId::from("size"), NodeId::from("loose")],
+                ..node("root", "AXWindow")
+            },
+            Node {
+                children: vec![NodeId::from("small"), NodeId::from("large")],
+                ..node("size", "AXRadioGroup")
+            },
+            radio("small", "0"),
+            radio("large", "1"),
+            radio("loose", "0"),
+        ]);
+        let state = state(&mock);
+
+        let response = handle_query_tree(
+            &state,
+            None,
+            None,
+            TraversalOrder::default(),
+            None,
+            None,
+            NodeFields::ALL,
+        )
+        .await;
+        let nodes = match response {
+            Response::Success {
+                result: ResponseData::Tree { nodes, .. },
+            } => nodes,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        let radio = |id: &str| {
+            let node = nodes.iter().find(|n| n.id.as_str() == id).unwrap();
+            (node.radio_group.clone(), node.checked)
+        };
+
+        let group = Some(NodeId::from("size"));
+        assert_eq!(radio("small"), (group.clone(), Some(false)));
+        assert_eq!(radio("large"), (group, Some(true)));
+        // Outside a radio group there is nothing to be exclusive with
+        assert_eq!(radio("loose"), (None, Some(false)));
+    }
+
+    #[tokio::test]
+    async fn get_text_chunk_pages_through_a_long_value() {
+        let text: String = ('a'..='z').cycle().take(2500).collect();
+        let mock = MockProvider::new(vec![
+            Node {
+                value: Some(text.clone()),
+                ..node("doc", "AXTextArea")
+            },
+            node("ok", "AXButton"),
+        ]);
+        let state = state(&mock);
+
+        let mut read = String::new();
+        let mut offset = 0;
+        loop {
+            match handle_get_text_chunk(&state, &NodeId::from("doc"), offset, 1000).await {
+                Response::Success {
+                    result:
+                        ResponseData::TextChunk {
+                            text,
+                            total_length,
+                            has_more,
+                        },
+                } => {
+                    assert_eq!(total_length, 2500);
+                    assert!(text.len() <= 1000);
+                    offset += text.len();
+                    read.push_str(&text);
+                    if !has_more {
+                        break;
+                    }
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        assert_eq!(offset, 2500);
+        assert_eq!(read, text);
+
+        match handle_get_text_chunk(&state, &NodeId::from("ok"), 0, 10).await {
+            Response::Error { error } => assert_eq!(error.code, ErrorCode::InvalidRequest),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_text_chunk_counts_utf16_units_without_splitting_pairs() {
+        // Each "ab😀" is four UTF-16 units, the emoji taking two
+        let text = "ab😀".repeat(5);
+        let mock = MockProvider::new(vec![Node {
+            value: Some(text.clone()),
+            ..node("doc", "AXTextArea")
+        }]);
+        let state = state(&mock);
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        loop {
+            match handle_get_text_chunk(&state, &NodeId::from("doc"), offset, 3).await {
+                Response::Success {
+                    result:
+                        ResponseData::TextChunk {
+                            text,
+                            total_length,
+                            has_more,
+                        },
+                } => {
+                    assert_eq!(total_length, 20);
+                    let units = text.encode_utf16().count();
+                    assert!((1..=3).contains(&units), "{:?}", text);
+                    offset += units;
+                    chunks.push(text);
+                    assert_eq!(has_more, offset < 20);
+                    if !has_more {
+                        break;
+                    }
+                }
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        assert_eq!(&chunks[..3], ["ab", "😀a", "b😀"]);
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[tokio::test]
+    async fn tree_stats_summarize_the_sample_tree() {
+        let mock = sample_tree();
+        mock.set_node(Node {
+            name: Some("b1".to_string()),
+            ..node("b1", "AXButton")
+        });
+        let state = state(&mock);
+
+        match handle_tree_stats(&state, None).await {
+            Response::Success {
+                result:
+                    ResponseData::TreeStats {
+                        node_count,
+                        max_depth,
+                        max_children,
+                        role_histogram,
+                        truncated,
+                    },
+            } => {
+                assert_eq!((node_count, max_depth, max_children), (6, 2, 2));
+                assert_eq!(
+                    role_histogram,
+                    [("AXButton".to_string(), 1), ("AXGroup".to_string(), 5)].into()
+                );
+                assert!(!truncated);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        match handle_tree_stats(&state, Some(4)).await {
+            Response::Success {
+                result:
+                    ResponseData::TreeStats {
+                        node_count,
+                        truncated,
+                        ..
+                    },
+            } => assert_eq!((node_count, truncated), (4, true)),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn find_relative_returns_the_nearest_neighbor_in_each_direction() {
+        // Name:  [ name field  ]
+        // Email: [ email field ]
+        let at = |id: &str, role: &str, x: f64, y: f64| Node {
+            bounds: Some(crate::protocol::Rect {
+                x,
+                y,
+                width: if role == "AXTextField" { 100.0 } else { 50.0 },
+                height: 20.0,
+            }),
+            ..node(id, role)
+        };
+        let mock = MockProvider::new(vec![
+            Node {
+                children: ["name_label", "name_field", "email_label", "email_field"]
+                    .map(NodeId::from)
+                    .to_vec(),
+                ..at("form", "AXGroup", 0.0, 0.0)
+            },
+            at("name_label", "AXStaticText", 0.0, 0.0),
+            at("name_field", "AXTextField", 60.0, 0.0),
+            at("email_label", "AXStaticText", 0.0, 30.0),
+            at("email_field", "AXTextField", 60.0, 30.0),
+        ]);
+        let state = state(&mock);
+        let find = |anchor: &'static str, direction, role: Option<&'static str>| {
+            let state = &state;
+            async move {
+                match handle_find_relative(state, &NodeId::from(anchor), direction, role).await {
+                    Response::Success {
+                        result: ResponseData::Node { node },
+                    } => Ok(node.id.as_str().to_string()),
+                    Response::Error { error } => Err(error.code),
+                    other => panic!("unexpected response: {:?}", other),
+                }
+            }
+        };
+
+        assert_eq!(
+            find("name_label", Direction::Right, None).await,
+            Ok("name_field".to_string())
+        );
+        assert_eq!(
+            find("name_label", Direction::Below, None).await,
+            Ok("email_label".to_string())
+        );
+        assert_eq!(
+            find("email_field", Direction::Left, None).await,
+            Ok("email_label".to_string())
+        );
+        assert_eq!(
+            find("email_field", Direction::Above, None).await,
+            Ok("name_field".to_string())
+        );
+        assert_eq!(
+            find("name_label", Direction::Below, Some("AXTextField")).await,
+            Ok("email_field".to_string())
+        );
+        assert_eq!(
+            find("name_label", Direction::Left, None).await,
+            Err(ErrorCode::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_node_flips_once_the_element_is_gone() {
+        let mock = sample_tree();
+        let state = state(&mock);
+        let validate = || async {
+            match handle_validate_node(&state, &NodeId::from("b1")).await {
+                Response::Success {
+                    result: ResponseData::Validity { valid, reason },
+                } => (valid, reason),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        };
+
+        let reads = mock.attribute_reads();
+        assert_eq!(validate().await, (true, None));
+        // Only the role was read
+        assert_eq!(mock.attribute_reads(), reads + 1);
+
+        // Like macOS, the mock still reads a destroyed element, as `unknown`
+        mock.remove_node("b1");
+        let node = state.provider.get_node(&NodeId::from("b1")).unwrap();
+        assert_eq!(node.role, "unknown");
+        let (valid, reason) = validate().await;
+        assert!(!valid);
+        assert!(reason.is_some());
+    }
+
+    #[tokio::test]
+    async fn non_finite_and_huge_scroll_amounts_are_rejected() {
+        let mock = MockProvider::new(vec![node("list", "AXScrollArea")]);
+        let state = state(&mock);
+        let scroll = |x, y| {
+            let state = &state;
+            async move {
+                match handle_perform_action(state, &NodeId::from("list"), &Action::Scroll { x, y })
+                    .await
+                {
+                    Response::Error { error } => Some(error.code),
+                    Response::Success { .. } => None,
+                }
+            }
+        };
+
+        let calls = mock.get_node_calls();
+        assert_eq!(scroll(f64::NAN, 0.0).await, Some(ErrorCode::InvalidRequest));
+        assert_eq!(
+            scroll(0.0, f64::INFINITY).await,
+            Some(ErrorCode::InvalidRequest)
+        );
+        assert_eq!(
+            scroll(0.0, f64::NEG_INFINITY).await,
+            Some(ErrorCode::InvalidRequest)
+        );
+        assert_eq!(scroll(1e12, 0.0).await, Some(ErrorCode::InvalidRequest));
+        // Refused before the provider saw anything
+        assert_eq!(mock.get_node_calls(), calls);
+        assert!(mock.performed().is_empty());
+
+        assert_eq!(scroll(0.0, -3.0).await, None);
+        assert_eq!(mock.performed().len(), 1);
+
+        // The compact form parses "NaN" too, and meets the same check
+        let action = Action::from_compact("s:NaN,0").unwrap();
+        assert!(matches!(
+            handle_perform_action(&state, &NodeId::from("list"), &action).await,
+            Response::Error { .. }
+        ));
     }
 }
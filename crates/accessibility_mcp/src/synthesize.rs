@@ -0,0 +1,350 @@
+//! Values derived server-side for nodes whose platform value says little,
+//! such as a tab group's selected tab
+
+use crate::config::Config;
+use crate::platform::{AccessibilityProvider, NodeFields};
+use crate::protocol::{Action, NameSources, Node, NodeId, RoleEntry};
+use anyhow::Result;
+use std::time::Duration;
+
+/// Containers whose value is the name of their selected child
+const SELECTION_CONTAINER_ROLES: &[&str] = &["AXTabGroup", "AXList", "AXRadioGroup"];
+
+/// Containers whose selected child may only show in its own value, as a
+/// checked radio button, when `AXSelectedChildren` is empty
+const RADIO_CONTAINER_ROLES: &[&str] = &["AXTabGroup", "AXRadioGroup"];
+
+/// Most children read one by one looking for the checked one; past this the
+/// container is left without a value rather than costing a read per child
+const MAX_SCANNED_CHILDREN: usize = 32;
+
+/// Wraps a provider, filling in `value` for containers and checkboxes.
+///
+/// Sits beneath redaction, so derived values are redacted and sanitized
+/// like any other.
+pub(crate) struct SynthesizingProvider {
+    inner: Box<dyn AccessibilityProvider>,
+}
+
+impl SynthesizingProvider {
+    /// Wrap `provider` if `config` asks for synthesized values, otherwise return it as is
+    pub fn wrap(
+        provider: Box<dyn AccessibilityProvider>,
+        config: &Config,
+    ) -> Box<dyn AccessibilityProvider> {
+        if !config.synthesize_container_values {
+            return provider;
+        }
+        Box::new(Self { inner: provider })
+    }
+
+    /// Fill in `node`'s value, given the attributes its read included
+    fn synthesize(&self, mut node: Node, fields: NodeFields) -> Node {
+        if node.role == "AXCheckBox" {
+            node.value = node.value.map(|value| match value.trim() {
+                "0" => "unchecked".to_string(),
+                "1" => "checked".to_string(),
+                "2" => "mixed".to_string(),
+                _ => value,
+            });
+        } else if node.value.is_none() && SELECTION_CONTAINER_ROLES.contains(&node.role.as_str()) {
+            node.value = self
+                .selected_child_name(&node, fields)
+                .inspect_err(|e| tracing::debug!("Failed to read selected child: {}", e))
+                .ok()
+                .flatten();
+        }
+        node
+    }
+
+    /// Name of the container's selected child. Tab groups and radio groups
+    /// don't always report `AXSelectedChildren`, so failing that, the
+    /// radio button whose value marks it selected, if there are few enough
+    /// children to look through.
+    fn selected_child_name(&self, container: &Node, fields: NodeFields) -> Result<Option<String>> {
+        // Reuse what the container's own read brought back
+        let wanted = NodeFields::SELECTED_CHILDREN | NodeFields::CHILDREN;
+        let reread;
+        let container = if fields.contains(wanted) {
+            container
+        } else {
+            reread = self.inner.get_node_fields(&container.id, wanted)?;
+            &reread
+        };
+        let scannable = RADIO_CONTAINER_ROLES.contains(&container.role.as_str())
+            && container.children.len() <= MAX_SCANNED_CHILDREN;
+        let selected = match container.selected_children.first() {
+            Some(selected) => Some(selected.clone()),
+            None if !scannable => None,
+            None => container
+                .children
+                .iter()
+                .find(|child| {
+                    self.inner
+                        .get_node_fields(child, NodeFields::VALUE)
+                        .is_ok_and(|child| {
+                            child.role == "AXRadioButton"
+                                && crate::platform::parse_checked(child.value.as_deref())
+                                    == Some(true)
+                        })
+                })
+                .cloned(),
+        };
+        match selected {
+            Some(selected) => Ok(self
+                .inner
+                .get_node_fields(&selected, NodeFields::NAME)?
+                .name),
+            None => Ok(None),
+        }
+    }
+
+    /// Synthesize only when the caller asked for the value
+    fn synthesize_fields(&self, node: Node, fields: NodeFields) -> Node {
+        if fields.contains(NodeFields::VALUE) {
+            self.synthesize(node, fields)
+        } else {
+            node
+        }
+    }
+}
+
+impl AccessibilityProvider for SynthesizingProvider {
+    fn get_root(&self) -> Result<Node> {
+        self.inner
+            .get_root()
+            .map(|node| self.synthesize(node, NodeFields::ALL))
+    }
+
+    fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        let children = self.inner.get_children(node_id)?;
+        Ok(children
+            .into_iter()
+            .map(|node| self.synthesize(node, NodeFields::ALL))
+            .collect())
+    }
+
+    fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        self.inner
+            .get_node(node_id)
+            .map(|node| self.synthesize(node, NodeFields::ALL))
+    }
+
+    fn get_node_fields(&self, node_id: &NodeId, fields: NodeFields) -> Result<Node> {
+        self.inner
+            .get_node_fields(node_id, fields)
+            .map(|node| self.synthesize_fields(node, fields))
+    }
+
+    fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        self.inner.perform_action(node_id, action)
+    }
+
+    fn refresh(&self, node_id: &NodeId) -> Result<Node> {
+        self.inner
+            .refresh(node_id)
+            .map(|node| self.synthesize(node, NodeFields::ALL))
+    }
+
+    fn get_parent(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let parent = self.inner.get_parent(node_id)?;
+        Ok(parent.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn get_focused(&self) -> Result<Option<Node>> {
+        let focused = self.inner.get_focused()?;
+        Ok(focused.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn get_window(&self, node_id: &NodeId) -> Result<Option<Node>> {
+        let window = self.inner.get_window(node_id)?;
+        Ok(window.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn get_focused_window(&self) -> Result<Option<Node>> {
+        let window = self.inner.get_focused_window()?;
+        Ok(window.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn get_menu_bar(&self) -> Result<Option<Node>> {
+        let menu_bar = self.inner.get_menu_bar()?;
+        Ok(menu_bar.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn cursor_position(&self) -> Result<Option<(f64, f64)>> {
+        self.inner.cursor_position()
+    }
+
+    fn node_at_point(&self, x: f64, y: f64) -> Result<Option<Node>> {
+        let node = self.inner.node_at_point(x, y)?;
+        Ok(node.map(|node| self.synthesize(node, NodeFields::ALL)))
+    }
+
+    fn is_frontmost_app(&self) -> Result<bool> {
+        self.inner.is_frontmost_app()
+    }
+
+    fn locale(&self) -> Result<(Option<String>, Option<String>)> {
+        self.inner.locale()
+    }
+
+    fn set_keyboard_focus(&self, node_id: &NodeId) -> Result<()> {
+        self.inner.set_keyboard_focus(node_id)
+    }
+
+    fn is_alive(&self, node_id: &NodeId) -> Result<bool> {
+        self.inner.is_alive(node_id)
+    }
+
+    fn text_range(
+        &self,
+        node_id: &NodeId,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<(String, usize)>> {
+        self.inner.text_range(node_id, offset, length)
+    }
+
+    fn selected_text(&self, node_id: &NodeId) -> Result<Option<String>> {
+        self.inner.selected_text(node_id)
+    }
+
+    fn focus_window(&self, window_id: &NodeId) -> Result<()> {
+        self.inner.focus_window(window_id)
+    }
+
+    fn name_sources(&self, node_id: &NodeId) -> Result<NameSources> {
+        self.inner.name_sources(node_id)
+    }
+
+    fn roles(&self) -> Vec<RoleEntry> {
+        self.inner.roles()
+    }
+
+    fn native_action_names(&self, node_id: &NodeId) -> Result<Option<Vec<String>>> {
+        self.inner.native_action_names(node_id)
+    }
+
+    fn wait_for_value_change(&self, node_id: &NodeId, timeout: Duration) -> Result<Option<bool>> {
+        // The platform doesn't announce changes to values derived here
+        let role = self.inner.get_node_fields(node_id, NodeFields::NONE)?.role;
+        if SELECTION_CONTAINER_ROLES.contains(&role.as_str()) {
+            return Ok(None);
+        }
+        self.inner.wait_for_value_change(node_id, timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, MockProvider};
+
+    fn tab_group() -> MockProvider {
+        let tab = |id: &str, selected: bool| Node {
+            name: Some(id.to_string()),
+            value: Some(if selected { "1" } else { "0" }.to_string()),
+            ..node(id, "AXRadioButton")
+        };
+        MockProvider::new(vec![
+            Node {
+                children: ["General", "Advanced"].map(NodeId::from).to_vec(),
+                ..node("tabs", "AXTabGroup")
+            },
+            tab("General", false),
+            tab("Advanced", true),
+        ])
+    }
+
+    #[test]
+    fn tab_group_value_is_the_selected_tab_name() {
+        let config = Config {
+            synthesize_container_values: true,
+            ..Config::default()
+        };
+        let provider = SynthesizingProvider::wrap(Box::new(tab_group()), &config);
+        let tabs = provider.get_node(&NodeId::from("tabs")).unwrap();
+        assert_eq!(tabs.value.as_deref(), Some("Advanced"));
+
+        // Only when the value is asked for
+        let tabs = provider
+            .get_node_fields(&NodeId::from("tabs"), NodeFields::NAME)
+            .unwrap();
+        assert_eq!(tabs.value, None);
+    }
+
+    #[test]
+    fn checkbox_value_is_its_checked_state() {
+        let mock = MockProvider::new(vec![Node {
+            value: Some("1".to_string()),
+            ..node("remember", "AXCheckBox")
+        }]);
+        let config = Config {
+            synthesize_container_values: true,
+            ..Config::default()
+        };
+        let provider = SynthesizingProvider::wrap(Box::new(mock), &config);
+        let remember = provider.get_node(&NodeId::from("remember")).unwrap();
+        assert_eq!(remember.value.as_deref(), Some("checked"));
+    }
+
+    #[test]
+    fn mixed_checkboxes_count_as_unchecked() {
+        let mock = MockProvider::new(vec![Node {
+            value: Some("2".to_string()),
+            ..node("all", "AXCheckBox")
+        }]);
+        let config = Config {
+            synthesize_container_values: true,
+            ..Config::default()
+        };
+        let provider = SynthesizingProvider::wrap(Box::new(mock), &config);
+        let all = provider.get_node(&NodeId::from("all")).unwrap();
+        assert_eq!(all.value.as_deref(), Some("mixed"));
+        assert_eq!(
+            crate::platform::parse_checked(all.value.as_deref()),
+            Some(false)
+        );
+        assert_eq!(crate::platform::parse_checked(Some("2")), Some(false));
+    }
+
+    #[test]
+    fn list_value_comes_from_its_selection_alone() {
+        let rows: Vec<_> = (0..100).map(|i| format!("row{}", i)).collect();
+        let mut nodes = vec![Node {
+            children: rows.iter().map(|id| NodeId::from(id.as_str())).collect(),
+            ..node("list", "AXList")
+        }];
+        nodes.extend(rows.iter().map(|id| Node {
+            name: Some(id.clone()),
+            ..node(id, "AXRow")
+        }));
+        let mock = MockProvider::new(nodes);
+        let config = Config {
+            synthesize_container_values: true,
+            ..Config::default()
+        };
+        let provider = SynthesizingProvider::wrap(Box::new(mock.clone()), &config);
+
+        // Nothing selected: no value, and no row read to find out
+        let list = provider.get_node(&NodeId::from("list")).unwrap();
+        assert_eq!(list.value, None);
+        assert_eq!(mock.get_node_calls(), 1);
+
+        mock.set_node(Node {
+            selected_children: vec![NodeId::from("row42")],
+            ..list
+        });
+        let list = provider.get_node(&NodeId::from("list")).unwrap();
+        assert_eq!(list.value.as_deref(), Some("row42"));
+        // The list itself and the selected row
+        assert_eq!(mock.get_node_calls(), 3);
+    }
+
+    #[test]
+    fn values_are_raw_by_default() {
+        let provider = SynthesizingProvider::wrap(Box::new(tab_group()), &Config::default());
+        let tabs = provider.get_node(&NodeId::from("tabs")).unwrap();
+        assert_eq!(tabs.value, None);
+    }
+}
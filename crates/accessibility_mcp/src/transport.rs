@@ -0,0 +1,670 @@
+//! JSON message transports over byte streams
+//!
+//! By default each request is one line of JSON and each response is written
+//! back as one line; this is what the Unix socket listener and the FIFO pair
+//! use. Stdio can instead use `Content-Length` header framing.
+
+use crate::config::StdioFraming;
+use crate::protocol::{ErrorCode, Message};
+use crate::server::{handle_request_blocking, AppState};
+use std::io::{Error, ErrorKind};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::watch;
+
+/// Largest `Content-Length` accepted, since the body is allocated up front;
+/// no request comes anywhere near it
+const MAX_FRAME_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Serialize `message` as a single newline-terminated frame.
+///
+/// Always compact, whatever `Config::pretty_json` says, as a newline inside
+/// the message would end the frame early. The whole frame is built before
+/// anything is written, so a serialization failure never leaves half a
+/// message on the wire.
+pub(crate) fn encode_frame(message: &Message) -> serde_json::Result<Vec<u8>> {
+    let mut frame = serde_json::to_vec(message)?;
+    frame.push(b'\n');
+    Ok(frame)
+}
+
+/// Serialize `message` as a `Content-Length`-framed body, pretty-printed if `pretty`.
+pub(crate) fn encode_content_length_frame(
+    message: &Message,
+    pretty: bool,
+) -> serde_json::Result<Vec<u8>> {
+    let body = if pretty {
+        serde_json::to_vec_pretty(message)?
+    } else {
+        serde_json::to_vec(message)?
+    };
+    let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+/// Encode `response` with `encode`, or if it can't be serialized, an
+/// `internal` error in its place, so the client still gets a reply.
+fn encode_or_fallback(
+    response: &Message,
+    encode: impl Fn(&Message) -> serde_json::Result<Vec<u8>>,
+) -> Vec<u8> {
+    encode(response).unwrap_or_else(|e| {
+        tracing::error!("Failed to serialize response: {}", e);
+        let fallback = Message::error(
+            ErrorCode::Internal,
+            format!("Failed to serialize response: {}", e),
+        );
+        encode(&fallback).expect("error messages always serialize")
+    })
+}
+
+/// Write a complete frame with a single `write_all` and flush it.
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> std::io::Result<()> {
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+/// Serve newline-delimited requests from `reader`, writing responses to `writer`.
+///
+/// Returns when the reader reaches EOF or a write fails. After a failed write
+/// the connection is abandoned rather than continued, so a client never sees
+/// a later frame appended to a truncated one.
+pub(crate) async fn serve_lines<R, W>(state: &AppState, reader: R, writer: W) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    serve_lines_with(state, reader, writer, encode_frame, None).await
+}
+
+/// [`serve_lines`], encoding each response with `encode`, and stopping
+/// between requests once `shutdown` is signalled
+async fn serve_lines_with<R, W>(
+    state: &AppState,
+    reader: R,
+    mut writer: W,
+    encode: impl Fn(&Message) -> serde_json::Result<Vec<u8>>,
+    mut shutdown: Option<watch::Receiver<bool>>,
+) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = reader.lines();
+
+    while let Some(line) = unless_shut_down(lines.next_line(), &mut shutdown).await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = respond(state, line.as_bytes()).await;
+        let frame = encode_or_fallback(&response, &encode);
+        write_frame(&mut writer, &frame).await?;
+    }
+
+    writer.flush().await
+}
+
+/// Serve `Content-Length`-framed requests from `reader`, framing responses the same way.
+///
+/// Malformed headers end the connection, since there is no way to find the
+/// start of the next frame. A body that isn't valid JSON gets an
+/// `invalid_request` error, like a bad line does.
+pub(crate) async fn serve_content_length<R, W>(
+    state: &AppState,
+    mut reader: R,
+    mut writer: W,
+    mut shutdown: Option<watch::Receiver<bool>>,
+) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    while let Some(body) =
+        unless_shut_down(read_content_length_frame(&mut reader), &mut shutdown).await?
+    {
+        let response = respond(state, &body).await;
+        let frame = encode_or_fallback(&response, |message| {
+            encode_content_length_frame(message, state.pretty_json)
+        });
+        write_frame(&mut writer, &frame).await?;
+    }
+
+    writer.flush().await
+}
+
+/// Resolve to `read`'s result, or to `None` (as if at EOF) once `shutdown`
+/// is signalled first
+async fn unless_shut_down<T>(
+    read: impl std::future::Future<Output = std::io::Result<Option<T>>>,
+    shutdown: &mut Option<watch::Receiver<bool>>,
+) -> std::io::Result<Option<T>> {
+    let Some(shutdown) = shutdown else {
+        return read.await;
+    };
+    tokio::select! {
+        read = read => read,
+        // An error means the sender is gone, and with it any reason to stop
+        Ok(_) = shutdown.wait_for(|stop| *stop) => Ok(None),
+    }
+}
+
+/// Serve with the given framing; used for stdin/stdout
+///
+/// Serving stops at EOF, or once `state` signals shutdown because the app
+/// quit. However it ends, including on a read error, the writer is flushed
+/// and shut down before returning, so a client that sends a request and
+/// immediately closes its end still gets the last response.
+pub(crate) async fn serve_stdio<R, W>(
+    state: &AppState,
+    reader: R,
+    mut writer: W,
+    framing: StdioFraming,
+) -> std::io::Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let shutdown = state.shutdown_on_app_exit.as_ref().map(|tx| tx.subscribe());
+    let served = match framing {
+        StdioFraming::LineDelimited => {
+            serve_lines_with(state, reader, &mut writer, encode_frame, shutdown).await
+        }
+        StdioFraming::ContentLength => {
+            serve_content_length(state, reader, &mut writer, shutdown).await
+        }
+    };
+    let closed = writer.shutdown().await;
+    served.and(closed)
+}
+
+/// Read the headers and body of one frame; `None` at EOF between frames.
+///
+/// Headers other than `Content-Length` (such as `Content-Type`) are ignored.
+async fn read_content_length_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut in_headers = false;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            if in_headers {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "EOF inside frame headers",
+                ));
+            }
+            return Ok(None);
+        }
+
+        let header = line.trim_end_matches(['\r', '\n']);
+        if header.is_empty() {
+            if in_headers {
+                break;
+            }
+            // Tolerate stray blank lines between frames
+            continue;
+        }
+        in_headers = true;
+
+        let (name, value) = header.split_once(':').ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Malformed header: {}", header),
+            )
+        })?;
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            let length = value.trim().parse::<usize>().map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Invalid Content-Length: {}", e),
+                )
+            })?;
+            content_length = Some(length);
+        }
+    }
+
+    let length = content_length
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Frame has no Content-Length header"))?;
+    if length > MAX_FRAME_LENGTH {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Content-Length {} exceeds the limit of {} bytes",
+                length, MAX_FRAME_LENGTH
+            ),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(body))
+}
+
+/// Parse and handle one request body.
+///
+/// A handler that panics fails only that request, with an `internal` error,
+/// rather than the connection.
+async fn respond(state: &AppState, body: &[u8]) -> Message {
+    match Message::from_json(body) {
+        Ok(message) => handle_request_blocking(state, message).await,
+        Err(e) => Message::error(ErrorCode::InvalidRequest, e),
+    }
+}
+
+/// Tell a connection over the limit that the server is busy, then close it
+#[cfg(unix)]
+async fn reject_busy<W: AsyncWrite + Unpin>(writer: &mut W) {
+    tracing::warn!("Rejecting connection: connection limit reached");
+    let busy = Message::error(ErrorCode::Transient, "server busy");
+    if let Ok(frame) = encode_frame(&busy) {
+        let _ = write_frame(writer, &frame).await;
+    }
+    let _ = writer.shutdown().await;
+}
+
+/// Accept connections on a Unix socket until shutdown, then remove `socket_file` if given
+#[cfg(unix)]
+pub(crate) async fn run_unix_socket_server(
+    state: AppState,
+    listener: tokio::net::UnixListener,
+    socket_file: Option<std::path::PathBuf>,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((mut stream, _)) => {
+                    let permit = match &state.connection_limit {
+                        Some(limit) => match limit.clone().try_acquire_owned() {
+                            Ok(permit) => Some(permit),
+                            Err(_) => {
+                                tokio::spawn(async move { reject_busy(&mut stream).await });
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        // Held for the life of the connection
+                        let _permit = permit;
+                        let (read, write) = stream.into_split();
+                        let reader = tokio::io::BufReader::new(read);
+                        if let Err(e) = serve_lines(&state, reader, write).await {
+                            tracing::debug!("Unix socket connection closed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => tracing::error!("Failed to accept Unix socket connection: {}", e),
+            },
+            _ = shutdown_rx.changed() => break,
+        }
+    }
+
+    tracing::info!("Unix socket server shutting down");
+    if let Some(path) = socket_file {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Open the request and response FIFOs.
+///
+/// Both are opened for reading and writing, which POSIX leaves undefined for
+/// FIFOs but Linux and macOS allow. That way opening never waits for a
+/// client, the request pipe never reads EOF when a client closes it, and a
+/// response written before its client opens the pipe waits there instead of
+/// failing.
+#[cfg(unix)]
+pub(crate) fn open_fifo_pair(
+    paths: &crate::config::FifoPaths,
+) -> std::io::Result<(
+    tokio::net::unix::pipe::Receiver,
+    tokio::net::unix::pipe::Sender,
+)> {
+    use tokio::net::unix::pipe;
+
+    let open = |path| {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+    };
+    let requests = pipe::Receiver::from_file(open(&paths.requests)?)?;
+    let responses = pipe::Sender::from_file(open(&paths.responses)?)?;
+    Ok((requests, responses))
+}
+
+/// Serve newline-delimited requests from one FIFO, answering on the other, until shutdown.
+///
+/// Lines from clients writing at the same time may interleave, so only one
+/// client should use the pair at once.
+#[cfg(unix)]
+pub(crate) async fn run_fifo_server(
+    state: AppState,
+    requests: tokio::net::unix::pipe::Receiver,
+    responses: tokio::net::unix::pipe::Sender,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    tokio::select! {
+        served = serve_lines(&state, tokio::io::BufReader::new(requests), responses) => {
+            if let Err(e) = served {
+                tracing::error!("FIFO transport failed: {}", e);
+            }
+        }
+        _ = shutdown_rx.changed() => {}
+    }
+
+    tracing::info!("FIFO server shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, MockProvider};
+    use crate::protocol::{MessageContent, Response};
+    use crate::Config;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    fn state() -> AppState {
+        let provider = MockProvider::new(vec![node("app", "AXApplication")]);
+        AppState::new(Box::new(provider), &Config::default())
+    }
+
+    const GET_APP: &str = r#"{"protocol_version":"1.0","method":"get_node","node_id":"app"}"#;
+
+    /// Accepts at most `chunk` bytes per `write` call, like a full pipe, and
+    /// fails every write once `bytes_left` have been accepted
+    struct FlakyWriter {
+        written: Vec<u8>,
+        chunk: usize,
+        bytes_left: usize,
+    }
+
+    impl AsyncWrite for FlakyWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            if self.bytes_left == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
+            let accepted = buf.len().min(self.chunk).min(self.bytes_left);
+            self.bytes_left -= accepted;
+            self.written.extend_from_slice(&buf[..accepted]);
+            Poll::Ready(Ok(accepted))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn responds_one_line_per_request() {
+        let input = format!("{GET_APP}\n\n{GET_APP}\n");
+        let mut output = Vec::new();
+        serve_lines(&state(), input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let reply: Message = serde_json::from_str(line).expect("each line is a full message");
+            assert!(serde_json::to_string(&reply)
+                .unwrap()
+                .contains("AXApplication"));
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_protocol_version_means_the_current_one() {
+        let input = concat!(
+            r#"{"method":"get_node","node_id":"app"}"#,
+            "\n",
+            r#"{"protocol_version":"0.9","method":"get_node","node_id":"app"}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+        serve_lines(&state(), input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let replies: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(replies[0]["status"], "success");
+        assert_eq!(replies[0]["result"]["node"]["role"], "AXApplication");
+        // A version given explicitly must still match
+        assert_eq!(replies[1]["status"], "error");
+        assert_eq!(
+            replies[1]["error"]["message"],
+            "Unsupported protocol version: 0.9"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_write_emits_no_partial_frame() {
+        let mut one_frame = Vec::new();
+        serve_lines(&state(), format!("{GET_APP}\n").as_bytes(), &mut one_frame)
+            .await
+            .unwrap();
+
+        // The first frame takes many short writes; the second is refused outright
+        let input = format!("{GET_APP}\n{GET_APP}\n{GET_APP}\n");
+        let mut writer = FlakyWriter {
+            written: Vec::new(),
+            chunk: 7,
+            bytes_left: one_frame.len(),
+        };
+
+        let result = serve_lines(&state(), input.as_bytes(), &mut writer).await;
+        assert!(result.is_err(), "write failure should end the connection");
+
+        // Exactly one whole frame made it out; nothing of the second
+        let text = String::from_utf8(writer.written).unwrap();
+        assert_eq!(text.matches('\n').count(), 1);
+        assert!(text.ends_with('\n'));
+        serde_json::from_str::<Message>(text.trim_end()).expect("complete frame");
+    }
+
+    #[tokio::test]
+    async fn unserializable_response_gets_a_fallback_error() {
+        // Fails on successful responses, as a response that can't be encoded would
+        let encode = |message: &Message| match &message.content {
+            MessageContent::Response(Response::Success { .. }) => {
+                Err(serde::ser::Error::custom("cannot encode this response"))
+            }
+            _ => encode_frame(message),
+        };
+        let input = format!("{GET_APP}\nnot json\n");
+        let mut output = Vec::new();
+        serve_lines_with(&state(), input.as_bytes(), &mut output, encode, None)
+            .await
+            .unwrap();
+
+        // Both requests got a reply: the connection carried on after the failure
+        let text = String::from_utf8(output).unwrap();
+        let replies: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["error"]["code"], "internal");
+        assert_eq!(
+            replies[0]["error"]["message"],
+            "Failed to serialize response: cannot encode this response"
+        );
+        assert_eq!(replies[1]["error"]["code"], "invalid_request");
+    }
+
+    /// Split a `Content-Length` frame into its declared length and body
+    fn split_frame(frame: &[u8]) -> (usize, &[u8]) {
+        let text = std::str::from_utf8(frame).unwrap();
+        let (headers, body) = text.split_once("\r\n\r\n").expect("header terminator");
+        let length = headers
+            .strip_prefix("Content-Length: ")
+            .expect("Content-Length header")
+            .parse()
+            .unwrap();
+        (length, body.as_bytes())
+    }
+
+    #[tokio::test]
+    async fn content_length_framing_round_trips() {
+        // Pretty-printed, so the body spans several lines
+        let request: serde_json::Value = serde_json::from_str(GET_APP).unwrap();
+        let body = serde_json::to_string_pretty(&request).unwrap();
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}",
+            body.len(),
+            body
+        );
+
+        let mut output = Vec::new();
+        serve_stdio(
+            &state(),
+            input.as_bytes(),
+            &mut output,
+            StdioFraming::ContentLength,
+        )
+        .await
+        .unwrap();
+
+        let (length, body) = split_frame(&output);
+        assert_eq!(length, body.len());
+        let reply: Message = serde_json::from_slice(body).expect("body is a full message");
+        assert!(serde_json::to_string(&reply)
+            .unwrap()
+            .contains("AXApplication"));
+    }
+
+    #[tokio::test]
+    async fn pretty_json_needs_content_length_framing() {
+        let provider = MockProvider::new(vec![node("app", "AXApplication")]);
+        let config = Config {
+            pretty_json: true,
+            ..Config::default()
+        };
+        let state = AppState::new(Box::new(provider), &config);
+        let input = format!("Content-Length: {}\r\n\r\n{}", GET_APP.len(), GET_APP);
+
+        let mut output = Vec::new();
+        serve_content_length(&state, input.as_bytes(), &mut output, None)
+            .await
+            .unwrap();
+        let (length, body) = split_frame(&output);
+        assert_eq!(length, body.len());
+        let text = std::str::from_utf8(body).unwrap();
+        assert!(text.contains("\n  \"protocol_version\""), "{text}");
+        serde_json::from_str::<Message>(text).expect("body is a full message");
+
+        // Newline framing ignores the setting, keeping one line per message
+        let mut output = Vec::new();
+        serve_lines(&state, format!("{GET_APP}\n").as_bytes(), &mut output)
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn stdio_response_arrives_after_client_closes_input() {
+        let (mut stdin, server_in) = tokio::io::duplex(64);
+        let (server_out, mut stdout) = tokio::io::duplex(64);
+        let server = tokio::spawn(async move {
+            let reader = tokio::io::BufReader::new(server_in);
+            serve_stdio(&state(), reader, server_out, StdioFraming::LineDelimited).await
+        });
+
+        stdin.write_all(GET_APP.as_bytes()).await.unwrap();
+        stdin.write_all(b"\n").await.unwrap();
+        drop(stdin);
+
+        // Only reaches EOF once the server has closed its output
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).await.unwrap();
+        server.await.unwrap().unwrap();
+
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.contains("AXApplication"));
+    }
+
+    #[tokio::test]
+    async fn stdio_stops_once_the_app_quits() {
+        let provider = MockProvider::new(vec![node("app", "AXApplication")]);
+        let mut state = AppState::new(Box::new(provider.clone()), &Config::default());
+        state.shutdown_on_app_exit = Some(std::sync::Arc::new(watch::channel(false).0));
+        provider.terminate();
+
+        // Stdin stays open, so only the shutdown ends serving
+        let (mut stdin, server_in) = tokio::io::duplex(64);
+        let (server_out, mut stdout) = tokio::io::duplex(256);
+        let server = tokio::spawn(async move {
+            let reader = tokio::io::BufReader::new(server_in);
+            serve_stdio(&state, reader, server_out, StdioFraming::LineDelimited).await
+        });
+        stdin.write_all(GET_APP.as_bytes()).await.unwrap();
+        stdin.write_all(b"\n").await.unwrap();
+
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).await.unwrap();
+        server.await.unwrap().unwrap();
+        assert!(output.contains("app_terminated"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn stdio_output_is_closed_after_a_malformed_frame() {
+        let body = GET_APP;
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{}garbage\r\n\r\n",
+            body.len(),
+            body
+        );
+        let (server_out, mut stdout) = tokio::io::duplex(64);
+        let server = tokio::spawn(async move {
+            serve_stdio(
+                &state(),
+                input.as_bytes(),
+                server_out,
+                StdioFraming::ContentLength,
+            )
+            .await
+        });
+
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).await.unwrap();
+        let result = server.await.unwrap();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // The response to the good frame still made it out
+        let (length, body) = split_frame(&output);
+        assert_eq!(length, body.len());
+    }
+
+    #[tokio::test]
+    async fn content_length_frame_without_length_is_rejected() {
+        let input = "Content-Type: application/json\r\n\r\n{}";
+        let result = serve_content_length(&state(), input.as_bytes(), Vec::new(), None).await;
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_is_rejected_before_allocating() {
+        let input = format!("Content-Length: {}\r\n\r\n{{}}", usize::MAX);
+        let result = serve_content_length(&state(), input.as_bytes(), Vec::new(), None).await;
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
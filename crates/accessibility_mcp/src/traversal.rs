@@ -0,0 +1,286 @@
+//! Tree traversal shared by the query and search handlers
+
+use crate::platform::{AccessibilityProvider, NodeFields};
+use crate::protocol::{Node, TraversalOrder};
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+/// Default cap on visited nodes, to keep traversals of huge trees bounded
+pub(crate) const DEFAULT_MAX_NODES: usize = 1000;
+
+/// Parameters for walking the accessibility tree
+pub(crate) struct Walk {
+    pub order: TraversalOrder,
+    /// Deepest level to expand; the starting node is depth 0
+    pub max_depth: Option<usize>,
+    /// Maximum number of nodes to visit
+    pub max_nodes: usize,
+    /// Expand nodes marked as web content; when false they are visited but not expanded
+    pub descend_into_web: bool,
+    /// Splice out anonymous single-child `AXGroup` wrappers, promoting their child
+    pub collapse_single_child_groups: bool,
+    /// Stop once this instant has passed; checked between node visits
+    pub deadline: Option<Instant>,
+    /// Attributes to read for each visited node below the starting one.
+    /// Whatever the walk itself needs (children, and names when collapsing)
+    /// is always read
+    pub fields: NodeFields,
+}
+
+impl Walk {
+    pub fn new(order: TraversalOrder) -> Self {
+        Self {
+            order,
+            max_depth: None,
+            max_nodes: DEFAULT_MAX_NODES,
+            descend_into_web: true,
+            collapse_single_child_groups: false,
+            deadline: None,
+            fields: NodeFields::ALL,
+        }
+    }
+
+    /// Visit every node reachable from `root`, passing each node and its depth.
+    ///
+    /// Visited nodes have `depth` and `index_in_parent` filled in.
+    ///
+    /// Children that fail to resolve are skipped. Returns `true` if the walk
+    /// stopped early because it hit `max_nodes` or its deadline.
+    pub fn run(
+        &self,
+        provider: &dyn AccessibilityProvider,
+        root: Node,
+        mut visit: impl FnMut(&Node, usize),
+    ) -> bool {
+        let mut pending = VecDeque::from([(self.promote(provider, root), 0)]);
+        let mut visited = HashSet::new();
+
+        while let Some((mut node, depth)) = self.next(&mut pending) {
+            if visited.len() >= self.max_nodes {
+                return true;
+            }
+
+            // Checked between visits, so the starting node is always visited
+            if !visited.is_empty() && self.deadline.is_some_and(|d| Instant::now() >= d) {
+                return true;
+            }
+
+            // Skip if already visited (prevent cycles)
+            if !visited.insert(node.id.clone()) {
+                continue;
+            }
+
+            let expand = self.max_depth.is_none_or(|max| depth < max)
+                && (self.descend_into_web || !node.is_web_content);
+
+            let children: Vec<_> = if expand {
+                node.children
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, child_id)| {
+                        provider
+                            .get_node_fields(child_id, self.read_fields())
+                            .inspect_err(|e| {
+                                tracing::debug!("Failed to get child node {:?}: {}", child_id, e)
+                            })
+                            .ok()
+                            .map(|child| (index, child))
+                    })
+                    .map(|(index, child)| Node {
+                        index_in_parent: Some(index),
+                        ..self.promote(provider, child)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            // Point the parent at the promoted children rather than their wrappers
+            for child_id in node.children.iter_mut() {
+                if let Some(promoted) = children
+                    .iter()
+                    .find(|c| c.collapsed_from.first() == Some(child_id))
+                {
+                    *child_id = promoted.id.clone();
+                }
+            }
+
+            node.depth = Some(depth);
+            visit(&node, depth);
+
+            match self.order {
+                // Popped from the back, so push in reverse to keep child order
+                TraversalOrder::DocumentOrder => {
+                    pending.extend(children.into_iter().rev().map(|c| (c, depth + 1)));
+                }
+                TraversalOrder::DepthFirst | TraversalOrder::BreadthFirst => {
+                    pending.extend(children.into_iter().map(|c| (c, depth + 1)));
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Replace an anonymous single-child group by its descendant, when collapsing is on.
+    ///
+    /// The promoted node lists the wrappers it replaced, outermost first, in
+    /// `collapsed_from`.
+    fn promote(&self, provider: &dyn AccessibilityProvider, mut node: Node) -> Node {
+        if !self.collapse_single_child_groups {
+            return node;
+        }
+
+        let mut collapsed = Vec::new();
+        while is_wrapper_group(&node) && !collapsed.contains(&node.id) {
+            match provider.get_node_fields(&node.children[0], self.read_fields()) {
+                Ok(child) => {
+                    collapsed.push(node.id.clone());
+                    node = child;
+                }
+                Err(e) => {
+                    tracing::debug!("Failed to get child node {:?}: {}", node.children[0], e);
+                    break;
+                }
+            }
+        }
+
+        node.collapsed_from = collapsed;
+        node
+    }
+
+    /// `fields` plus the attributes the walk relies on
+    fn read_fields(&self) -> NodeFields {
+        let fields = self.fields | NodeFields::CHILDREN;
+        if self.collapse_single_child_groups {
+            fields | NodeFields::NAME
+        } else {
+            fields
+        }
+    }
+
+    fn next(&self, pending: &mut VecDeque<(Node, usize)>) -> Option<(Node, usize)> {
+        match self.order {
+            TraversalOrder::BreadthFirst => pending.pop_front(),
+            TraversalOrder::DepthFirst | TraversalOrder::DocumentOrder => pending.pop_back(),
+        }
+    }
+}
+
+/// An unnamed `AXGroup` with exactly one child, which adds depth but no information
+fn is_wrapper_group(node: &Node) -> bool {
+    node.role == "AXGroup"
+        && node.name.as_deref().is_none_or(str::is_empty)
+        && node.children.len() == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::mock::{node, sample_tree, MockProvider};
+    use crate::protocol::NodeId;
+
+    fn order_of(walk: Walk) -> Vec<String> {
+        let provider = sample_tree();
+        let root = provider.get_root().unwrap();
+        let mut seen = Vec::new();
+        walk.run(&provider, root, |n, _| seen.push(n.id.as_str().to_string()));
+        seen
+    }
+
+    #[test]
+    fn document_order_follows_child_order() {
+        let seen = order_of(Walk::new(TraversalOrder::DocumentOrder));
+        assert_eq!(seen, ["root", "a", "a1", "a2", "b", "b1"]);
+    }
+
+    #[test]
+    fn breadth_first_visits_level_by_level() {
+        let seen = order_of(Walk::new(TraversalOrder::BreadthFirst));
+        assert_eq!(seen, ["root", "a", "b", "a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn depth_first_visits_last_child_first() {
+        let seen = order_of(Walk::new(TraversalOrder::DepthFirst));
+        assert_eq!(seen, ["root", "b", "b1", "a", "a2", "a1"]);
+    }
+
+    #[test]
+    fn visited_nodes_record_their_position() {
+        let provider = sample_tree();
+        let root = provider.get_root().unwrap();
+        let mut seen = Vec::new();
+        Walk::new(TraversalOrder::BreadthFirst).run(&provider, root, |n, _| {
+            seen.push((n.id.as_str().to_string(), n.depth, n.index_in_parent))
+        });
+
+        let expected = [
+            ("root", 0, None),
+            ("a", 1, Some(0)),
+            ("b", 1, Some(1)),
+            ("a1", 2, Some(0)),
+            ("a2", 2, Some(1)),
+            ("b1", 2, Some(0)),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(id, depth, index)| (id.to_string(), Some(*depth), *index))
+            .collect();
+        assert_eq!(seen, expected);
+
+        // The provider's own nodes don't carry walk positions
+        let a1 = provider.get_node(&NodeId::from("a1")).unwrap();
+        assert_eq!((a1.depth, a1.index_in_parent), (None, None));
+    }
+
+    #[test]
+    fn respects_depth_and_node_limits() {
+        let mut walk = Walk::new(TraversalOrder::DocumentOrder);
+        walk.max_depth = Some(1);
+        assert_eq!(order_of(walk), ["root", "a", "b"]);
+
+        let mut walk = Walk::new(TraversalOrder::DocumentOrder);
+        walk.max_nodes = 2;
+        assert_eq!(order_of(walk), ["root", "a"]);
+    }
+
+    #[test]
+    fn collapses_anonymous_single_child_groups() {
+        let parent = |id: &str, role: &str, children: &[&str]| Node {
+            children: children.iter().map(|c| NodeId::from(*c)).collect(),
+            ..node(id, role)
+        };
+        let provider = MockProvider::new(vec![
+            parent("win", "AXWindow", &["g1"]),
+            parent("g1", "AXGroup", &["g2"]),
+            parent("g2", "AXGroup", &["g3"]),
+            parent("g3", "AXGroup", &["ok"]),
+            parent("ok", "AXButton", &[]),
+        ]);
+        let depths = |walk: Walk| {
+            let mut seen = Vec::new();
+            let root = provider.get_root().unwrap();
+            walk.run(&provider, root, |n, depth| seen.push((n.clone(), depth)));
+            seen
+        };
+
+        let full = depths(Walk::new(TraversalOrder::DocumentOrder));
+        assert_eq!(
+            full.last().map(|(n, d)| (n.id.as_str(), *d)),
+            Some(("ok", 4))
+        );
+
+        let mut walk = Walk::new(TraversalOrder::DocumentOrder);
+        walk.collapse_single_child_groups = true;
+        let collapsed = depths(walk);
+        let ids: Vec<_> = collapsed.iter().map(|(n, _)| n.id.as_str()).collect();
+        assert_eq!(ids, ["win", "ok"]);
+
+        let (win, _) = &collapsed[0];
+        assert_eq!(win.children, [NodeId::from("ok")]);
+        let (ok, depth) = &collapsed[1];
+        assert_eq!(*depth, 1);
+        assert_eq!(ok.collapsed_from, ["g1", "g2", "g3"].map(NodeId::from));
+    }
+}
@@ -0,0 +1,106 @@
+//! Heuristic on-screen checks for nodes visited during a traversal
+//!
+//! A node counts as visible when its bounds overlap the clip rect of every
+//! enclosing window and scroll area, and, outside the frontmost window, when
+//! it isn't entirely covered by the frontmost window's bounds. This only uses the geometry the accessibility API reports: windows of
+//! other applications, overlapping siblings, transparency, and `hidden`
+//! styling are not considered.
+
+use crate::protocol::{Node, NodeId, Rect};
+use std::collections::HashMap;
+
+/// Roles whose bounds clip what their descendants can show
+const CLIPPING_ROLES: &[&str] = &["AXWindow", "AXScrollArea"];
+
+/// What a node inherits from its ancestors
+#[derive(Clone, Copy)]
+struct Clip {
+    rect: Option<Rect>,
+    /// The frontmost window's bounds, for nodes in a window behind it
+    covered_by: Option<Rect>,
+}
+
+/// Tracks clip rects down a traversal.
+///
+/// Feed it every node in the order the walk visits them; a parent must be
+/// visited before its children, which every [`Walk`](crate::traversal::Walk)
+/// order guarantees. A chain of ancestors, root first, works the same way.
+pub(crate) struct VisibilityTracker {
+    frontmost_window: Option<(NodeId, Option<Rect>)>,
+    clips: HashMap<NodeId, Clip>,
+}
+
+impl VisibilityTracker {
+    /// `frontmost_window` is the window considered unoccluded; `None` treats every window as in front
+    pub fn new(frontmost_window: Option<&Node>) -> Self {
+        Self {
+            frontmost_window: frontmost_window.map(|window| (window.id.clone(), window.bounds)),
+            clips: HashMap::new(),
+        }
+    }
+
+    /// Whether `node` is visible, or `None` if it reports no bounds
+    pub fn visit(&mut self, node: &Node) -> Option<bool> {
+        let mut clip = self.clips.remove(&node.id).unwrap_or(Clip {
+            rect: None,
+            covered_by: None,
+        });
+
+        if node.role == "AXWindow" {
+            clip.covered_by = self
+                .frontmost_window
+                .as_ref()
+                .filter(|(front, _)| *front != node.id)
+                .and_then(|(_, bounds)| *bounds);
+        }
+
+        let visible = node.bounds.map(|bounds| {
+            !is_empty(&bounds)
+                && clip.rect.is_none_or(|c| overlaps(&bounds, &c))
+                && clip
+                    .covered_by
+                    .is_none_or(|front| !contains(&front, &bounds))
+        });
+
+        if CLIPPING_ROLES.contains(&node.role.as_str()) {
+            if let Some(bounds) = node.bounds {
+                clip.rect = Some(match clip.rect {
+                    Some(outer) => intersect(&outer, &bounds),
+                    None => bounds,
+                });
+            }
+        }
+        for child_id in &node.children {
+            self.clips.insert(child_id.clone(), clip);
+        }
+
+        visible
+    }
+}
+
+fn is_empty(rect: &Rect) -> bool {
+    rect.width <= 0.0 || rect.height <= 0.0
+}
+
+/// Whether `outer` covers all of `inner`
+fn contains(outer: &Rect, inner: &Rect) -> bool {
+    inner.x >= outer.x
+        && inner.y >= outer.y
+        && inner.x + inner.width <= outer.x + outer.width
+        && inner.y + inner.height <= outer.y + outer.height
+}
+
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    !is_empty(&intersect(a, b))
+}
+
+fn intersect(a: &Rect, b: &Rect) -> Rect {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    Rect {
+        x,
+        y,
+        width: ((a.x + a.width).min(b.x + b.width) - x).max(0.0),
+        height: ((a.y + a.height).min(b.y + b.height) - y).max(0.0),
+    }
+}
@@ -0,0 +1,92 @@
+//! Runs the `a11y-mcp` binary against an in-process server
+
+mod common;
+
+use accessibility_mcp::{Action, NodeId};
+use common::{start_server, start_server_with_config, StaticProvider};
+use std::process::{Command, Output};
+
+fn cli(port: u16, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_a11y-mcp"))
+        .arg("--port")
+        .arg(port.to_string())
+        .args(args)
+        .output()
+        .expect("Failed to run a11y-mcp")
+}
+
+fn stdout_json(output: &Output) -> serde_json::Value {
+    assert!(
+        output.status.success(),
+        "a11y-mcp failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("stdout should be JSON")
+}
+
+#[test]
+fn cli_runs_one_shot_commands() {
+    let provider = StaticProvider::new();
+    let (_runtime, handle) = start_server(provider.clone());
+
+    let tree = stdout_json(&cli(handle.port, &["tree"]));
+    assert_eq!(tree["nodes"][0]["role"], "AXApplication");
+
+    let node = stdout_json(&cli(handle.port, &["get", "ok"]));
+    assert_eq!(node["node"]["name"], "OK");
+
+    let found = stdout_json(&cli(handle.port, &["find", "cancel"]));
+    assert_eq!(found["nodes"][0]["id"], "cancel");
+
+    stdout_json(&cli(handle.port, &["action", "ok", "press"]));
+    stdout_json(&cli(handle.port, &["action", "cancel", "v:hello"]));
+    assert_eq!(
+        *provider.performed.lock().unwrap(),
+        vec![
+            (NodeId::from("ok"), Action::Press),
+            (
+                NodeId::from("cancel"),
+                Action::SetValue {
+                    value: "hello".to_string()
+                }
+            ),
+        ]
+    );
+
+    handle.shutdown();
+}
+
+#[test]
+fn cli_reports_errors_with_nonzero_exit() {
+    let (_runtime, handle) = start_server(StaticProvider::new());
+
+    let output = cli(handle.port, &["get", "missing"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NotFound"));
+
+    let output = cli(handle.port, &["bogus"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    handle.shutdown();
+}
+
+#[cfg(unix)]
+#[test]
+fn cli_connects_over_unix_socket() {
+    let path = std::env::temp_dir().join(format!("a11y_mcp_cli_{}.sock", std::process::id()));
+    let config = accessibility_mcp::Config {
+        unix_socket: Some(path.clone()),
+        ..Default::default()
+    };
+    let (_runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_a11y-mcp"))
+        .arg("--socket")
+        .arg(&path)
+        .args(["get", "ok"])
+        .output()
+        .expect("Failed to run a11y-mcp");
+    assert_eq!(stdout_json(&output)["node"]["name"], "OK");
+
+    handle.shutdown();
+}
@@ -0,0 +1,65 @@
+//! Drives an in-process server through the typed client
+
+mod common;
+
+use accessibility_mcp::client::Client;
+use accessibility_mcp::protocol::ResponseData;
+use accessibility_mcp::{Action, NodeId};
+use common::{start_server, StaticProvider};
+
+#[test]
+fn client_round_trips_against_in_process_server() {
+    let provider = StaticProvider::new();
+    let (runtime, handle) = start_server(provider.clone());
+    let client = Client::with_port(handle.port);
+
+    runtime.block_on(async {
+        match client.query_tree().await.expect("query_tree") {
+            ResponseData::Tree { nodes, .. } => assert_eq!(nodes[0].role, "AXApplication"),
+            other => panic!("unexpected query_tree result: {:?}", other),
+        }
+
+        match client
+            .get_node(&NodeId::from("ok"))
+            .await
+            .expect("get_node")
+        {
+            ResponseData::Node { node } => assert_eq!(node.name.as_deref(), Some("OK")),
+            other => panic!("unexpected get_node result: {:?}", other),
+        }
+
+        // find_by_name replies with a node list, which parses as the first matching shape
+        match client.find_by_name("cancel").await.expect("find_by_name") {
+            ResponseData::Tree { nodes, .. } | ResponseData::Nodes { nodes, .. } => {
+                assert_eq!(nodes.len(), 1);
+                assert_eq!(nodes[0].id, NodeId::from("cancel"));
+            }
+            other => panic!("unexpected find_by_name result: {:?}", other),
+        }
+
+        client
+            .perform_action(&NodeId::from("ok"), Action::Press)
+            .await
+            .expect("perform_action");
+
+        let err = client
+            .get_node(&NodeId::from("missing"))
+            .await
+            .expect_err("unknown node should be an error");
+        assert!(err.to_string().contains("NotFound"));
+    });
+
+    assert_eq!(
+        *provider.performed.lock().unwrap(),
+        vec![(NodeId::from("ok"), Action::Press)]
+    );
+    handle.shutdown();
+}
+
+#[test]
+fn client_refuses_https_addresses() {
+    let err = Client::new("https://127.0.0.1:3000").expect_err("the client has no TLS support");
+    assert!(err.to_string().contains("plain HTTP"), "{err}");
+    assert!(Client::new("http://127.0.0.1:3000").is_ok());
+    assert!(Client::new("127.0.0.1:3000").is_ok());
+}
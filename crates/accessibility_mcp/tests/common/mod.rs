@@ -0,0 +1,92 @@
+//! Shared helpers for integration tests that run a server in-process
+
+// Each test binary uses a different subset of these helpers
+#![allow(dead_code)]
+
+use accessibility_mcp::platform::AccessibilityProvider;
+use accessibility_mcp::{start_mcp_server_with_provider, Action, Config, McpHandle, Node, NodeId};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+
+/// A small fixed tree: an application with an "OK" button and a "Cancel" button
+#[derive(Clone)]
+pub struct StaticProvider {
+    nodes: Arc<Mutex<HashMap<NodeId, Node>>>,
+    pub performed: Arc<Mutex<Vec<(NodeId, Action)>>>,
+}
+
+fn node(id: &str, role: &str, name: &str, children: &[&str]) -> Node {
+    Node {
+        id: NodeId::from(id),
+        role: role.to_string(),
+        name: Some(name.to_string()),
+        actions: vec![Action::Press, Action::Focus],
+        children: children.iter().map(|c| NodeId::from(*c)).collect(),
+        ..Default::default()
+    }
+}
+
+impl StaticProvider {
+    pub fn new() -> Self {
+        let nodes = [
+            node("app", "AXApplication", "Demo", &["ok", "cancel"]),
+            node("ok", "AXButton", "OK", &[]),
+            node("cancel", "AXButton", "Cancel", &[]),
+        ];
+        Self {
+            nodes: Arc::new(Mutex::new(
+                nodes.into_iter().map(|n| (n.id.clone(), n)).collect(),
+            )),
+            performed: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl AccessibilityProvider for StaticProvider {
+    fn get_root(&self) -> Result<Node> {
+        self.get_node(&NodeId::from("app"))
+    }
+
+    fn get_children(&self, node_id: &NodeId) -> Result<Vec<Node>> {
+        self.get_node(node_id)?
+            .children
+            .iter()
+            .map(|id| self.get_node(id))
+            .collect()
+    }
+
+    fn get_node(&self, node_id: &NodeId) -> Result<Node> {
+        self.nodes
+            .lock()
+            .unwrap()
+            .get(node_id)
+            .cloned()
+            .context("Node ID not found")
+    }
+
+    fn perform_action(&self, node_id: &NodeId, action: &Action) -> Result<()> {
+        self.get_node(node_id)?;
+        self.performed
+            .lock()
+            .unwrap()
+            .push((node_id.clone(), action.clone()));
+        Ok(())
+    }
+}
+
+/// Start a server for `provider` on an OS-assigned port, with its own runtime
+pub fn start_server(provider: StaticProvider) -> (Runtime, McpHandle) {
+    start_server_with_config(provider, Config::default())
+}
+
+/// Start a server for `provider` with the given configuration, with its own runtime
+pub fn start_server_with_config(provider: StaticProvider, config: Config) -> (Runtime, McpHandle) {
+    let runtime = Runtime::new().expect("Failed to create runtime");
+    let handle = {
+        let _guard = runtime.enter();
+        start_mcp_server_with_provider(Box::new(provider), config).expect("Failed to start server")
+    };
+    (runtime, handle)
+}
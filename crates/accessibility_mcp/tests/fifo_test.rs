@@ -0,0 +1,61 @@
+//! Exercises the newline-delimited JSON transport over a pair of FIFOs
+
+#![cfg(unix)]
+
+mod common;
+
+use accessibility_mcp::{Config, FifoPaths};
+use common::{start_server_with_config, StaticProvider};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::Command;
+
+fn mkfifo(path: &Path) {
+    let _ = std::fs::remove_file(path);
+    let status = Command::new("mkfifo")
+        .arg(path)
+        .status()
+        .expect("run mkfifo");
+    assert!(status.success(), "mkfifo {} failed", path.display());
+}
+
+#[test]
+fn serves_requests_over_a_fifo_pair() {
+    let dir = std::env::temp_dir();
+    let paths = FifoPaths {
+        requests: dir.join(format!("a11y_mcp_requests_{}.fifo", std::process::id())),
+        responses: dir.join(format!("a11y_mcp_responses_{}.fifo", std::process::id())),
+    };
+    mkfifo(&paths.requests);
+    mkfifo(&paths.responses);
+
+    let config = Config {
+        fifo: Some(paths.clone()),
+        ..Default::default()
+    };
+    let (_runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+
+    // The server holds both ends open, so neither open waits
+    let mut requests = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&paths.requests)
+        .unwrap();
+    let mut responses = BufReader::new(std::fs::File::open(&paths.responses).unwrap());
+
+    for _ in 0..2 {
+        requests
+            .write_all(
+                b"{\"protocol_version\":\"1.0\",\"method\":\"get_node\",\"node_id\":\"ok\"}\n",
+            )
+            .unwrap();
+        let mut line = String::new();
+        responses.read_line(&mut line).unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["status"], "success");
+        assert_eq!(reply["result"]["node"]["name"], "OK");
+    }
+
+    handle.shutdown();
+    let _ = std::fs::remove_file(&paths.requests);
+    let _ = std::fs::remove_file(&paths.responses);
+}
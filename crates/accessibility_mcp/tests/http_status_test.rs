@@ -0,0 +1,73 @@
+//! Checks that HTTP responses carry a status code matching the MCP envelope
+
+mod common;
+
+use accessibility_mcp::Config;
+use common::{start_server, start_server_with_config, StaticProvider};
+use serde_json::json;
+
+#[test]
+fn error_envelopes_use_matching_http_status() {
+    let (runtime, handle) = start_server(StaticProvider::new());
+    let url = format!("http://127.0.0.1:{}/mcp", handle.port);
+
+    runtime.block_on(async {
+        let http = reqwest::Client::new();
+        let post = |body: serde_json::Value| http.post(&url).json(&body).send();
+
+        let found = post(json!({"protocol_version": "1.0", "method": "get_node", "node_id": "ok"}))
+            .await
+            .unwrap();
+        assert_eq!(found.status(), 200);
+
+        let missing =
+            post(json!({"protocol_version": "1.0", "method": "get_node", "node_id": "gone"}))
+                .await
+                .unwrap();
+        assert_eq!(missing.status(), 404);
+        let body: serde_json::Value = missing.json().await.unwrap();
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["error"]["code"], "not_found");
+
+        let wrong_version =
+            post(json!({"protocol_version": "9.9", "method": "get_node", "node_id": "ok"}))
+                .await
+                .unwrap();
+        assert_eq!(wrong_version.status(), 400);
+
+        let unknown = post(json!({"protocol_version": "1.0", "method": "fly_to_moon"}))
+            .await
+            .unwrap();
+        assert_eq!(unknown.status(), 400);
+        let body: serde_json::Value = unknown.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "invalid_request");
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(message.starts_with("unknown method 'fly_to_moon'; supported: ["));
+    });
+
+    handle.shutdown();
+}
+
+#[test]
+fn unspecified_bind_addr_accepts_loopback_connections() {
+    let config = Config {
+        bind_addr: Some("0.0.0.0".parse().unwrap()),
+        ..Default::default()
+    };
+    let (runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+    let url = format!("http://127.0.0.1:{}/mcp", handle.port);
+
+    runtime.block_on(async {
+        let response = reqwest::Client::new()
+            .post(&url)
+            .json(&json!({"method": "get_node", "node_id": "ok"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["result"]["node"]["name"], "OK");
+    });
+
+    handle.shutdown();
+}
@@ -8,6 +8,10 @@ fn test_request_serialization() {
     let request = Request::QueryTree {
         max_depth: Some(5),
         max_nodes: Some(100),
+        fields: None,
+        order: TraversalOrder::default(),
+        window_id: None,
+        deadline_ms: None,
     };
 
     let message = Message::request(request);
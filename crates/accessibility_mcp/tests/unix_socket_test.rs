@@ -0,0 +1,165 @@
+//! Exercises the newline-delimited JSON transport over a Unix socket
+
+#![cfg(unix)]
+
+mod common;
+
+use accessibility_mcp::Config;
+use common::{start_server_with_config, StaticProvider};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+#[test]
+fn serves_requests_and_removes_socket_on_shutdown() {
+    let path = std::env::temp_dir().join(format!("a11y_mcp_test_{}.sock", std::process::id()));
+    let config = Config {
+        unix_socket: Some(path.clone()),
+        ..Default::default()
+    };
+    let (runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+    assert_eq!(handle.socket_path.as_deref(), Some(path.as_path()));
+
+    runtime.block_on(async {
+        let stream = UnixStream::connect(&path).await.expect("connect");
+        let (read, mut write) = stream.into_split();
+        write
+            .write_all(
+                b"{\"protocol_version\":\"1.0\",\"method\":\"get_node\",\"node_id\":\"ok\"}\n",
+            )
+            .await
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(read).read_line(&mut line).await.unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["status"], "success");
+        assert_eq!(reply["result"]["node"]["name"], "OK");
+    });
+
+    handle.shutdown();
+    runtime.block_on(async {
+        for _ in 0..50 {
+            if !path.exists() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("socket file should be removed on shutdown");
+    });
+}
+
+#[test]
+fn shutdown_and_wait_returns_once_the_socket_is_removed() {
+    let path = std::env::temp_dir().join(format!("a11y_mcp_wait_{}.sock", std::process::id()));
+    let config = Config {
+        unix_socket: Some(path.clone()),
+        ..Default::default()
+    };
+    let (runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+    assert!(path.exists());
+
+    runtime.block_on(handle.shutdown_and_wait());
+    assert!(
+        !path.exists(),
+        "socket file should be gone once shutdown completes"
+    );
+}
+
+/// Request the "ok" node; `Null` if the server closed the connection first
+async fn get_ok(stream: UnixStream) -> serde_json::Value {
+    let (read, mut write) = stream.into_split();
+    // A rejected connection may already be closed, failing the write
+    let request = b"{\"protocol_version\":\"1.0\",\"method\":\"get_node\",\"node_id\":\"ok\"}\n";
+    let _ = write.write_all(request).await;
+    let mut line = String::new();
+    match BufReader::new(read).read_line(&mut line).await {
+        Ok(n) if n > 0 => serde_json::from_str(&line).unwrap(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+#[test]
+fn rejects_connections_over_the_limit() {
+    let path = std::env::temp_dir().join(format!("a11y_mcp_limit_{}.sock", std::process::id()));
+    let config = Config {
+        unix_socket: Some(path.clone()),
+        max_connections: Some(1),
+        ..Default::default()
+    };
+    let (runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+
+    runtime.block_on(async {
+        // Occupy the only slot and make sure the server has accepted it
+        let first = UnixStream::connect(&path).await.expect("connect");
+        let (read, mut write) = first.into_split();
+        let mut first_reader = BufReader::new(read);
+        write
+            .write_all(
+                b"{\"protocol_version\":\"1.0\",\"method\":\"get_node\",\"node_id\":\"ok\"}\n",
+            )
+            .await
+            .unwrap();
+        let mut line = String::new();
+        first_reader.read_line(&mut line).await.unwrap();
+
+        // The excess connection gets a busy error and is closed
+        let second = UnixStream::connect(&path).await.expect("connect");
+        let mut reader = BufReader::new(second);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["status"], "error");
+        assert_eq!(reply["error"]["code"], "transient");
+        assert_eq!(reply["error"]["message"], "server busy");
+        line.clear();
+        assert_eq!(
+            reader.read_line(&mut line).await.unwrap(),
+            0,
+            "should be closed"
+        );
+
+        // Closing the first connection frees its slot
+        drop(write);
+        drop(first_reader);
+        for _ in 0..50 {
+            let stream = UnixStream::connect(&path).await.expect("connect");
+            let reply = get_ok(stream).await;
+            if reply["status"] == "success" {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("slot should be released after the first connection closes");
+    });
+
+    handle.shutdown();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn serves_requests_on_abstract_socket() {
+    use std::os::linux::net::SocketAddrExt;
+
+    let path = std::env::temp_dir().join(format!("a11y_mcp_abstract_{}.sock", std::process::id()));
+    let config = Config {
+        unix_socket: Some(path.clone()),
+        abstract_socket: true,
+        ..Default::default()
+    };
+    let (runtime, handle) = start_server_with_config(StaticProvider::new(), config);
+    assert_eq!(handle.socket_path.as_deref(), Some(path.as_path()));
+    assert!(!path.exists(), "no socket file should be created");
+
+    runtime.block_on(async {
+        let name = path.to_str().unwrap().as_bytes();
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name).unwrap();
+        let stream = std::os::unix::net::UnixStream::connect_addr(&addr).expect("connect");
+        stream.set_nonblocking(true).unwrap();
+
+        let reply = get_ok(UnixStream::from_std(stream).unwrap()).await;
+        assert_eq!(reply["status"], "success");
+        assert_eq!(reply["result"]["node"]["name"], "OK");
+    });
+
+    handle.shutdown();
+}
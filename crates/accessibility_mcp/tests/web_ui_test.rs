@@ -0,0 +1,45 @@
+//! Checks the browser inspector page served at the HTTP root
+
+mod common;
+
+use common::{start_server, StaticProvider};
+use serde_json::json;
+
+#[test]
+fn root_serves_an_inspector_page_backed_by_mcp() {
+    let (runtime, handle) = start_server(StaticProvider::new());
+    let base = format!("http://127.0.0.1:{}", handle.port);
+
+    runtime.block_on(async {
+        let http = reqwest::Client::new();
+
+        let page = http.get(format!("{}/", base)).send().await.unwrap();
+        assert_eq!(page.status(), 200);
+        let content_type = page.headers()["content-type"].to_str().unwrap().to_string();
+        assert!(content_type.starts_with("text/html"), "{content_type}");
+        let html = page.text().await.unwrap();
+        assert!(html.contains(r#"fetch("/mcp""#));
+        assert!(html.contains(r#"const PROTOCOL_VERSION = "1.0";"#));
+
+        // The request the page makes
+        let tree: serde_json::Value = http
+            .post(format!("{}/mcp", base))
+            .json(&json!({"protocol_version": "1.0", "method": "query_tree"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(tree["status"], "success");
+        let names: Vec<_> = tree["result"]["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Demo", "OK", "Cancel"]);
+    });
+
+    handle.shutdown();
+}
@@ -432,4 +432,36 @@ mod accesskit_tests {
             nodes.len()
         );
     }
+
+    #[tokio::test]
+    #[ignore] // Run with: cargo test -- --ignored
+    #[serial]
+    async fn test_menu_bar_lists_top_level_menus() {
+        let app = TestApp::start().await;
+
+        let response = app
+            .send_request(json!({
+                "protocol_version": "1.0",
+                "method": "get_menu_bar",
+                "max_depth": 1
+            }))
+            .await;
+        let nodes = response["result"]["nodes"]
+            .as_array()
+            .expect("get_menu_bar should return a tree");
+
+        assert_eq!(nodes[0]["role"], "AXMenuBar");
+        let titles: Vec<_> = nodes
+            .iter()
+            .filter(|n| n["role"] == "AXMenuBarItem")
+            .filter_map(|n| n["name"].as_str())
+            .collect();
+        // Every macOS menu bar starts with the Apple menu, then the app menu
+        assert!(
+            titles.len() >= 2,
+            "Expected the Apple and application menus, found {:?}",
+            titles
+        );
+        assert_eq!(titles[0], "Apple");
+    }
 }